@@ -1,14 +1,175 @@
 use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
 use anchor_lang::solana_program::clock::Clock;
-use anchor_spl::token::{self, Token, TokenAccount, Transfer};
+use anchor_lang::solana_program::ed25519_program;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::log::sol_log_compute_units;
+use anchor_lang::solana_program::program::invoke;
+use anchor_lang::solana_program::sysvar::{
+    self,
+    instructions::{load_current_index_checked, load_instruction_at_checked},
+};
+use anchor_spl::token::{self, Burn, Mint, MintTo, Token, TokenAccount, Transfer};
 
 declare_id!("N36WGuo9LKUWeDBCKPcmrW8ykCgECxQsMqxzaVdzQmg");
 
+/// Maximum accepted ZK proof size in bytes; larger proofs are rejected before verification.
+pub const MAX_PROOF_LEN: usize = 512;
+/// Circuit proving ownership of staked or revealed collateral.
+pub const CIRCUIT_COLLATERAL: u8 = 0;
+/// Circuit proving a borrower stays solvent after a new borrow.
+pub const CIRCUIT_SOLVENCY: u8 = 1;
+/// Circuit proving a position is eligible for liquidation.
+pub const CIRCUIT_LIQUIDATION: u8 = 2;
+/// Number of circuit slots in the `VerifyingKeyRegistry`.
+pub const MAX_CIRCUITS: usize = 8;
+/// Reward boost earned per full day of lender tenure, in basis points.
+pub const REWARD_BOOST_BPS_PER_DAY: u64 = 10;
+/// Maximum tenure boost applied to lender rewards, in basis points (e.g., 50%).
+pub const MAX_REWARD_BOOST_BPS: u64 = 5_000;
+/// Governance proposal type that lists a new collateral asset.
+pub const PROPOSAL_TYPE_ADD_COLLATERAL_ASSET: u8 = 1;
+/// Governance proposal type that sets `base_interest_rate`.
+pub const PROPOSAL_TYPE_BASE_INTEREST_RATE: u8 = 2;
+/// Governance proposal type that sets `min_collateral_ratio`, in basis points.
+pub const PROPOSAL_TYPE_MIN_COLLATERAL_RATIO: u8 = 3;
+/// Governance proposal type that sets `min_collateral_lock_time`, in seconds.
+pub const PROPOSAL_TYPE_MIN_COLLATERAL_LOCK_TIME: u8 = 4;
+/// Governance proposal type that approves a realized loss of `new_value` for insurance claims.
+pub const PROPOSAL_TYPE_LOSS_EVENT: u8 = 5;
+/// Governance proposal type that approves deploying up to `new_value` of treasury reserves as
+/// protocol-owned liquidity.
+pub const PROPOSAL_TYPE_TREASURY_LIQUIDITY: u8 = 6;
+/// Governance proposal type that turns borrow fees on (`new_value` 1) or off (`new_value` 0).
+pub const PROPOSAL_TYPE_FEE_SWITCH: u8 = 7;
+/// How long a governance proposal stays open for voting, in seconds (e.g., 7 days).
+pub const PROPOSAL_VOTING_PERIOD: i64 = 604_800;
+/// Net votes a proposal needs to pass.
+pub const PROPOSAL_QUORUM: u64 = 1;
+/// Proposal still open for voting without enough votes to pass.
+pub const PROPOSAL_STATE_ACTIVE: u8 = 0;
+/// Proposal has enough votes and awaits execution.
+pub const PROPOSAL_STATE_PASSED: u8 = 1;
+/// Proposal has been executed.
+pub const PROPOSAL_STATE_EXECUTED: u8 = 2;
+/// Voting ended without the proposal passing.
+pub const PROPOSAL_STATE_EXPIRED: u8 = 3;
+/// Number of parameter changes retained by the `ParamChangeLog` ring buffer.
+pub const PARAM_CHANGE_LOG_CAPACITY: usize = 32;
+/// Minimum slots between marking a position liquidatable and executing the liquidation.
+pub const LIQUIDATION_DELAY_SLOTS: u64 = 10;
+/// Slots after which a liquidation mark expires and must be re-marked.
+pub const LIQUIDATION_MARK_EXPIRY_SLOTS: u64 = 150;
+/// Default bonus included in seized collateral on liquidation, in basis points of the debt
+/// covered. Applies to collateral pools that don't set their own.
+pub const LIQUIDATION_BONUS_BPS: u64 = 500;
+/// Largest premium a recapitalization bid may earn on the bad debt it covers, in basis points.
+pub const MAX_RECAP_PREMIUM_BPS: u16 = 2_000;
+/// Most borrow-and-stake rounds a single `loop_borrow` may run.
+pub const MAX_BORROW_LOOPS: u8 = 5;
+/// Highest collateral multiple `loop_borrow` may build over the collateral staked before
+/// looping, in basis points (e.g., 3x).
+pub const MAX_LOOP_LEVERAGE_BPS: u64 = 30_000;
+/// Largest liquidation bonus a collateral pool may set, in basis points.
+pub const MAX_LIQUIDATION_BONUS_BPS: u16 = 2_000;
+/// Collateral pool without a price oracle; its collateral is valued at par with the borrow asset.
+pub const ORACLE_KIND_NONE: u8 = 0;
+/// Collateral pool priced by a Pyth v2 price account.
+pub const ORACLE_KIND_PYTH: u8 = 1;
+/// Collateral pool priced by a Switchboard v2 aggregator account.
+pub const ORACLE_KIND_SWITCHBOARD: u8 = 2;
+/// Normalized oracle price of 1.0; prices carry 6 decimal places.
+pub const ORACLE_PRICE_ONE: u64 = 1_000_000;
+/// Oldest oracle price accepted, in seconds.
+pub const MAX_ORACLE_STALENESS: i64 = 60;
+/// Price side for borrows: collateral valued at `price - k * confidence`.
+pub const PRICE_SIDE_BORROW: u8 = 0;
+/// Price side for liquidations: debt valued at `price + k * confidence`.
+pub const PRICE_SIDE_LIQUIDATION: u8 = 1;
+/// Fee on flash loans, in basis points of the amount borrowed.
+pub const FLASH_LOAN_FEE_BPS: u64 = 9;
+/// Default confidence multiplier `k`, in basis points (10_000 = 1x).
+pub const DEFAULT_CONFIDENCE_MULTIPLIER_BPS: u16 = 10_000;
+/// Health factor of exactly 1.0 in basis points: collateral sits exactly at the minimum ratio.
+pub const HEALTH_FACTOR_ONE: u64 = 10_000;
+/// Maximum number of borrow fee tiers on `ProtocolState`.
+pub const MAX_FEE_TIERS: usize = 4;
+/// Maximum number of members on the standard borrow whitelist.
+pub const MAX_BORROW_WHITELIST_MEMBERS: usize = 64;
+/// Maximum number of liquidation grace tiers on `ProtocolState`.
+pub const MAX_GRACE_TIERS: usize = 4;
+/// Maximum number of reputation-based collateral lock tiers on `ProtocolState`.
+pub const MAX_LOCK_TIERS: usize = 4;
+/// Maximum number of collateral assets the `AssetRegistry` can list.
+pub const MAX_LISTED_ASSETS: usize = 32;
+/// Seconds a cached solvency snapshot stays fresh.
+pub const SOLVENCY_SNAPSHOT_TTL: i64 = 60;
+/// Loan rate mode that follows the variable rate curve.
+pub const RATE_MODE_VARIABLE: u8 = 0;
+/// Loan rate mode fixed at origination (institutional loans).
+pub const RATE_MODE_FIXED: u8 = 1;
+/// Repayment waterfall that pays the highest-rate loan first.
+pub const REPAY_ORDER_HIGHEST_RATE: u8 = 0;
+/// Repayment waterfall that pays the oldest loan first.
+pub const REPAY_ORDER_OLDEST: u8 = 1;
+/// Term of a fixed-rate institutional loan before it matures and can be rolled over, in seconds
+/// (e.g., 30 days).
+pub const INSTITUTIONAL_LOAN_TERM: i64 = 2_592_000;
+/// Grace after maturity before an unpaid fixed-term loan can be recorded as a missed payment,
+/// in seconds (e.g., 3 days).
+pub const MISSED_PAYMENT_GRACE_PERIOD: i64 = 259_200;
+/// Reputation score deducted from a borrower for each missed payment.
+pub const MISSED_PAYMENT_REPUTATION_PENALTY: u64 = 100;
+/// Percentage points added to a delinquent loan's interest and penalty rates.
+pub const DELINQUENCY_RATE_PREMIUM: u8 = 5;
+/// Window over which lender rewards are tallied for the realized supply APY, in seconds
+/// (e.g., 7 days).
+pub const APY_WINDOW_SECONDS: i64 = 604_800;
+/// Longest interest-free period after origination, in seconds (e.g., 7 days).
+pub const MAX_INTEREST_FREE_SECONDS: i64 = 604_800;
+/// Upfront fee for locking a variable rate, in basis points of principal per 30 days locked.
+pub const RATE_LOCK_FEE_BPS_PER_30_DAYS: u64 = 25;
+/// Longest period a variable rate can be locked for, in seconds (e.g., 1 year).
+pub const MAX_RATE_LOCK_DURATION: i64 = 31_536_000;
+/// Byte offset of `utilization_rate` in a serialized `ProtocolState`.
+pub const PROTOCOL_STATE_UTILIZATION_OFFSET: usize = 8 + 8 + 8 + 8 + 1;
+/// Byte offset of `admin` in a legacy `ProtocolState` with a `u8` utilization rate.
+pub const LEGACY_PROTOCOL_STATE_ADMIN_OFFSET: usize = PROTOCOL_STATE_UTILIZATION_OFFSET + 1 + 8 + 2;
+/// Size of a legacy `ProtocolState` with a `u8` utilization rate.
+pub const LEGACY_PROTOCOL_STATE_LEN: usize =
+    8 + 8 + 8 + 8 + 1 + 1 + 8 + 2 + 32 + (8 + 2) * MAX_FEE_TIERS + 1 + 1 + 1 + 1 + 1;
+/// Size of a current `ProtocolState`, including its discriminator.
+pub const PROTOCOL_STATE_LEN: usize = 8 + 8 + 8 + 8 + 1 + 2 + 8 + 2 + 32
+    + (8 + 2) * MAX_FEE_TIERS + 1 + 1 + 1 + 1 + 1 + 2 + 1
+    + (2 + 8) * MAX_GRACE_TIERS + 1 + 32 + 8 + 4 + 2 + 8 + 2 + 2 + 1 + 1 + 8 + 2
+    + (8 + 8) * MAX_LOCK_TIERS + 1 + 1 + 8 + 8 + 2 + 8 + 1 + 8;
+/// Collateral below this amount on a debt-free account is dust that can be swept back.
+pub const DUST_COLLATERAL_THRESHOLD: u64 = 1_000;
+/// Keeper incentive for accruing a loan's interest, in basis points of the interest accrued.
+pub const ACCRUAL_INCENTIVE_BPS: u64 = 500;
+/// Cap on the keeper incentive paid for a single accrual.
+pub const MAX_ACCRUAL_INCENTIVE: u64 = 10_000;
+/// Borrow fee discount while collateral is under a stake lock-up, in basis points of the fee.
+pub const STAKE_LOCK_FEE_DISCOUNT_BPS: u64 = 2_500;
+/// Penalty on collateral withdrawn before a stake lock-up ends, in basis points.
+pub const EARLY_WITHDRAW_PENALTY_BPS: u16 = 500;
+/// Longest stake lock-up a borrower can opt into, in seconds (e.g., 1 year).
+pub const MAX_STAKE_LOCK_DURATION: i64 = 31_536_000;
+/// Fixed-point scale (1e18) for interest math.
+pub const RAY: u128 = 1_000_000_000_000_000_000;
+/// Fee for refinancing a loan onto the variable curve, in basis points of principal.
+pub const REFINANCE_FEE_BPS: u64 = 10;
+/// Utilization moved from at or below `optimal_utilization` to above it.
+pub const KINK_DIRECTION_UP: u8 = 0;
+/// Utilization moved from above `optimal_utilization` to at or below it.
+pub const KINK_DIRECTION_DOWN: u8 = 1;
+
 #[program]
 pub mod zk_lending_protocol {
     use super::*;
 
-    /// Initializes the protocol state and treasury.
+    /// Initializes the protocol state and treasury. The state lives at a single canonical PDA,
+    /// so the protocol can only be initialized once.
     pub fn initialize(ctx: Context<Initialize>) -> Result<()> {
         let protocol_state = &mut ctx.accounts.protocol_state;
         protocol_state.total_collateral = 0;
@@ -16,315 +177,634 @@ pub mod zk_lending_protocol {
         protocol_state.total_liquidity = 0;
         protocol_state.base_interest_rate = 5; // e.g., 5% per annum (example)
         protocol_state.utilization_rate = 0;
+        protocol_state.optimal_utilization = 80; // e.g., kink at 80% utilization
+        protocol_state.rate_slope_low = 4;
+        protocol_state.rate_slope_high = 60;
         protocol_state.min_collateral_lock_time = 600; // e.g., 600 seconds = 10 minutes
+        protocol_state.min_collateral_ratio = 15_000; // e.g., 150% in basis points
+        protocol_state.admin = ctx.accounts.user.key();
+        // Single flat tier until configured, e.g., 1% on every borrow.
+        protocol_state.fee_tiers = [FeeTier::default(); MAX_FEE_TIERS];
+        protocol_state.fee_tiers[0] = FeeTier { threshold: 0, bps: 100 };
+        protocol_state.fee_tier_count = 1;
+        protocol_state.borrows_frozen = false;
+        protocol_state.liquidation_protocol_share_bps = 0; // whole bonus to the liquidator
+        protocol_state.liquidity_bootstrapped = false;
+        // Single grace period for every loan size until configured.
+        protocol_state.grace_tiers = [GraceTier::default(); MAX_GRACE_TIERS];
+        protocol_state.grace_tiers[0] = GraceTier {
+            min_pool_share_bps: 0,
+            grace_slots: LIQUIDATION_DELAY_SLOTS,
+        };
+        protocol_state.grace_tier_count = 1;
+        protocol_state.borrow_whitelist = Pubkey::default(); // open borrowing
+        protocol_state.rebalance_min_health = 11_000; // e.g., 10% above the liquidation threshold
+        protocol_state.max_loans_per_borrower = 16;
+        protocol_state.max_protocol_leverage_bps = 0; // unlimited until configured
+        protocol_state.early_repay_window = 0; // no early-repayment rebate until configured
+        protocol_state.early_repay_rebate_bps = 0;
+        protocol_state.confidence_multiplier_bps = DEFAULT_CONFIDENCE_MULTIPLIER_BPS;
+        protocol_state.fees_enabled = true; // governance can switch borrow fees off
+        protocol_state.debug_compute = false; // compute profiling is for devnet only
+        protocol_state.interest_free_seconds = 0; // interest accrues from origination
+        protocol_state.max_utilization = 0; // uncapped until configured
+        // Flat `min_collateral_lock_time` for every borrower until reputation tiers are set.
+        protocol_state.lock_tiers = [LockTier::default(); MAX_LOCK_TIERS];
+        protocol_state.lock_tier_count = 0;
+        protocol_state.overdue_penalty_rate = 0; // overdue loans keep their contracted rate
+        protocol_state.stake_settle_time = 0; // staked collateral can back a borrow immediately
+        protocol_state.bad_debt = 0;
+        protocol_state.recap_premium_bps = 0; // recapitalization bids earn no premium until set
+        protocol_state.liquidator_bonus_floor = 0; // no liquidator rebates until configured
+        protocol_state.repay_order = REPAY_ORDER_HIGHEST_RATE;
+        protocol_state.min_proposal_bond = 0; // proposals need no bond until configured
 
         let treasury = &mut ctx.accounts.protocol_treasury;
         treasury.total_fees_collected = 0;
         treasury.governance_fund = 0;
+        treasury.reserve_cap = u64::MAX; // uncapped until configured
+        treasury.insurance_fund = 0;
+        treasury.liquidator_rebate_pool = 0;
         Ok(())
     }
 
-    /// Stake collateral into a specific collateral pool.
-    pub fn stake_collateral(
-        ctx: Context<StakeCollateral>,
-        amount: u64,
-        zk_proof: Vec<u8>,
-    ) -> Result<()> {
-        // Validate proof (placeholder).
-        require!(verify_zk_proof(&zk_proof), ZKError::InvalidProof);
+    /// Creates the signer's borrower account.
+    pub fn initialize_borrower_account(ctx: Context<InitializeBorrowerAccount>) -> Result<()> {
+        let borrower_account = &mut ctx.accounts.borrower_account;
+        borrower_account.owner = ctx.accounts.owner.key();
+        borrower_account.encrypted_collateral = reset_encryption();
+        borrower_account.encrypted_principal = reset_encryption();
+        Ok(())
+    }
 
-        // Transfer collateral tokens from user to collateral pool escrow.
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.user_collateral_account.to_account_info(),
-            to: ctx.accounts.collateral_pool_token_account.to_account_info(),
-            authority: ctx.accounts.borrower.to_account_info(),
-        };
-        token::transfer(
-            CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
-            amount,
-        )?;
+    /// Admin: register the custodian whose attestations back staking into a collateral pool.
+    pub fn set_custodian_key(ctx: Context<SetCustodianKey>, custodian_key: Pubkey) -> Result<()> {
+        ctx.accounts.collateral_pool.custodian_key = custodian_key;
+        Ok(())
+    }
 
-        // Update the borrower's encrypted collateral.
-        let borrower_account = &mut ctx.accounts.borrower_account;
-        borrower_account.encrypted_collateral = update_encrypted_value(
-            borrower_account.encrypted_collateral.clone(),
-            amount,
-            true,
-        );
+    /// Admin: set the smallest stake a collateral pool accepts; zero accepts any amount.
+    pub fn set_min_stake(ctx: Context<SetMinStake>, min_stake: u64) -> Result<()> {
+        ctx.accounts.collateral_pool.min_stake = min_stake;
+        Ok(())
+    }
 
-        // Update collateral pool state.
+    /// Admin: schedule a collateral pool's effective collateral factor to decline by
+    /// `decay_bps_per_day` each day from `decay_start`, down to `min_collateral_factor`, shrinking
+    /// borrowing power against it. A zero rate removes the schedule.
+    pub fn set_collateral_factor_decay(
+        ctx: Context<SetCollateralFactorDecay>,
+        decay_start: i64,
+        decay_bps_per_day: u16,
+        min_collateral_factor: u16,
+    ) -> Result<()> {
         let collateral_pool = &mut ctx.accounts.collateral_pool;
-        collateral_pool.total_collateral = collateral_pool
-            .total_collateral
-            .checked_add(amount)
-            .ok_or(ZKError::MathOverflow)?;
+        require!(
+            min_collateral_factor <= collateral_pool.collateral_factor,
+            ZKError::InvalidCollateralParams
+        );
+        collateral_pool.factor_decay_start = decay_start;
+        collateral_pool.factor_decay_bps_per_day = decay_bps_per_day;
+        collateral_pool.min_collateral_factor = min_collateral_factor;
         Ok(())
     }
 
-    /// Normal borrowing instruction with flash loan protection and treasury fee collection.
-    pub fn borrow(
-        ctx: Context<Borrow>,
-        amount: u64,
-        zk_proof: Vec<u8>,
+    /// Admin: attach a yield index to a collateral pool, starting it at `base_index`.
+    pub fn set_collateral_index(
+        ctx: Context<SetCollateralIndex>,
+        index_account: Pubkey,
+        base_index: u64,
     ) -> Result<()> {
-        // Verify ZK proof.
-        require!(verify_zk_proof(&zk_proof), ZKError::InvalidProof);
+        require!(base_index > 0, ZKError::InvalidCollateralIndex);
+        let collateral_pool = &mut ctx.accounts.collateral_pool;
+        collateral_pool.index_account = index_account;
+        collateral_pool.base_index = base_index;
+        collateral_pool.current_index = base_index;
+        Ok(())
+    }
 
-        let clock = Clock::get()?;
-        let now = clock.unix_timestamp;
-        let borrower_account = &mut ctx.accounts.borrower_account;
-        let protocol_state = &mut ctx.accounts.protocol_state;
+    /// Admin: price a collateral pool with a Pyth or Switchboard oracle account, or at par with
+    /// `ORACLE_KIND_NONE`.
+    pub fn set_collateral_oracle(
+        ctx: Context<SetCollateralOracle>,
+        oracle_kind: u8,
+        oracle: Pubkey,
+    ) -> Result<()> {
+        require!(oracle_kind <= ORACLE_KIND_SWITCHBOARD, ZKError::InvalidOracle);
+        let collateral_pool = &mut ctx.accounts.collateral_pool;
+        collateral_pool.oracle_kind = oracle_kind;
+        collateral_pool.oracle = oracle;
+        Ok(())
+    }
 
-        // Flash loan protection: if already borrowed, require minimum lock time.
-        if borrower_account.borrow_timestamp > 0 {
-            require!(
-                now - borrower_account.borrow_timestamp >= protocol_state.min_collateral_lock_time,
-                ZKError::CollateralLockTimeNotMet
-            );
-        }
-        // Set the borrow timestamp.
-        borrower_account.borrow_timestamp = now;
+    /// Admin: set how many oracle confidence intervals (`k`, in basis points) prices are
+    /// shaded by when borrowing and liquidating.
+    pub fn set_confidence_multiplier(
+        ctx: Context<SetConfidenceMultiplier>,
+        confidence_multiplier_bps: u16,
+    ) -> Result<()> {
+        ctx.accounts.protocol_state.confidence_multiplier_bps = confidence_multiplier_bps;
+        Ok(())
+    }
 
-        // Check encrypted collateral sufficiency.
+    /// Admin: set how long after origination a loan accrues no interest, in seconds.
+    pub fn set_interest_free_period(
+        ctx: Context<SetInterestFreePeriod>,
+        interest_free_seconds: i64,
+    ) -> Result<()> {
         require!(
-            has_sufficient_collateral(
-                borrower_account.encrypted_collateral.clone(),
-                amount
-            ),
-            ZKError::InsufficientCollateral
+            (0..=MAX_INTEREST_FREE_SECONDS).contains(&interest_free_seconds),
+            ZKError::InvalidInterestFreePeriod
         );
+        ctx.accounts.protocol_state.interest_free_seconds = interest_free_seconds;
+        Ok(())
+    }
 
-        // Deduct a borrow fee (e.g., 1%).
-        let fee = amount.checked_div(100).ok_or(ZKError::MathOverflow)?;
-        let net_amount = amount.checked_sub(fee).ok_or(ZKError::MathOverflow)?;
-
-        // Transfer tokens from lending pool escrow to borrower.
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.lending_pool_token_account.to_account_info(),
-            to: ctx.accounts.user_borrow_token_account.to_account_info(),
-            authority: ctx.accounts.lending_pool_authority.to_account_info(),
-        };
-        token::transfer(
-            CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
-            net_amount,
-        )?;
+    /// Admin: set the annual rate, in percent, that fixed-term loans accrue once past maturity;
+    /// zero keeps the loan's own rate. Applies to loans opened or rolled over afterwards.
+    pub fn set_overdue_penalty_rate(
+        ctx: Context<SetOverduePenaltyRate>,
+        overdue_penalty_rate: u8,
+    ) -> Result<()> {
+        ctx.accounts.protocol_state.overdue_penalty_rate = overdue_penalty_rate;
+        Ok(())
+    }
 
-        // Update treasury with collected fee.
-        let treasury = &mut ctx.accounts.protocol_treasury;
-        treasury.total_fees_collected = treasury
-            .total_fees_collected
-            .checked_add(fee)
-            .ok_or(ZKError::MathOverflow)?;
+    /// Admin: set how long, in seconds, newly staked collateral must settle before the borrower
+    /// can borrow, so collateral can't be staked and borrowed against in the same transaction.
+    pub fn set_stake_settle_time(
+        ctx: Context<SetStakeSettleTime>,
+        stake_settle_time: i64,
+    ) -> Result<()> {
+        require!(stake_settle_time >= 0, ZKError::InvalidStakeSettleTime);
+        ctx.accounts.protocol_state.stake_settle_time = stake_settle_time;
+        Ok(())
+    }
 
-        // Update the borrower's encrypted borrowed amount.
-        borrower_account.encrypted_borrowed = update_encrypted_value(
-            borrower_account.encrypted_borrowed.clone(),
-            amount, // principal (before fee)
-            true,
+    /// Admin: set the premium, in basis points, that bad-debt auction bids earn on top of the
+    /// debt they cover.
+    pub fn set_recap_premium(ctx: Context<SetRecapPremium>, recap_premium_bps: u16) -> Result<()> {
+        require!(
+            recap_premium_bps <= MAX_RECAP_PREMIUM_BPS,
+            ZKError::InvalidRecapPremium
         );
+        ctx.accounts.protocol_state.recap_premium_bps = recap_premium_bps;
+        Ok(())
+    }
 
-        // Update protocol state.
-        protocol_state.total_loans = protocol_state
-            .total_loans
-            .checked_add(amount)
-            .ok_or(ZKError::MathOverflow)?;
-        protocol_state.total_liquidity = protocol_state
-            .total_liquidity
-            .checked_sub(amount)
-            .ok_or(ZKError::MathOverflow)?;
-        protocol_state.utilization_rate =
-            calculate_utilization(protocol_state.total_loans, protocol_state.total_liquidity);
+    /// Admin: toggle compute-unit logging around proof verification and interest accrual, for
+    /// profiling on devnet. Leave off in production; the markers cost compute themselves.
+    pub fn set_debug_compute(ctx: Context<SetDebugCompute>, enabled: bool) -> Result<()> {
+        ctx.accounts.protocol_state.debug_compute = enabled;
+        Ok(())
+    }
 
+    /// Admin: set a collateral pool's own liquidation bonus; zero falls back to the protocol default.
+    pub fn set_liquidation_bonus(ctx: Context<SetLiquidationBonus>, bonus_bps: u16) -> Result<()> {
+        require!(
+            bonus_bps <= MAX_LIQUIDATION_BONUS_BPS,
+            ZKError::InvalidLiquidationBonus
+        );
+        ctx.accounts.collateral_pool.liquidation_bonus_bps = bonus_bps;
         Ok(())
     }
 
-    /// Institutional borrowing instruction that checks a whitelist and applies a fixed interest rate.
-    pub fn institutional_borrow(
-        ctx: Context<InstitutionalBorrow>,
-        amount: u64,
-        zk_proof: Vec<u8>,
+    /// Index account: publish the current exchange-rate index of a liquid staking collateral.
+    pub fn update_collateral_index(
+        ctx: Context<UpdateCollateralIndex>,
+        current_index: u64,
     ) -> Result<()> {
-        require!(verify_zk_proof(&zk_proof), ZKError::InvalidProof);
+        let collateral_pool = &mut ctx.accounts.collateral_pool;
+        require!(
+            collateral_pool.base_index > 0 && current_index > 0,
+            ZKError::InvalidCollateralIndex
+        );
+        collateral_pool.current_index = current_index;
+        Ok(())
+    }
 
-        let clock = Clock::get()?;
-        let now = clock.unix_timestamp;
-        let borrower_account = &mut ctx.accounts.borrower_account;
-        let protocol_state = &mut ctx.accounts.protocol_state;
-        let institutional_pool = &ctx.accounts.institutional_pool;
+    /// Admin: cap the treasury reserve; fees above the cap flow to the governance fund.
+    pub fn set_reserve_cap(ctx: Context<SetReserveCap>, reserve_cap: u64) -> Result<()> {
+        ctx.accounts.protocol_treasury.reserve_cap = reserve_cap;
+        Ok(())
+    }
 
-        // Check that the borrower is whitelisted.
+    /// Admin: set the share of the liquidation bonus diverted to the protocol treasury.
+    pub fn set_liquidation_protocol_share(
+        ctx: Context<SetLiquidationProtocolShare>,
+        liquidation_protocol_share_bps: u16,
+    ) -> Result<()> {
         require!(
-            institutional_pool.zk_whitelist.contains(&ctx.accounts.borrower.key()),
-            ZKError::UnauthorizedBorrower
+            liquidation_protocol_share_bps <= 10_000,
+            ZKError::InvalidLiquidationShare
         );
+        ctx.accounts.protocol_state.liquidation_protocol_share_bps = liquidation_protocol_share_bps;
+        Ok(())
+    }
 
-        // Flash loan protection.
-        if borrower_account.borrow_timestamp > 0 {
-            require!(
-                now - borrower_account.borrow_timestamp >= protocol_state.min_collateral_lock_time,
-                ZKError::CollateralLockTimeNotMet
-            );
-        }
-        borrower_account.borrow_timestamp = now;
+    /// Admin: restrict `borrow` to a whitelist account's members, or reopen it with the
+    /// default pubkey.
+    pub fn set_borrow_whitelist(
+        ctx: Context<SetBorrowWhitelist>,
+        borrow_whitelist: Pubkey,
+    ) -> Result<()> {
+        ctx.accounts.protocol_state.borrow_whitelist = borrow_whitelist;
+        Ok(())
+    }
+
+    /// Admin: register the verifying key that proofs for `circuit_id` are checked against.
+    pub fn register_circuit(
+        ctx: Context<RegisterCircuit>,
+        circuit_id: u8,
+        key: [u8; 32],
+    ) -> Result<()> {
+        require!((circuit_id as usize) < MAX_CIRCUITS, ZKError::CircuitNotRegistered);
+        ctx.accounts.verifying_key_registry.keys[circuit_id as usize] = key;
+        Ok(())
+    }
 
-        // (For institutional pools, you may choose to use a fixed interest rate later.)
+    /// Admin: replace the members of the standard borrow whitelist.
+    pub fn set_borrow_whitelist_members(
+        ctx: Context<SetBorrowWhitelistMembers>,
+        members: Vec<Pubkey>,
+    ) -> Result<()> {
         require!(
-            has_sufficient_collateral(
-                borrower_account.encrypted_collateral.clone(),
-                amount
-            ),
-            ZKError::InsufficientCollateral
+            members.len() <= MAX_BORROW_WHITELIST_MEMBERS,
+            ZKError::WhitelistFull
         );
+        ctx.accounts.borrow_whitelist.members = members;
+        Ok(())
+    }
 
-        // Deduct borrow fee.
-        let fee = amount.checked_div(100).ok_or(ZKError::MathOverflow)?;
-        let net_amount = amount.checked_sub(fee).ok_or(ZKError::MathOverflow)?;
+    /// Admin: set the minimum health factor a collateral reduction must leave, in basis points.
+    pub fn set_rebalance_min_health(
+        ctx: Context<SetRebalanceMinHealth>,
+        rebalance_min_health: u64,
+    ) -> Result<()> {
+        require!(
+            rebalance_min_health > HEALTH_FACTOR_ONE,
+            ZKError::InvalidHealthTarget
+        );
+        ctx.accounts.protocol_state.rebalance_min_health = rebalance_min_health;
+        Ok(())
+    }
 
-        // Transfer tokens.
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.lending_pool_token_account.to_account_info(),
-            to: ctx.accounts.user_borrow_token_account.to_account_info(),
-            authority: ctx.accounts.lending_pool_authority.to_account_info(),
-        };
-        token::transfer(
-            CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
-            net_amount,
-        )?;
+    /// Admin: cap how many loans a single borrower account may have open at once.
+    pub fn set_max_loans_per_borrower(
+        ctx: Context<SetMaxLoansPerBorrower>,
+        max_loans_per_borrower: u32,
+    ) -> Result<()> {
+        ctx.accounts.protocol_state.max_loans_per_borrower = max_loans_per_borrower;
+        Ok(())
+    }
 
-        // Update treasury.
-        let treasury = &mut ctx.accounts.protocol_treasury;
-        treasury.total_fees_collected = treasury
-            .total_fees_collected
-            .checked_add(fee)
-            .ok_or(ZKError::MathOverflow)?;
+    /// Admin: cap protocol-wide leverage (`total_loans / total_collateral`, in basis points).
+    pub fn set_max_protocol_leverage(
+        ctx: Context<SetMaxProtocolLeverage>,
+        max_protocol_leverage_bps: u16,
+    ) -> Result<()> {
+        ctx.accounts.protocol_state.max_protocol_leverage_bps = max_protocol_leverage_bps;
+        Ok(())
+    }
 
-        // Update borrower's encrypted borrowed amount.
-        borrower_account.encrypted_borrowed = update_encrypted_value(
-            borrower_account.encrypted_borrowed.clone(),
-            amount,
-            true,
-        );
+    /// Admin: cap the utilization, in percent, that a borrow may push the protocol to.
+    pub fn set_max_utilization(ctx: Context<SetMaxUtilization>, max_utilization: u16) -> Result<()> {
+        ctx.accounts.protocol_state.max_utilization = max_utilization;
+        Ok(())
+    }
 
-        protocol_state.total_loans = protocol_state
-            .total_loans
-            .checked_add(amount)
-            .ok_or(ZKError::MathOverflow)?;
-        protocol_state.total_liquidity = protocol_state
-            .total_liquidity
-            .checked_sub(amount)
-            .ok_or(ZKError::MathOverflow)?;
-        protocol_state.utilization_rate =
-            calculate_utilization(protocol_state.total_loans, protocol_state.total_liquidity);
+    /// Admin: rebate `rebate_bps` of a loan's origination fee when it is repaid within
+    /// `window` seconds of origination. A zero window disables the rebate.
+    pub fn set_early_repay_rebate(
+        ctx: Context<SetEarlyRepayRebate>,
+        window: i64,
+        rebate_bps: u16,
+    ) -> Result<()> {
+        require!(window >= 0 && rebate_bps <= 10_000, ZKError::InvalidEarlyRepayRebate);
+        let protocol_state = &mut ctx.accounts.protocol_state;
+        protocol_state.early_repay_window = window;
+        protocol_state.early_repay_rebate_bps = rebate_bps;
+        Ok(())
+    }
 
+    /// Admin: freeze or unfreeze new borrows; repay, stake, and other flows are unaffected.
+    pub fn set_borrow_freeze(ctx: Context<SetBorrowFreeze>, frozen: bool) -> Result<()> {
+        ctx.accounts.protocol_state.borrows_frozen = frozen;
         Ok(())
     }
 
-    /// Delegated borrowing for DAOs/businesses that assign a credit line.
-    pub fn delegated_borrow(
-        ctx: Context<DelegatedBorrow>,
-        amount: u64,
-        zk_proof: Vec<u8>,
+    /// Admin: enable or disable borrowing from a lending pool; deposits and repayments are unaffected.
+    pub fn set_borrow_enabled(ctx: Context<SetBorrowEnabled>, enabled: bool) -> Result<()> {
+        ctx.accounts.lending_pool.borrow_enabled = enabled;
+        Ok(())
+    }
+
+    /// Admin: set the liquidity a lending pool always keeps back from borrowers for exiting lenders.
+    pub fn set_min_liquidity_buffer(
+        ctx: Context<SetMinLiquidityBuffer>,
+        min_liquidity_buffer: u64,
     ) -> Result<()> {
-        require!(verify_zk_proof(&zk_proof), ZKError::InvalidProof);
+        ctx.accounts.lending_pool.min_liquidity_buffer = min_liquidity_buffer;
+        Ok(())
+    }
 
-        let delegated = &ctx.accounts.delegated_borrower;
-        // Check that the delegate is borrowing on behalf of the delegator.
-        require!(
-            delegated.delegate == ctx.accounts.borrower.key(),
-            ZKError::UnauthorizedBorrower
-        );
+    /// Admin: record the mint a lending pool lends out. It can only be set once.
+    pub fn set_borrow_mint(ctx: Context<SetBorrowMint>, borrow_mint: Pubkey) -> Result<()> {
+        let lending_pool = &mut ctx.accounts.lending_pool;
         require!(
-            amount <= delegated.max_borrow_amount,
-            ZKError::BorrowLimitExceeded
+            lending_pool.borrow_mint == Pubkey::default(),
+            ZKError::BorrowMintAlreadySet
         );
+        lending_pool.borrow_mint = borrow_mint;
+        Ok(())
+    }
 
-        let clock = Clock::get()?;
-        let now = clock.unix_timestamp;
-        let borrower_account = &mut ctx.accounts.borrower_account;
-        let protocol_state = &mut ctx.accounts.protocol_state;
-
-        if borrower_account.borrow_timestamp > 0 {
+    /// Admin: migrate a legacy protocol state with a `u8` utilization rate to the current
+    /// layout, giving every field added since the defaults `initialize` sets.
+    pub fn migrate_protocol_state(ctx: Context<MigrateProtocolState>) -> Result<()> {
+        let protocol_state = &ctx.accounts.protocol_state;
+        let migrated = {
+            let data = protocol_state.try_borrow_data()?;
+            let migrated = migrate_legacy_protocol_state(&data)?;
             require!(
-                now - borrower_account.borrow_timestamp >= protocol_state.min_collateral_lock_time,
-                ZKError::CollateralLockTimeNotMet
+                migrated.admin == ctx.accounts.admin.key(),
+                ZKError::Unauthorized
             );
-        }
-        borrower_account.borrow_timestamp = now;
+            migrated
+        };
 
-        require!(
-            has_sufficient_collateral(
-                borrower_account.encrypted_collateral.clone(),
-                amount
-            ),
-            ZKError::InsufficientCollateral
-        );
+        // Top up rent for the larger layout, then grow the account.
+        let new_len = PROTOCOL_STATE_LEN;
+        let shortfall = Rent::get()?
+            .minimum_balance(new_len)
+            .saturating_sub(protocol_state.lamports());
+        if shortfall > 0 {
+            anchor_lang::system_program::transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    anchor_lang::system_program::Transfer {
+                        from: ctx.accounts.admin.to_account_info(),
+                        to: protocol_state.to_account_info(),
+                    },
+                ),
+                shortfall,
+            )?;
+        }
+        protocol_state.realloc(new_len, false)?;
 
-        let fee = amount.checked_div(100).ok_or(ZKError::MathOverflow)?;
-        let net_amount = amount.checked_sub(fee).ok_or(ZKError::MathOverflow)?;
+        let mut data = protocol_state.try_borrow_mut_data()?;
+        migrated.try_serialize(&mut &mut data[..])
+    }
 
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.lending_pool_token_account.to_account_info(),
-            to: ctx.accounts.user_borrow_token_account.to_account_info(),
-            authority: ctx.accounts.lending_pool_authority.to_account_info(),
-        };
-        token::transfer(
-            CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
-            net_amount,
-        )?;
+    /// Admin: move treasury reserves into the insurance fund backing lender loss claims.
+    pub fn fund_insurance(ctx: Context<FundInsurance>, amount: u64) -> Result<()> {
+        let treasury = &mut ctx.accounts.protocol_treasury;
+        treasury.total_fees_collected = treasury
+            .total_fees_collected
+            .checked_sub(amount)
+            .ok_or(ZKError::InsufficientTreasuryReserves)?;
+        treasury.insurance_fund = treasury
+            .insurance_fund
+            .checked_add(amount)
+            .ok_or(ZKError::MathOverflow)?;
+        Ok(())
+    }
 
+    /// Admin: move treasury reserves into the rebate pool that tops up thin liquidation bonuses.
+    pub fn fund_liquidator_rebates(ctx: Context<FundLiquidatorRebates>, amount: u64) -> Result<()> {
         let treasury = &mut ctx.accounts.protocol_treasury;
         treasury.total_fees_collected = treasury
             .total_fees_collected
-            .checked_add(fee)
+            .checked_sub(amount)
+            .ok_or(ZKError::InsufficientTreasuryReserves)?;
+        treasury.liquidator_rebate_pool = treasury
+            .liquidator_rebate_pool
+            .checked_add(amount)
             .ok_or(ZKError::MathOverflow)?;
+        Ok(())
+    }
 
-        borrower_account.encrypted_borrowed = update_encrypted_value(
-            borrower_account.encrypted_borrowed.clone(),
-            amount,
-            true,
+    /// Admin: set the smallest liquidation bonus, valued in borrow tokens, a liquidator should
+    /// earn; thinner bonuses are topped up from the liquidator rebate pool. Zero disables rebates.
+    pub fn set_liquidator_bonus_floor(
+        ctx: Context<SetLiquidatorBonusFloor>,
+        liquidator_bonus_floor: u64,
+    ) -> Result<()> {
+        ctx.accounts.protocol_state.liquidator_bonus_floor = liquidator_bonus_floor;
+        Ok(())
+    }
+
+    /// Admin: choose the order `repay_all` pays a borrower's loans in, one of the
+    /// `REPAY_ORDER_*` modes.
+    pub fn set_repay_order(ctx: Context<SetRepayOrder>, repay_order: u8) -> Result<()> {
+        require!(
+            repay_order == REPAY_ORDER_HIGHEST_RATE || repay_order == REPAY_ORDER_OLDEST,
+            ZKError::InvalidRepayOrder
         );
+        ctx.accounts.protocol_state.repay_order = repay_order;
+        Ok(())
+    }
 
-        protocol_state.total_loans = protocol_state
-            .total_loans
+    /// Admin: set the smallest bond, in lamports, a proposer must post with `propose_change`.
+    pub fn set_min_proposal_bond(
+        ctx: Context<SetMinProposalBond>,
+        min_proposal_bond: u64,
+    ) -> Result<()> {
+        ctx.accounts.protocol_state.min_proposal_bond = min_proposal_bond;
+        Ok(())
+    }
+
+    /// Admin: deploy governance-approved treasury reserves into a lending pool as protocol-owned
+    /// liquidity. Treasury funds already sit in lending pool escrow, so no tokens move; the
+    /// liquidity is tracked apart from lender deposits, and its share of lender rewards is
+    /// claimed back to the treasury with `claim_protocol_yield`.
+    pub fn deploy_treasury_liquidity(
+        ctx: Context<DeployTreasuryLiquidity>,
+        amount: u64,
+    ) -> Result<()> {
+        let proposal = &mut ctx.accounts.proposal;
+        require!(
+            proposal.proposal_type == PROPOSAL_TYPE_TREASURY_LIQUIDITY,
+            ZKError::InvalidProposal
+        );
+        require!(proposal_passed(proposal), ZKError::ProposalNotPassed);
+        require!(!proposal.executed, ZKError::ProposalAlreadyExecuted);
+        // Deployments draw down the approved amount; a fully deployed proposal is closed.
+        proposal.new_value = proposal
+            .new_value
+            .checked_sub(amount)
+            .ok_or(ZKError::InvalidProposal)?;
+        if proposal.new_value == 0 {
+            proposal.executed = true;
+        }
+
+        let treasury = &mut ctx.accounts.protocol_treasury;
+        treasury.total_fees_collected = treasury
+            .total_fees_collected
+            .checked_sub(amount)
+            .ok_or(ZKError::InsufficientTreasuryReserves)?;
+
+        let lending_pool = &mut ctx.accounts.lending_pool;
+        lending_pool.total_liquidity = lending_pool
+            .total_liquidity
+            .checked_add(amount)
+            .ok_or(ZKError::MathOverflow)?;
+        lending_pool.protocol_owned_liquidity = lending_pool
+            .protocol_owned_liquidity
             .checked_add(amount)
             .ok_or(ZKError::MathOverflow)?;
+
+        let protocol_state = &mut ctx.accounts.protocol_state;
         protocol_state.total_liquidity = protocol_state
             .total_liquidity
+            .checked_add(amount)
+            .ok_or(ZKError::MathOverflow)?;
+        update_utilization(protocol_state);
+        Ok(())
+    }
+
+    /// Admin: move the rewards earned by a lending pool's protocol-owned liquidity into the
+    /// treasury reserve. Like the deployed liquidity itself, the yield already sits in lending
+    /// pool escrow, so no tokens move.
+    pub fn claim_protocol_yield(ctx: Context<ClaimProtocolYield>) -> Result<()> {
+        let lending_pool = &mut ctx.accounts.lending_pool;
+        let amount = lending_pool.protocol_yield;
+        require!(amount > 0, ZKError::NoProtocolYield);
+        lending_pool.protocol_yield = 0;
+        credit_treasury_fee(&mut ctx.accounts.protocol_treasury, amount)?;
+
+        emit!(ProtocolYieldClaimedEvent {
+            lending_pool: lending_pool.key(),
+            amount,
+        });
+        Ok(())
+    }
+
+    /// Admin: move treasury reserves into a lending pool's budget for long-term lenders' reward
+    /// boosts. Treasury funds already sit in lending pool escrow, so no tokens move.
+    pub fn fund_reward_boost(ctx: Context<FundRewardBoost>, amount: u64) -> Result<()> {
+        let treasury = &mut ctx.accounts.protocol_treasury;
+        treasury.total_fees_collected = treasury
+            .total_fees_collected
             .checked_sub(amount)
+            .ok_or(ZKError::InsufficientTreasuryReserves)?;
+        let lending_pool = &mut ctx.accounts.lending_pool;
+        lending_pool.reward_boost_budget = lending_pool
+            .reward_boost_budget
+            .checked_add(amount)
             .ok_or(ZKError::MathOverflow)?;
-        protocol_state.utilization_rate =
-            calculate_utilization(protocol_state.total_loans, protocol_state.total_liquidity);
+        Ok(())
+    }
+
+    /// Admin: configure the kinked variable rate curve.
+    pub fn set_rate_curve(
+        ctx: Context<SetRateCurve>,
+        optimal_utilization: u8,
+        rate_slope_low: u8,
+        rate_slope_high: u8,
+    ) -> Result<()> {
+        require!(
+            optimal_utilization > 0 && optimal_utilization < 100,
+            ZKError::InvalidRateCurve
+        );
 
+        let protocol_state = &mut ctx.accounts.protocol_state;
+        protocol_state.optimal_utilization = optimal_utilization;
+        protocol_state.rate_slope_low = rate_slope_low;
+        protocol_state.rate_slope_high = rate_slope_high;
         Ok(())
     }
 
-    /// Repay borrowed funds; includes accrued interest.
-    pub fn repay(ctx: Context<Repay>, amount: u64) -> Result<()> {
-        let clock = Clock::get()?;
-        let now = clock.unix_timestamp;
+    /// Admin: replace the borrow fee schedule. Tiers must start at zero and be strictly ascending.
+    pub fn set_fee_tiers(ctx: Context<SetFeeTiers>, tiers: Vec<FeeTier>) -> Result<()> {
+        require!(
+            !tiers.is_empty() && tiers.len() <= MAX_FEE_TIERS && tiers[0].threshold == 0,
+            ZKError::InvalidFeeTiers
+        );
+        require!(
+            tiers.windows(2).all(|pair| pair[0].threshold < pair[1].threshold),
+            ZKError::InvalidFeeTiers
+        );
+        require!(tiers.iter().all(|tier| tier.bps <= 10_000), ZKError::InvalidFeeTiers);
 
-        let borrower_account = &mut ctx.accounts.borrower_account;
         let protocol_state = &mut ctx.accounts.protocol_state;
-        let lending_pool = &mut ctx.accounts.lending_pool;
+        protocol_state.fee_tiers = [FeeTier::default(); MAX_FEE_TIERS];
+        protocol_state.fee_tiers[..tiers.len()].copy_from_slice(&tiers);
+        protocol_state.fee_tier_count = tiers.len() as u8;
+        Ok(())
+    }
 
-        // Calculate time elapsed and accrued interest.
-        let time_elapsed = now.checked_sub(borrower_account.borrow_timestamp).unwrap_or(0);
-        // Simplified interest calculation:
-        // interest_due = principal * base_interest_rate * time_elapsed / (seconds in a year * 100)
-        let principal = borrower_account.encrypted_borrowed.clone().value;
-        let interest_due = principal
-            .checked_mul(protocol_state.base_interest_rate as u64)
-            .and_then(|v| v.checked_mul(time_elapsed as u64))
-            .and_then(|v| v.checked_div(31_536_000 * 100))
-            .ok_or(ZKError::MathOverflow)?;
+    /// Admin: replace the liquidation grace schedule. Tiers must start at a zero pool share,
+    /// ascend strictly, and each grace period must end before a liquidation mark expires.
+    pub fn set_grace_tiers(ctx: Context<SetGraceTiers>, tiers: Vec<GraceTier>) -> Result<()> {
+        require!(
+            !tiers.is_empty() && tiers.len() <= MAX_GRACE_TIERS && tiers[0].min_pool_share_bps == 0,
+            ZKError::InvalidGraceTiers
+        );
+        require!(
+            tiers
+                .windows(2)
+                .all(|pair| pair[0].min_pool_share_bps < pair[1].min_pool_share_bps),
+            ZKError::InvalidGraceTiers
+        );
+        require!(
+            tiers
+                .iter()
+                .all(|tier| tier.grace_slots < LIQUIDATION_MARK_EXPIRY_SLOTS),
+            ZKError::InvalidGraceTiers
+        );
 
-        let total_due = principal.checked_add(interest_due).ok_or(ZKError::MathOverflow)?;
-        require!(amount >= total_due, ZKError::RepayExceedsBorrow);
+        let protocol_state = &mut ctx.accounts.protocol_state;
+        protocol_state.grace_tiers = [GraceTier::default(); MAX_GRACE_TIERS];
+        protocol_state.grace_tiers[..tiers.len()].copy_from_slice(&tiers);
+        protocol_state.grace_tier_count = tiers.len() as u8;
+        Ok(())
+    }
+
+    /// Admin: replace the reputation-based collateral lock schedule. Tiers must ascend strictly
+    /// by score and may only shorten `min_collateral_lock_time`; an empty schedule clears them.
+    pub fn set_lock_tiers(ctx: Context<SetLockTiers>, tiers: Vec<LockTier>) -> Result<()> {
+        let protocol_state = &mut ctx.accounts.protocol_state;
+        require!(tiers.len() <= MAX_LOCK_TIERS, ZKError::InvalidLockTiers);
+        require!(
+            tiers
+                .windows(2)
+                .all(|pair| pair[0].min_reputation_score < pair[1].min_reputation_score),
+            ZKError::InvalidLockTiers
+        );
+        require!(
+            tiers.iter().all(|tier| {
+                tier.lock_time >= 0 && tier.lock_time <= protocol_state.min_collateral_lock_time
+            }),
+            ZKError::InvalidLockTiers
+        );
+
+        protocol_state.lock_tiers = [LockTier::default(); MAX_LOCK_TIERS];
+        protocol_state.lock_tiers[..tiers.len()].copy_from_slice(&tiers);
+        protocol_state.lock_tier_count = tiers.len() as u8;
+        Ok(())
+    }
+
+    /// Stake collateral into a specific collateral pool.
+    pub fn stake_collateral(
+        ctx: Context<StakeCollateral>,
+        amount: u64,
+        zk_proof: Vec<u8>,
+    ) -> Result<()> {
+        require!(
+            amount >= ctx.accounts.collateral_pool.min_stake,
+            ZKError::StakeTooSmall
+        );
+        require!(zk_proof.len() <= MAX_PROOF_LEN, ZKError::ProofTooLarge);
+        // Validate proof (placeholder).
+        log_compute(&ctx.accounts.protocol_state, "verify_zk_proof: start");
+        verify_circuit_proof(&ctx.accounts.verifying_key_registry, CIRCUIT_COLLATERAL, &zk_proof)?;
+        log_compute(&ctx.accounts.protocol_state, "verify_zk_proof: end");
 
-        // Transfer repayment tokens from borrower to lending pool.
+        // Transfer collateral tokens from user to collateral pool escrow.
         let cpi_accounts = Transfer {
-            from: ctx.accounts.user_borrow_token_account.to_account_info(),
-            to: ctx.accounts.lending_pool_token_account.to_account_info(),
+            from: ctx.accounts.user_collateral_account.to_account_info(),
+            to: ctx.accounts.collateral_pool_token_account.to_account_info(),
             authority: ctx.accounts.borrower.to_account_info(),
         };
         token::transfer(
@@ -332,291 +812,6948 @@ pub mod zk_lending_protocol {
             amount,
         )?;
 
-        // Distribute a portion of repayment as yield farming rewards (e.g., 1%).
-        let reward = amount.checked_div(100).ok_or(ZKError::MathOverflow)?;
-        lending_pool.lender_rewards = lending_pool
-            .lender_rewards
-            .checked_add(reward)
-            .ok_or(ZKError::MathOverflow)?;
-
-        // Update borrower account: clear borrowed amount and reset timestamp.
-        borrower_account.encrypted_borrowed = reset_encryption();
-        borrower_account.borrow_timestamp = 0;
+        // Update the borrower's encrypted collateral.
+        let borrower_account = &mut ctx.accounts.borrower_account;
+        borrower_account.encrypted_collateral = update_encrypted_value(
+            borrower_account.encrypted_collateral.clone(),
+            amount,
+            true,
+        );
+        borrower_account.last_stake_at = Clock::get()?.unix_timestamp;
 
-        // Update protocol state.
-        protocol_state.total_loans = protocol_state
-            .total_loans
-            .checked_sub(principal)
-            .ok_or(ZKError::MathOverflow)?;
-        protocol_state.total_liquidity = protocol_state
-            .total_liquidity
+        // Update collateral pool state.
+        let collateral_pool = &mut ctx.accounts.collateral_pool;
+        collateral_pool.total_collateral = collateral_pool
+            .total_collateral
             .checked_add(amount)
             .ok_or(ZKError::MathOverflow)?;
-        protocol_state.utilization_rate =
-            calculate_utilization(protocol_state.total_loans, protocol_state.total_liquidity);
-
+        adjust_protocol_collateral(&mut ctx.accounts.protocol_state, amount, true)?;
         Ok(())
     }
 
-    /// Partial liquidation: liquidate 50% of collateral if conditions are met.
-    pub fn liquidate(ctx: Context<Liquidate>, zk_proof: Vec<u8>) -> Result<()> {
-        require!(verify_zk_proof(&zk_proof), ZKError::InvalidProof);
-
-        // Check that collateral is insufficient.
+    /// Co-signer: stake collateral into `beneficiary`'s borrower account from the co-signer's own
+    /// tokens. The stake is recorded so only the co-signer can withdraw it.
+    pub fn stake_collateral_for(
+        ctx: Context<StakeCollateralFor>,
+        _beneficiary: Pubkey,
+        amount: u64,
+        zk_proof: Vec<u8>,
+    ) -> Result<()> {
         require!(
-            !has_sufficient_collateral(
-                ctx.accounts.borrower_account.encrypted_collateral.clone(),
-                0
-            ),
-            ZKError::LiquidationNotAllowed
+            amount >= ctx.accounts.collateral_pool.min_stake,
+            ZKError::StakeTooSmall
         );
+        require!(zk_proof.len() <= MAX_PROOF_LEN, ZKError::ProofTooLarge);
+        log_compute(&ctx.accounts.protocol_state, "verify_zk_proof: start");
+        verify_circuit_proof(&ctx.accounts.verifying_key_registry, CIRCUIT_COLLATERAL, &zk_proof)?;
+        log_compute(&ctx.accounts.protocol_state, "verify_zk_proof: end");
 
-        let borrower_account = &mut ctx.accounts.borrower_account;
-        let collateral_pool = &mut ctx.accounts.collateral_pool;
-
-        // Partial liquidation: liquidate 50% of the collateral.
-        let current_collateral = extract_value_from_encryption(borrower_account.encrypted_collateral.clone());
-        let liquidate_amount = current_collateral / 2;
+        // Transfer collateral tokens from the co-signer to collateral pool escrow.
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.cosigner_collateral_account.to_account_info(),
+            to: ctx.accounts.collateral_pool_token_account.to_account_info(),
+            authority: ctx.accounts.cosigner.to_account_info(),
+        };
+        token::transfer(
+            CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+            amount,
+        )?;
 
+        let borrower_account = &mut ctx.accounts.borrower_account;
         borrower_account.encrypted_collateral = update_encrypted_value(
             borrower_account.encrypted_collateral.clone(),
-            liquidate_amount,
-            false,
+            amount,
+            true,
         );
+        borrower_account.last_stake_at = Clock::get()?.unix_timestamp;
+        borrower_account.cosigned_collateral = borrower_account
+            .cosigned_collateral
+            .checked_add(amount)
+            .ok_or(ZKError::MathOverflow)?;
+
+        let cosigner_stake = &mut ctx.accounts.cosigner_stake;
+        cosigner_stake.cosigner = ctx.accounts.cosigner.key();
+        cosigner_stake.borrower_account = borrower_account.key();
+        cosigner_stake.amount = cosigner_stake
+            .amount
+            .checked_add(amount)
+            .ok_or(ZKError::MathOverflow)?;
+
+        let collateral_pool = &mut ctx.accounts.collateral_pool;
         collateral_pool.total_collateral = collateral_pool
             .total_collateral
-            .checked_sub(liquidate_amount)
+            .checked_add(amount)
             .ok_or(ZKError::MathOverflow)?;
-
+        adjust_protocol_collateral(&mut ctx.accounts.protocol_state, amount, true)?;
         Ok(())
     }
 
-    /// Governance: Propose a protocol parameter change.
-    pub fn propose_change(
-        ctx: Context<ProposeChange>,
-        proposal_type: u8,
-        new_value: u64,
-    ) -> Result<()> {
-        let governance = &mut ctx.accounts.governance;
-        governance.proposal_id = governance
-            .proposal_id
-            .checked_add(1)
+    /// Deposit a liquidation-protection buffer, drawn into collateral automatically when the
+    /// position would otherwise become liquidatable. The tokens are held in pool escrow.
+    pub fn fund_protection(ctx: Context<FundProtection>, amount: u64) -> Result<()> {
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.user_collateral_account.to_account_info(),
+            to: ctx.accounts.collateral_pool_token_account.to_account_info(),
+            authority: ctx.accounts.borrower.to_account_info(),
+        };
+        token::transfer(
+            CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+            amount,
+        )?;
+
+        let borrower_account = &mut ctx.accounts.borrower_account;
+        borrower_account.protection_buffer = borrower_account
+            .protection_buffer
+            .checked_add(amount)
             .ok_or(ZKError::MathOverflow)?;
-        governance.proposal_type = proposal_type;
-        governance.new_value = new_value;
-        governance.votes = 0;
+        let collateral_pool = &mut ctx.accounts.collateral_pool;
+        collateral_pool.total_collateral = collateral_pool
+            .total_collateral
+            .checked_add(amount)
+            .ok_or(ZKError::MathOverflow)?;
+        adjust_protocol_collateral(&mut ctx.accounts.protocol_state, amount, true)?;
         Ok(())
     }
 
-    /// Governance: Vote on a proposal (only allowed for authorized voters).
-    pub fn vote(ctx: Context<Vote>, proposal_id: u64, vote: bool) -> Result<()> {
+    /// Stake into several collateral pools in one transaction.
+    ///
+    /// `remaining_accounts` holds one `[collateral_pool, user_collateral_account,
+    /// collateral_pool_token_account]` triple per entry of `amounts`, all writable. Each
+    /// escrow must be owned by its pool's authority PDA.
+    pub fn batch_stake<'info>(
+        ctx: Context<'_, '_, 'info, 'info, BatchStake<'info>>,
+        amounts: Vec<u64>,
+        proofs: Vec<Vec<u8>>,
+    ) -> Result<()> {
         require!(
-            ctx.accounts.institutional_pool.zk_whitelist.contains(&ctx.accounts.voter.key()),
-            ZKError::UnauthorizedVoter
+            amounts.len() == proofs.len() && ctx.remaining_accounts.len() == amounts.len() * 3,
+            ZKError::BatchLengthMismatch
         );
 
-        let governance = &mut ctx.accounts.governance;
-        require!(governance.proposal_id == proposal_id, ZKError::InvalidProposal);
+        let mut total_staked: u64 = 0;
+        for ((amount, zk_proof), accounts) in amounts
+            .iter()
+            .zip(proofs.iter())
+            .zip(ctx.remaining_accounts.chunks(3))
+        {
+            require!(zk_proof.len() <= MAX_PROOF_LEN, ZKError::ProofTooLarge);
+            verify_circuit_proof(
+                &ctx.accounts.verifying_key_registry,
+                CIRCUIT_COLLATERAL,
+                zk_proof,
+            )?;
 
-        if vote {
-            governance.votes = governance
-                .votes
-                .checked_add(1)
+            let mut collateral_pool = Account::<CollateralPool>::try_from(&accounts[0])?;
+            let user_collateral_account = Account::<TokenAccount>::try_from(&accounts[1])?;
+            let collateral_pool_token_account = Account::<TokenAccount>::try_from(&accounts[2])?;
+            require!(
+                user_collateral_account.mint == collateral_pool.asset_mint
+                    && collateral_pool_token_account.mint == collateral_pool.asset_mint,
+                ZKError::CollateralMintMismatch
+            );
+            require!(
+                collateral_pool_token_account.owner == collateral_pool_authority(&accounts[0].key()),
+                ZKError::InvalidPoolEscrow
+            );
+            require!(*amount >= collateral_pool.min_stake, ZKError::StakeTooSmall);
+
+            // Transfer collateral tokens from user to this pool's escrow.
+            let cpi_accounts = Transfer {
+                from: user_collateral_account.to_account_info(),
+                to: collateral_pool_token_account.to_account_info(),
+                authority: ctx.accounts.borrower.to_account_info(),
+            };
+            token::transfer(
+                CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+                *amount,
+            )?;
+
+            collateral_pool.total_collateral = collateral_pool
+                .total_collateral
+                .checked_add(*amount)
                 .ok_or(ZKError::MathOverflow)?;
-        } else {
-            governance.votes = governance
-                .votes
-                .checked_sub(1)
+            collateral_pool.exit(&crate::ID)?;
+
+            total_staked = total_staked
+                .checked_add(*amount)
                 .ok_or(ZKError::MathOverflow)?;
         }
-        Ok(())
-    }
 
-    /// Rebalance collateral: adjust collateral without revealing details.
-    pub fn rebalance_collateral(
-        ctx: Context<RebalanceCollateral>,
-        additional_collateral: u64,
-        zk_proof: Vec<u8>,
-    ) -> Result<()> {
-        require!(verify_zk_proof(&zk_proof), ZKError::InvalidProof);
+        adjust_protocol_collateral(&mut ctx.accounts.protocol_state, total_staked, true)?;
+
+        // Update the borrower's encrypted collateral once for the whole batch.
         let borrower_account = &mut ctx.accounts.borrower_account;
-        // For simplicity, we add the additional collateral (could also support reductions).
         borrower_account.encrypted_collateral = update_encrypted_value(
             borrower_account.encrypted_collateral.clone(),
-            additional_collateral,
+            total_staked,
             true,
         );
+        borrower_account.last_stake_at = Clock::get()?.unix_timestamp;
         Ok(())
     }
-}
-
-// ─────────────────────────────────────────────────────────────
-// Dummy & Helper Functions (Replace with actual ZK and confidential logic)
-// ─────────────────────────────────────────────────────────────
-
-fn verify_zk_proof(_zk_proof: &Vec<u8>) -> bool {
-    true
-}
 
-fn update_encrypted_value(
-    current: EncryptedAmount,
-    amount: u64,
-    add: bool,
-) -> EncryptedAmount {
-    if add {
-        EncryptedAmount {
-            value: current.value.checked_add(amount).unwrap_or(current.value),
-        }
-    } else {
-        EncryptedAmount {
-            value: current.value.saturating_sub(amount),
-        }
+    /// Opt the borrower's collateral into a lock-up for a borrow fee discount. Withdrawing
+    /// before the lock-up ends forfeits a penalty to the treasury. Lock-ups can't be shortened.
+    pub fn lock_collateral(ctx: Context<LockCollateral>, duration: i64) -> Result<()> {
+        require!(
+            duration > 0 && duration <= MAX_STAKE_LOCK_DURATION,
+            ZKError::InvalidStakeLockDuration
+        );
+        let now = Clock::get()?.unix_timestamp;
+        let borrower_account = &mut ctx.accounts.borrower_account;
+        borrower_account.stake_lock_until = borrower_account.stake_lock_until.max(now + duration);
+        borrower_account.early_withdraw_penalty_bps = EARLY_WITHDRAW_PENALTY_BPS;
+        Ok(())
     }
-}
 
-fn has_sufficient_collateral(encrypted_collateral: EncryptedAmount, amount: u64) -> bool {
-    encrypted_collateral.value >= amount
-}
+    /// Withdraw staked collateral, keeping any outstanding debt at the minimum collateral ratio.
+    pub fn withdraw_collateral(ctx: Context<WithdrawCollateral>, amount: u64) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let borrower_account = &ctx.accounts.borrower_account;
+        // Co-signed collateral goes back to its co-signers, not the borrower.
+        let own_collateral = borrower_account
+            .encrypted_collateral
+            .value
+            .saturating_sub(borrower_account.cosigned_collateral);
+        require!(amount <= own_collateral, ZKError::InsufficientCollateral);
+        let remaining = borrower_account
+            .encrypted_collateral
+            .value
+            .checked_sub(amount)
+            .ok_or(ZKError::InsufficientCollateral)?;
+        let debt = borrower_debt(borrower_account);
+        require!(
+            debt == 0
+                || meets_collateral_ratio(
+                    EncryptedAmount { value: remaining },
+                    debt,
+                    ctx.accounts.protocol_state.min_collateral_ratio
+                ),
+            ZKError::InsufficientCollateral
+        );
 
-fn reset_encryption() -> EncryptedAmount {
-    EncryptedAmount { value: 0 }
-}
+        // Withdrawing during a lock-up forfeits a penalty to the treasury.
+        let penalty = if now < borrower_account.stake_lock_until {
+            mul_div_up(
+                amount as u128,
+                borrower_account.early_withdraw_penalty_bps as u128,
+                10_000,
+            )? as u64
+        } else {
+            0
+        };
 
-fn extract_value_from_encryption(encrypted: EncryptedAmount) -> u64 {
-    encrypted.value
-}
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.collateral_pool_token_account.to_account_info(),
+            to: ctx.accounts.user_collateral_account.to_account_info(),
+            authority: ctx.accounts.collateral_pool_authority.to_account_info(),
+        };
+        token::transfer(
+            CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+            amount - penalty,
+        )?;
+        if penalty > 0 {
+            let cpi_accounts = Transfer {
+                from: ctx.accounts.collateral_pool_token_account.to_account_info(),
+                to: ctx.accounts.treasury_collateral_account.to_account_info(),
+                authority: ctx.accounts.collateral_pool_authority.to_account_info(),
+            };
+            token::transfer(
+                CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+                penalty,
+            )?;
+        }
 
-fn calculate_utilization(total_loans: u64, total_liquidity: u64) -> u8 {
-    if total_liquidity == 0 {
-        0
-    } else {
-        ((total_loans as u128 * 100 / total_liquidity as u128) as u8)
+        let borrower_account = &mut ctx.accounts.borrower_account;
+        borrower_account.encrypted_collateral = update_encrypted_value(
+            borrower_account.encrypted_collateral.clone(),
+            amount,
+            false,
+        );
+        let collateral_pool = &mut ctx.accounts.collateral_pool;
+        collateral_pool.total_collateral = collateral_pool
+            .total_collateral
+            .checked_sub(amount)
+            .ok_or(ZKError::MathOverflow)?;
+        adjust_protocol_collateral(&mut ctx.accounts.protocol_state, amount, false)?;
+        Ok(())
+    }
+
+    /// Co-signer: withdraw collateral previously staked for a borrower back to the co-signer,
+    /// while the borrower's debt stays above the minimum collateral ratio.
+    pub fn withdraw_cosigned_collateral(
+        ctx: Context<WithdrawCosignedCollateral>,
+        amount: u64,
+    ) -> Result<()> {
+        require!(
+            amount <= ctx.accounts.cosigner_stake.amount,
+            ZKError::InsufficientCollateral
+        );
+        let borrower_account = &ctx.accounts.borrower_account;
+        let remaining = borrower_account
+            .encrypted_collateral
+            .value
+            .checked_sub(amount)
+            .ok_or(ZKError::InsufficientCollateral)?;
+        let debt = borrower_debt(borrower_account);
+        require!(
+            debt == 0
+                || meets_collateral_ratio(
+                    EncryptedAmount { value: remaining },
+                    debt,
+                    ctx.accounts.protocol_state.min_collateral_ratio
+                ),
+            ZKError::InsufficientCollateral
+        );
+
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.collateral_pool_token_account.to_account_info(),
+            to: ctx.accounts.cosigner_collateral_account.to_account_info(),
+            authority: ctx.accounts.collateral_pool_authority.to_account_info(),
+        };
+        token::transfer(
+            CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+            amount,
+        )?;
+
+        let cosigner_stake = &mut ctx.accounts.cosigner_stake;
+        cosigner_stake.amount = cosigner_stake
+            .amount
+            .checked_sub(amount)
+            .ok_or(ZKError::MathOverflow)?;
+        let borrower_account = &mut ctx.accounts.borrower_account;
+        borrower_account.encrypted_collateral = update_encrypted_value(
+            borrower_account.encrypted_collateral.clone(),
+            amount,
+            false,
+        );
+        borrower_account.cosigned_collateral =
+            borrower_account.cosigned_collateral.saturating_sub(amount);
+        let collateral_pool = &mut ctx.accounts.collateral_pool;
+        collateral_pool.total_collateral = collateral_pool
+            .total_collateral
+            .checked_sub(amount)
+            .ok_or(ZKError::MathOverflow)?;
+        adjust_protocol_collateral(&mut ctx.accounts.protocol_state, amount, false)?;
+        Ok(())
+    }
+
+    /// Stake institutional collateral backed by a custodian's signed proof-of-reserves attestation.
+    ///
+    /// The transaction must include an ed25519 precompile instruction, immediately before this
+    /// one, verifying the pool's registered custodian signed the attestation.
+    pub fn stake_collateral_with_attestation(
+        ctx: Context<StakeCollateralWithAttestation>,
+        amount: u64,
+        zk_proof: Vec<u8>,
+        attestation: ReserveAttestation,
+    ) -> Result<()> {
+        require!(
+            amount >= ctx.accounts.collateral_pool.min_stake,
+            ZKError::StakeTooSmall
+        );
+        require!(zk_proof.len() <= MAX_PROOF_LEN, ZKError::ProofTooLarge);
+        log_compute(&ctx.accounts.protocol_state, "verify_zk_proof: start");
+        verify_circuit_proof(&ctx.accounts.verifying_key_registry, CIRCUIT_COLLATERAL, &zk_proof)?;
+        log_compute(&ctx.accounts.protocol_state, "verify_zk_proof: end");
+
+        // Verify the registered custodian attests to reserves backing this stake.
+        let custodian_key = ctx.accounts.collateral_pool.custodian_key;
+        require!(
+            custodian_key != Pubkey::default(),
+            ZKError::CustodianNotRegistered
+        );
+        let message = attestation_message(
+            &ctx.accounts.collateral_pool.key(),
+            &ctx.accounts.borrower.key(),
+            &attestation,
+        );
+        verify_ed25519_instruction(&ctx.accounts.instructions_sysvar, &custodian_key, &message)?;
+        require!(
+            Clock::get()?.unix_timestamp <= attestation.expiry,
+            ZKError::AttestationExpired
+        );
+        require!(
+            attestation.attested_reserves >= amount,
+            ZKError::InsufficientReserves
+        );
+
+        // Transfer collateral tokens from user to collateral pool escrow.
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.user_collateral_account.to_account_info(),
+            to: ctx.accounts.collateral_pool_token_account.to_account_info(),
+            authority: ctx.accounts.borrower.to_account_info(),
+        };
+        token::transfer(
+            CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+            amount,
+        )?;
+
+        let borrower_account = &mut ctx.accounts.borrower_account;
+        borrower_account.encrypted_collateral = update_encrypted_value(
+            borrower_account.encrypted_collateral.clone(),
+            amount,
+            true,
+        );
+        borrower_account.last_stake_at = Clock::get()?.unix_timestamp;
+
+        let collateral_pool = &mut ctx.accounts.collateral_pool;
+        collateral_pool.total_collateral = collateral_pool
+            .total_collateral
+            .checked_add(amount)
+            .ok_or(ZKError::MathOverflow)?;
+        adjust_protocol_collateral(&mut ctx.accounts.protocol_state, amount, true)?;
+        Ok(())
+    }
+
+    /// Normal borrowing instruction with flash loan protection and treasury fee collection.
+    pub fn borrow(
+        ctx: Context<Borrow>,
+        amount: u64,
+        zk_proof: Vec<u8>,
+    ) -> Result<()> {
+        require!(!ctx.accounts.protocol_state.borrows_frozen, ZKError::BorrowsFrozen);
+        require!(ctx.accounts.lending_pool.borrow_enabled, ZKError::AssetBorrowDisabled);
+        require!(zk_proof.len() <= MAX_PROOF_LEN, ZKError::ProofTooLarge);
+        // Verify ZK proof.
+        log_compute(&ctx.accounts.protocol_state, "verify_zk_proof: start");
+        verify_circuit_proof(&ctx.accounts.verifying_key_registry, CIRCUIT_SOLVENCY, &zk_proof)?;
+        log_compute(&ctx.accounts.protocol_state, "verify_zk_proof: end");
+
+        // When a borrow whitelist is configured, only its members may borrow.
+        let whitelist_key = ctx.accounts.protocol_state.borrow_whitelist;
+        if whitelist_key != Pubkey::default() {
+            let borrower_key = ctx.accounts.borrower.key();
+            let whitelist = ctx
+                .accounts
+                .borrow_whitelist
+                .as_ref()
+                .filter(|whitelist| whitelist.key() == whitelist_key)
+                .ok_or(ZKError::UnauthorizedBorrower)?;
+            require!(
+                whitelist.members.contains(&borrower_key),
+                ZKError::UnauthorizedBorrower
+            );
+        }
+
+        let clock = Clock::get()?;
+        let now = clock.unix_timestamp;
+        let borrower_account = &mut ctx.accounts.borrower_account;
+        let protocol_state = &mut ctx.accounts.protocol_state;
+
+        require_stake_settled(protocol_state, borrower_account, now)?;
+        // Flash loan protection: a repeat borrow must wait the reputation-tiered lock time.
+        let lock_time =
+            collateral_lock_time(protocol_state, ctx.accounts.borrower_reputation.as_deref());
+        if record_flash_attempt(borrower_account, lock_time, now)? {
+            // Free the loan id reserved for this borrow; the recorded attempt still commits.
+            return ctx.accounts.loan_position.close(ctx.accounts.borrower.to_account_info());
+        }
+        // Set the borrow timestamp.
+        borrower_account.borrow_timestamp = now;
+
+        // Check encrypted collateral covers the borrow at the minimum collateralization ratio.
+        let price = collateral_price(
+            &ctx.accounts.collateral_pool,
+            ctx.accounts.price_oracle.as_ref(),
+            PRICE_SIDE_BORROW,
+            protocol_state.confidence_multiplier_bps,
+            now,
+        )?;
+        let collateral = EncryptedAmount {
+            value: borrowing_power(
+                borrower_account.encrypted_collateral.value,
+                &ctx.accounts.collateral_pool,
+                price,
+                now,
+            )?,
+        };
+        let debt_after = borrower_debt(borrower_account)
+            .checked_add(amount)
+            .ok_or(ZKError::MathOverflow)?;
+        require_collateral_ratio(&collateral, debt_after, protocol_state.min_collateral_ratio)?;
+
+        // Throttle borrows to the per-window cap.
+        apply_borrow_rate_limit(&mut ctx.accounts.borrow_rate_limit, amount, now)?;
+        require_protocol_leverage(protocol_state, amount)?;
+        require_liquidity(protocol_state, &ctx.accounts.lending_pool, amount)?;
+        require_max_utilization(protocol_state, amount)?;
+
+        // Deduct the borrow fee for the amount's fee tier.
+        let fee = calculate_borrow_fee(amount, protocol_state, borrower_account, now)?;
+        let net_amount = amount.checked_sub(fee).ok_or(ZKError::MathOverflow)?;
+
+        // Transfer tokens from lending pool escrow to borrower.
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.lending_pool_token_account.to_account_info(),
+            to: ctx.accounts.user_borrow_token_account.to_account_info(),
+            authority: ctx.accounts.lending_pool_authority.to_account_info(),
+        };
+        token::transfer(
+            CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+            net_amount,
+        )?;
+
+        // Update treasury with collected fee.
+        let treasury = &mut ctx.accounts.protocol_treasury;
+        credit_treasury_fee(treasury, fee)?;
+
+        // Open a new loan position for this borrow at the current variable rate.
+        let borrower_account_key = borrower_account.key();
+        open_loan_position(
+            &mut ctx.accounts.loan_position,
+            borrower_account,
+            borrower_account_key,
+            amount,
+            fee,
+            RATE_MODE_VARIABLE,
+            calculate_borrow_rate(protocol_state),
+            now,
+            protocol_state.max_loans_per_borrower,
+        )?;
+
+        // Update the borrower's encrypted borrowed amount.
+        borrower_account.encrypted_principal = update_encrypted_value(
+            borrower_account.encrypted_principal.clone(),
+            amount, // principal (before fee)
+            true,
+        );
+
+        // Update protocol state.
+        protocol_state.total_loans = protocol_state
+            .total_loans
+            .checked_add(amount)
+            .ok_or(ZKError::MathOverflow)?;
+        protocol_state.total_liquidity = protocol_state
+            .total_liquidity
+            .checked_sub(amount)
+            .ok_or(ZKError::MathOverflow)?;
+        update_utilization(protocol_state);
+
+        Ok(())
+    }
+
+    /// Leveraged borrow: borrow `amount`, stake the proceeds as collateral, and borrow again
+    /// against them for up to `loops` rounds. Each later round borrows as much as the new
+    /// collateral supports; looping stops early once the next round would breach the minimum
+    /// collateral ratio or `MAX_LOOP_LEVERAGE_BPS`. The rounds are booked as a single loan.
+    ///
+    /// The collateral pool must hold the lending pool's borrow mint.
+    pub fn loop_borrow(
+        ctx: Context<LoopBorrow>,
+        amount: u64,
+        loops: u8,
+        zk_proof: Vec<u8>,
+    ) -> Result<()> {
+        require!(!ctx.accounts.protocol_state.borrows_frozen, ZKError::BorrowsFrozen);
+        require!(ctx.accounts.lending_pool.borrow_enabled, ZKError::AssetBorrowDisabled);
+        require!((1..=MAX_BORROW_LOOPS).contains(&loops), ZKError::InvalidLoopCount);
+        require!(zk_proof.len() <= MAX_PROOF_LEN, ZKError::ProofTooLarge);
+        log_compute(&ctx.accounts.protocol_state, "verify_zk_proof: start");
+        verify_circuit_proof(&ctx.accounts.verifying_key_registry, CIRCUIT_SOLVENCY, &zk_proof)?;
+        log_compute(&ctx.accounts.protocol_state, "verify_zk_proof: end");
+
+        let whitelist_key = ctx.accounts.protocol_state.borrow_whitelist;
+        if whitelist_key != Pubkey::default() {
+            let borrower_key = ctx.accounts.borrower.key();
+            let whitelist = ctx
+                .accounts
+                .borrow_whitelist
+                .as_ref()
+                .filter(|whitelist| whitelist.key() == whitelist_key)
+                .ok_or(ZKError::UnauthorizedBorrower)?;
+            require!(
+                whitelist.members.contains(&borrower_key),
+                ZKError::UnauthorizedBorrower
+            );
+        }
+
+        let now = Clock::get()?.unix_timestamp;
+        let borrower_account = &mut ctx.accounts.borrower_account;
+        let protocol_state = &mut ctx.accounts.protocol_state;
+
+        require_stake_settled(protocol_state, borrower_account, now)?;
+        let lock_time =
+            collateral_lock_time(protocol_state, ctx.accounts.borrower_reputation.as_deref());
+        if record_flash_attempt(borrower_account, lock_time, now)? {
+            // Free the loan id reserved for this borrow; the recorded attempt still commits.
+            return ctx.accounts.loan_position.close(ctx.accounts.borrower.to_account_info());
+        }
+        borrower_account.borrow_timestamp = now;
+
+        let price = collateral_price(
+            &ctx.accounts.collateral_pool,
+            ctx.accounts.price_oracle.as_ref(),
+            PRICE_SIDE_BORROW,
+            protocol_state.confidence_multiplier_bps,
+            now,
+        )?;
+
+        // Each round's proceeds are staked, so collateral and debt grow together.
+        let initial_collateral = borrower_account.encrypted_collateral.value;
+        let max_collateral = mul_div_down(
+            initial_collateral as u128,
+            MAX_LOOP_LEVERAGE_BPS as u128,
+            10_000,
+        )?;
+        let mut collateral = initial_collateral;
+        let mut debt = borrower_debt(borrower_account);
+        let mut borrowed: u64 = 0;
+        let mut fees: u64 = 0;
+        let mut rounds: u8 = 0;
+        let mut next = amount;
+        while rounds < loops && next > 0 {
+            let fee = calculate_borrow_fee(next, protocol_state, borrower_account, now)?;
+            let proceeds = next.checked_sub(fee).ok_or(ZKError::MathOverflow)?;
+            let debt_after = debt.checked_add(next).ok_or(ZKError::MathOverflow)?;
+            let collateral_after =
+                collateral.checked_add(proceeds).ok_or(ZKError::MathOverflow)?;
+            let backing = EncryptedAmount {
+                value: borrowing_power(collateral, &ctx.accounts.collateral_pool, price, now)?,
+            };
+            if !meets_collateral_ratio(backing, debt_after, protocol_state.min_collateral_ratio)
+                || collateral_after as u128 > max_collateral
+            {
+                break;
+            }
+            collateral = collateral_after;
+            debt = debt_after;
+            borrowed = borrowed.checked_add(next).ok_or(ZKError::MathOverflow)?;
+            fees = fees.checked_add(fee).ok_or(ZKError::MathOverflow)?;
+            rounds += 1;
+            // The next round borrows what the newly staked proceeds support on their own.
+            let proceeds_value =
+                borrowing_power(proceeds, &ctx.accounts.collateral_pool, price, now)?;
+            next = mul_div_down(
+                proceeds_value as u128,
+                10_000,
+                protocol_state.min_collateral_ratio.max(1) as u128,
+            )? as u64;
+        }
+        require!(borrowed > 0, ZKError::InsufficientCollateral);
+        let staked = collateral - initial_collateral;
+
+        apply_borrow_rate_limit(&mut ctx.accounts.borrow_rate_limit, borrowed, now)?;
+        require_protocol_leverage(protocol_state, borrowed)?;
+        require_liquidity(protocol_state, &ctx.accounts.lending_pool, borrowed)?;
+        require_max_utilization(protocol_state, borrowed)?;
+
+        // The proceeds move straight from lending pool escrow into collateral pool escrow.
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.lending_pool_token_account.to_account_info(),
+            to: ctx.accounts.collateral_pool_token_account.to_account_info(),
+            authority: ctx.accounts.lending_pool_authority.to_account_info(),
+        };
+        token::transfer(
+            CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+            staked,
+        )?;
+        credit_treasury_fee(&mut ctx.accounts.protocol_treasury, fees)?;
+
+        let borrower_account_key = borrower_account.key();
+        open_loan_position(
+            &mut ctx.accounts.loan_position,
+            borrower_account,
+            borrower_account_key,
+            borrowed,
+            fees,
+            RATE_MODE_VARIABLE,
+            calculate_borrow_rate(protocol_state),
+            now,
+            protocol_state.max_loans_per_borrower,
+        )?;
+        borrower_account.encrypted_principal = update_encrypted_value(
+            borrower_account.encrypted_principal.clone(),
+            borrowed,
+            true,
+        );
+        borrower_account.encrypted_collateral = update_encrypted_value(
+            borrower_account.encrypted_collateral.clone(),
+            staked,
+            true,
+        );
+        borrower_account.last_stake_at = now;
+
+        let collateral_pool = &mut ctx.accounts.collateral_pool;
+        collateral_pool.total_collateral = collateral_pool
+            .total_collateral
+            .checked_add(staked)
+            .ok_or(ZKError::MathOverflow)?;
+        adjust_protocol_collateral(protocol_state, staked, true)?;
+        protocol_state.total_loans = protocol_state
+            .total_loans
+            .checked_add(borrowed)
+            .ok_or(ZKError::MathOverflow)?;
+        protocol_state.total_liquidity = protocol_state
+            .total_liquidity
+            .checked_sub(borrowed)
+            .ok_or(ZKError::MathOverflow)?;
+        update_utilization(protocol_state);
+
+        emit!(LoopBorrowEvent {
+            borrower_account: borrower_account_key,
+            rounds,
+            borrowed,
+            staked,
+        });
+        Ok(())
+    }
+
+    /// Preview a borrow: run the same checks and computations as `borrow` and emit the
+    /// outcome as a `BorrowSimulationEvent`, without transferring tokens or mutating state.
+    pub fn simulate_borrow(ctx: Context<SimulateBorrow>, amount: u64) -> Result<()> {
+        let protocol_state = &ctx.accounts.protocol_state;
+        require!(!protocol_state.borrows_frozen, ZKError::BorrowsFrozen);
+        if protocol_state.borrow_whitelist != Pubkey::default() {
+            let whitelist = ctx
+                .accounts
+                .borrow_whitelist
+                .as_ref()
+                .filter(|whitelist| whitelist.key() == protocol_state.borrow_whitelist)
+                .ok_or(ZKError::UnauthorizedBorrower)?;
+            require!(
+                whitelist.members.contains(&ctx.accounts.borrower_account.owner),
+                ZKError::UnauthorizedBorrower
+            );
+        }
+
+        let now = Clock::get()?.unix_timestamp;
+        let borrower_account = &ctx.accounts.borrower_account;
+        require_stake_settled(protocol_state, borrower_account, now)?;
+        if borrower_account.borrow_timestamp > 0 {
+            let lock_time = collateral_lock_time(
+                protocol_state,
+                ctx.accounts.borrower_reputation.as_deref(),
+            );
+            require_collateral_lock(borrower_account, lock_time, now)?;
+        }
+        let price = collateral_price(
+            &ctx.accounts.collateral_pool,
+            ctx.accounts.price_oracle.as_ref(),
+            PRICE_SIDE_BORROW,
+            protocol_state.confidence_multiplier_bps,
+            now,
+        )?;
+        let collateral = EncryptedAmount {
+            value: borrowing_power(
+                borrower_account.encrypted_collateral.value,
+                &ctx.accounts.collateral_pool,
+                price,
+                now,
+            )?,
+        };
+        let debt = borrower_debt(borrower_account)
+            .checked_add(amount)
+            .ok_or(ZKError::MathOverflow)?;
+        require_collateral_ratio(&collateral, debt, protocol_state.min_collateral_ratio)?;
+        // Apply the rate limit to a scratch copy so the real window is untouched.
+        let mut rate_limit = (*ctx.accounts.borrow_rate_limit).clone();
+        apply_borrow_rate_limit(&mut rate_limit, amount, now)?;
+        require_protocol_leverage(protocol_state, amount)?;
+        require_liquidity(protocol_state, &ctx.accounts.lending_pool, amount)?;
+        require_max_utilization(protocol_state, amount)?;
+
+        let fee = calculate_borrow_fee(amount, protocol_state, borrower_account, now)?;
+        let net_amount = amount.checked_sub(fee).ok_or(ZKError::MathOverflow)?;
+
+        let total_loans = protocol_state
+            .total_loans
+            .checked_add(amount)
+            .ok_or(ZKError::MathOverflow)?;
+        let total_liquidity = protocol_state
+            .total_liquidity
+            .checked_sub(amount)
+            .ok_or(ZKError::MathOverflow)?;
+
+        emit!(BorrowSimulationEvent {
+            borrower_account: borrower_account.key(),
+            amount,
+            fee,
+            net_amount,
+            health_factor: health_factor(
+                collateral.value,
+                debt,
+                protocol_state.min_collateral_ratio,
+            ),
+            utilization_rate: calculate_utilization(total_loans, total_liquidity),
+            interest_rate: calculate_borrow_rate(protocol_state),
+        });
+        Ok(())
+    }
+
+    /// A whitelisted institution lets `member` borrow under its whitelist entry in
+    /// `institutional_pool`, up to `max_borrow_amount` in total. Calling it again for the same
+    /// member updates the cap and keeps what has already been borrowed.
+    pub fn set_sub_account(
+        ctx: Context<SetSubAccount>,
+        member: Pubkey,
+        max_borrow_amount: u64,
+    ) -> Result<()> {
+        let parent = ctx.accounts.parent.key();
+        let institutional_pool = ctx.accounts.institutional_pool.key();
+        require!(member != parent, ZKError::InvalidDelegate);
+        require!(
+            ctx.accounts
+                .institutional_pool
+                .zk_whitelist
+                .iter()
+                .any(|entry| entry.institution == parent),
+            ZKError::UnauthorizedBorrower
+        );
+
+        let sub_account = &mut ctx.accounts.sub_account;
+        if sub_account.parent == Pubkey::default() {
+            sub_account.institutional_pool = institutional_pool;
+            sub_account.parent = parent;
+            sub_account.member = member;
+            sub_account.borrowed_so_far = 0;
+            sub_account.last_nonce = 0;
+        }
+        sub_account.max_borrow_amount = max_borrow_amount;
+        Ok(())
+    }
+
+    /// Revoke a sub-account's borrowing rights, refunding its rent to the parent.
+    pub fn revoke_sub_account(_ctx: Context<RevokeSubAccount>) -> Result<()> {
+        Ok(())
     }
+
+    /// Institutional borrowing instruction that checks a whitelist and applies a fixed interest rate.
+    ///
+    /// With `sub_account`, the signer borrows as a sub-account of a whitelisted institution:
+    /// the parent's whitelist entry must still exist, and the borrow counts against the
+    /// sub-account's own cap and nonce sequence.
+    pub fn institutional_borrow(
+        ctx: Context<InstitutionalBorrow>,
+        amount: u64,
+        nonce: u64,
+        zk_proof: Vec<u8>,
+    ) -> Result<()> {
+        require!(!ctx.accounts.protocol_state.borrows_frozen, ZKError::BorrowsFrozen);
+        require!(ctx.accounts.lending_pool.borrow_enabled, ZKError::AssetBorrowDisabled);
+        require!(zk_proof.len() <= MAX_PROOF_LEN, ZKError::ProofTooLarge);
+        log_compute(&ctx.accounts.protocol_state, "verify_zk_proof: start");
+        verify_circuit_proof(&ctx.accounts.verifying_key_registry, CIRCUIT_SOLVENCY, &zk_proof)?;
+        log_compute(&ctx.accounts.protocol_state, "verify_zk_proof: end");
+
+        let clock = Clock::get()?;
+        let now = clock.unix_timestamp;
+        let borrower_account = &mut ctx.accounts.borrower_account;
+        let protocol_state = &mut ctx.accounts.protocol_state;
+        let institutional_pool = &mut ctx.accounts.institutional_pool;
+
+        // Check that the borrower, or the parent of its sub-account, is whitelisted.
+        let borrower_key = ctx.accounts.borrower.key();
+        let institution = match ctx.accounts.sub_account.as_ref() {
+            Some(sub_account) => {
+                require!(
+                    sub_account.member == borrower_key
+                        && sub_account.institutional_pool == institutional_pool.key(),
+                    ZKError::UnauthorizedBorrower
+                );
+                sub_account.parent
+            }
+            None => borrower_key,
+        };
+        let entry = institutional_pool
+            .zk_whitelist
+            .iter_mut()
+            .find(|entry| entry.institution == institution)
+            .ok_or(ZKError::UnauthorizedBorrower)?;
+        let exempt_from_lock = entry.exempt_from_lock;
+
+        require_stake_settled(protocol_state, borrower_account, now)?;
+        // Flash loan protection, unless the institution is trusted to skip it.
+        if !exempt_from_lock
+            && record_flash_attempt(
+                borrower_account,
+                protocol_state.min_collateral_lock_time,
+                now,
+            )?
+        {
+            // Free the loan id reserved for this borrow; the recorded attempt still commits.
+            return ctx.accounts.loan_position.close(ctx.accounts.borrower.to_account_info());
+        }
+
+        // Each borrow carries a strictly increasing nonce so it can't be replayed.
+        if let Some(sub_account) = ctx.accounts.sub_account.as_mut() {
+            require!(nonce > sub_account.last_nonce, ZKError::StaleNonce);
+            sub_account.last_nonce = nonce;
+            let borrowed_so_far = sub_account
+                .borrowed_so_far
+                .checked_add(amount)
+                .ok_or(ZKError::MathOverflow)?;
+            require!(
+                borrowed_so_far <= sub_account.max_borrow_amount,
+                ZKError::BorrowLimitExceeded
+            );
+            sub_account.borrowed_so_far = borrowed_so_far;
+        } else {
+            require!(nonce > entry.last_nonce, ZKError::StaleNonce);
+            entry.last_nonce = nonce;
+        }
+        borrower_account.borrow_timestamp = now;
+
+        let price = collateral_price(
+            &ctx.accounts.collateral_pool,
+            ctx.accounts.price_oracle.as_ref(),
+            PRICE_SIDE_BORROW,
+            protocol_state.confidence_multiplier_bps,
+            now,
+        )?;
+        let collateral = EncryptedAmount {
+            value: borrowing_power(
+                borrower_account.encrypted_collateral.value,
+                &ctx.accounts.collateral_pool,
+                price,
+                now,
+            )?,
+        };
+        let debt_after = borrower_debt(borrower_account)
+            .checked_add(amount)
+            .ok_or(ZKError::MathOverflow)?;
+        require_collateral_ratio(&collateral, debt_after, protocol_state.min_collateral_ratio)?;
+
+        apply_borrow_rate_limit(&mut ctx.accounts.borrow_rate_limit, amount, now)?;
+        require_protocol_leverage(protocol_state, amount)?;
+        require_liquidity(protocol_state, &ctx.accounts.lending_pool, amount)?;
+        require_max_utilization(protocol_state, amount)?;
+
+        // Deduct borrow fee.
+        let fee = calculate_borrow_fee(amount, protocol_state, borrower_account, now)?;
+        let net_amount = amount.checked_sub(fee).ok_or(ZKError::MathOverflow)?;
+
+        // Transfer tokens.
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.lending_pool_token_account.to_account_info(),
+            to: ctx.accounts.user_borrow_token_account.to_account_info(),
+            authority: ctx.accounts.lending_pool_authority.to_account_info(),
+        };
+        token::transfer(
+            CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+            net_amount,
+        )?;
+
+        // Update treasury.
+        let treasury = &mut ctx.accounts.protocol_treasury;
+        credit_treasury_fee(treasury, fee)?;
+
+        // Open a new loan position at the pool's fixed interest rate.
+        let borrower_account_key = borrower_account.key();
+        open_loan_position(
+            &mut ctx.accounts.loan_position,
+            borrower_account,
+            borrower_account_key,
+            amount,
+            fee,
+            RATE_MODE_FIXED,
+            institutional_pool.fixed_interest_rate,
+            now,
+            protocol_state.max_loans_per_borrower,
+        )?;
+        ctx.accounts.loan_position.maturity = now
+            .checked_add(INSTITUTIONAL_LOAN_TERM)
+            .ok_or(ZKError::MathOverflow)?;
+        ctx.accounts.loan_position.penalty_rate = protocol_state.overdue_penalty_rate;
+
+        // Update borrower's encrypted borrowed amount.
+        borrower_account.encrypted_principal = update_encrypted_value(
+            borrower_account.encrypted_principal.clone(),
+            amount,
+            true,
+        );
+
+        protocol_state.total_loans = protocol_state
+            .total_loans
+            .checked_add(amount)
+            .ok_or(ZKError::MathOverflow)?;
+        protocol_state.total_liquidity = protocol_state
+            .total_liquidity
+            .checked_sub(amount)
+            .ok_or(ZKError::MathOverflow)?;
+        update_utilization(protocol_state);
+
+        Ok(())
+    }
+
+    /// Delegator assigns a credit line that `delegate` may borrow against.
+    pub fn create_delegation(
+        ctx: Context<CreateDelegation>,
+        delegate: Pubkey,
+        max_borrow_amount: u64,
+    ) -> Result<()> {
+        let delegator = ctx.accounts.delegator.key();
+        require!(delegate != delegator, ZKError::InvalidDelegate);
+
+        let delegated = &mut ctx.accounts.delegated_borrower;
+        delegated.delegator = delegator;
+        delegated.delegate = delegate;
+        delegated.max_borrow_amount = max_borrow_amount;
+        delegated.borrowed_so_far = 0;
+        Ok(())
+    }
+
+    /// Delegated borrowing for DAOs/businesses that assign a credit line.
+    pub fn delegated_borrow(
+        ctx: Context<DelegatedBorrow>,
+        amount: u64,
+        zk_proof: Vec<u8>,
+    ) -> Result<()> {
+        require!(!ctx.accounts.protocol_state.borrows_frozen, ZKError::BorrowsFrozen);
+        require!(ctx.accounts.lending_pool.borrow_enabled, ZKError::AssetBorrowDisabled);
+        require!(zk_proof.len() <= MAX_PROOF_LEN, ZKError::ProofTooLarge);
+        log_compute(&ctx.accounts.protocol_state, "verify_zk_proof: start");
+        verify_circuit_proof(&ctx.accounts.verifying_key_registry, CIRCUIT_SOLVENCY, &zk_proof)?;
+        log_compute(&ctx.accounts.protocol_state, "verify_zk_proof: end");
+
+        let delegated = &ctx.accounts.delegated_borrower;
+        // Check that the delegate is borrowing on behalf of the delegator.
+        require!(
+            delegated.delegate == ctx.accounts.borrower.key(),
+            ZKError::UnauthorizedBorrower
+        );
+        require!(
+            delegated.delegate != delegated.delegator,
+            ZKError::InvalidDelegate
+        );
+        let borrowed_so_far = delegated
+            .borrowed_so_far
+            .checked_add(amount)
+            .ok_or(ZKError::MathOverflow)?;
+        require!(
+            borrowed_so_far <= delegated.max_borrow_amount,
+            ZKError::BorrowLimitExceeded
+        );
+
+        let clock = Clock::get()?;
+        let now = clock.unix_timestamp;
+        let borrower_account = &mut ctx.accounts.borrower_account;
+        let protocol_state = &mut ctx.accounts.protocol_state;
+
+        require_stake_settled(protocol_state, borrower_account, now)?;
+        if record_flash_attempt(
+            borrower_account,
+            protocol_state.min_collateral_lock_time,
+            now,
+        )? {
+            // Free the loan id reserved for this borrow; the recorded attempt still commits.
+            return ctx.accounts.loan_position.close(ctx.accounts.borrower.to_account_info());
+        }
+        borrower_account.borrow_timestamp = now;
+        ctx.accounts.delegated_borrower.borrowed_so_far = borrowed_so_far;
+
+        let price = collateral_price(
+            &ctx.accounts.collateral_pool,
+            ctx.accounts.price_oracle.as_ref(),
+            PRICE_SIDE_BORROW,
+            protocol_state.confidence_multiplier_bps,
+            now,
+        )?;
+        let collateral = EncryptedAmount {
+            value: borrowing_power(
+                borrower_account.encrypted_collateral.value,
+                &ctx.accounts.collateral_pool,
+                price,
+                now,
+            )?,
+        };
+        let debt_after = borrower_debt(borrower_account)
+            .checked_add(amount)
+            .ok_or(ZKError::MathOverflow)?;
+        require_collateral_ratio(&collateral, debt_after, protocol_state.min_collateral_ratio)?;
+
+        apply_borrow_rate_limit(&mut ctx.accounts.borrow_rate_limit, amount, now)?;
+        require_protocol_leverage(protocol_state, amount)?;
+        require_liquidity(protocol_state, &ctx.accounts.lending_pool, amount)?;
+        require_max_utilization(protocol_state, amount)?;
+
+        let fee = calculate_borrow_fee(amount, protocol_state, borrower_account, now)?;
+        let net_amount = amount.checked_sub(fee).ok_or(ZKError::MathOverflow)?;
+
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.lending_pool_token_account.to_account_info(),
+            to: ctx.accounts.user_borrow_token_account.to_account_info(),
+            authority: ctx.accounts.lending_pool_authority.to_account_info(),
+        };
+        token::transfer(
+            CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+            net_amount,
+        )?;
+
+        let treasury = &mut ctx.accounts.protocol_treasury;
+        credit_treasury_fee(treasury, fee)?;
+
+        let borrower_account_key = borrower_account.key();
+        open_loan_position(
+            &mut ctx.accounts.loan_position,
+            borrower_account,
+            borrower_account_key,
+            amount,
+            fee,
+            RATE_MODE_VARIABLE,
+            calculate_borrow_rate(protocol_state),
+            now,
+            protocol_state.max_loans_per_borrower,
+        )?;
+        ctx.accounts.loan_position.delegation = ctx.accounts.delegated_borrower.key();
+
+        borrower_account.encrypted_principal = update_encrypted_value(
+            borrower_account.encrypted_principal.clone(),
+            amount,
+            true,
+        );
+
+        protocol_state.total_loans = protocol_state
+            .total_loans
+            .checked_add(amount)
+            .ok_or(ZKError::MathOverflow)?;
+        protocol_state.total_liquidity = protocol_state
+            .total_liquidity
+            .checked_sub(amount)
+            .ok_or(ZKError::MathOverflow)?;
+        update_utilization(protocol_state);
+
+        Ok(())
+    }
+
+    /// Delegated borrowing authorized by a delegator's off-chain ed25519 signature.
+    ///
+    /// The transaction must include an ed25519 precompile instruction, immediately before this
+    /// one, verifying the delegator's signature over `(delegator, delegate, amount, expiry, nonce)`.
+    pub fn delegated_borrow_with_signature(
+        ctx: Context<DelegatedBorrowWithSignature>,
+        amount: u64,
+        expiry: i64,
+        nonce: u64,
+        zk_proof: Vec<u8>,
+    ) -> Result<()> {
+        require!(!ctx.accounts.protocol_state.borrows_frozen, ZKError::BorrowsFrozen);
+        require!(ctx.accounts.lending_pool.borrow_enabled, ZKError::AssetBorrowDisabled);
+        require!(zk_proof.len() <= MAX_PROOF_LEN, ZKError::ProofTooLarge);
+        log_compute(&ctx.accounts.protocol_state, "verify_zk_proof: start");
+        verify_circuit_proof(&ctx.accounts.verifying_key_registry, CIRCUIT_SOLVENCY, &zk_proof)?;
+        log_compute(&ctx.accounts.protocol_state, "verify_zk_proof: end");
+
+        require!(
+            ctx.accounts.delegator.key() != ctx.accounts.borrower.key(),
+            ZKError::InvalidDelegate
+        );
+
+        let clock = Clock::get()?;
+        let now = clock.unix_timestamp;
+
+        // Check the delegator signed this exact authorization.
+        let message = delegation_message(
+            &ctx.accounts.delegator.key(),
+            &ctx.accounts.borrower.key(),
+            amount,
+            expiry,
+            nonce,
+        );
+        verify_ed25519_instruction(
+            &ctx.accounts.instructions_sysvar,
+            &ctx.accounts.delegator.key(),
+            &message,
+        )?;
+        require!(now <= expiry, ZKError::DelegationExpired);
+        require!(
+            nonce > ctx.accounts.delegation_nonce.last_nonce,
+            ZKError::StaleNonce
+        );
+
+        let borrower_account = &mut ctx.accounts.borrower_account;
+        let protocol_state = &mut ctx.accounts.protocol_state;
+
+        require_stake_settled(protocol_state, borrower_account, now)?;
+        if record_flash_attempt(
+            borrower_account,
+            protocol_state.min_collateral_lock_time,
+            now,
+        )? {
+            // Free the loan id reserved for this borrow; the recorded attempt still commits.
+            return ctx.accounts.loan_position.close(ctx.accounts.borrower.to_account_info());
+        }
+        borrower_account.borrow_timestamp = now;
+
+        // Each authorization can be used once.
+        ctx.accounts.delegation_nonce.last_nonce = nonce;
+
+        let price = collateral_price(
+            &ctx.accounts.collateral_pool,
+            ctx.accounts.price_oracle.as_ref(),
+            PRICE_SIDE_BORROW,
+            protocol_state.confidence_multiplier_bps,
+            now,
+        )?;
+        let collateral = EncryptedAmount {
+            value: borrowing_power(
+                borrower_account.encrypted_collateral.value,
+                &ctx.accounts.collateral_pool,
+                price,
+                now,
+            )?,
+        };
+        let debt_after = borrower_debt(borrower_account)
+            .checked_add(amount)
+            .ok_or(ZKError::MathOverflow)?;
+        require_collateral_ratio(&collateral, debt_after, protocol_state.min_collateral_ratio)?;
+
+        apply_borrow_rate_limit(&mut ctx.accounts.borrow_rate_limit, amount, now)?;
+        require_protocol_leverage(protocol_state, amount)?;
+        require_liquidity(protocol_state, &ctx.accounts.lending_pool, amount)?;
+        require_max_utilization(protocol_state, amount)?;
+
+        let fee = calculate_borrow_fee(amount, protocol_state, borrower_account, now)?;
+        let net_amount = amount.checked_sub(fee).ok_or(ZKError::MathOverflow)?;
+
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.lending_pool_token_account.to_account_info(),
+            to: ctx.accounts.user_borrow_token_account.to_account_info(),
+            authority: ctx.accounts.lending_pool_authority.to_account_info(),
+        };
+        token::transfer(
+            CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+            net_amount,
+        )?;
+
+        let treasury = &mut ctx.accounts.protocol_treasury;
+        credit_treasury_fee(treasury, fee)?;
+
+        let borrower_account_key = borrower_account.key();
+        open_loan_position(
+            &mut ctx.accounts.loan_position,
+            borrower_account,
+            borrower_account_key,
+            amount,
+            fee,
+            RATE_MODE_VARIABLE,
+            calculate_borrow_rate(protocol_state),
+            now,
+            protocol_state.max_loans_per_borrower,
+        )?;
+
+        borrower_account.encrypted_principal = update_encrypted_value(
+            borrower_account.encrypted_principal.clone(),
+            amount,
+            true,
+        );
+
+        protocol_state.total_loans = protocol_state
+            .total_loans
+            .checked_add(amount)
+            .ok_or(ZKError::MathOverflow)?;
+        protocol_state.total_liquidity = protocol_state
+            .total_liquidity
+            .checked_sub(amount)
+            .ok_or(ZKError::MathOverflow)?;
+        update_utilization(protocol_state);
+
+        Ok(())
+    }
+
+    /// Repay a single loan position in full; includes accrued interest.
+    pub fn repay(ctx: Context<Repay>, loan_id: u64, amount: u64) -> Result<()> {
+        let clock = Clock::get()?;
+        let now = clock.unix_timestamp;
+
+        // Checks: everything is computed from read-only state before any token moves.
+        let loan_position = &ctx.accounts.loan_position;
+        require!(loan_position.loan_id == loan_id, ZKError::InvalidLoan);
+
+        // Calculate accrued interest at the loan's own rate.
+        let principal = loan_position.principal;
+        let accrued_interest = loan_position.accrued_interest;
+        let interest_due = loan_interest_due(loan_position, &ctx.accounts.protocol_state, now)?;
+
+        let total_due = principal
+            .checked_add(accrued_interest)
+            .and_then(|v| v.checked_add(interest_due))
+            .ok_or(ZKError::MathOverflow)?;
+        require!(amount >= total_due, ZKError::RepayExceedsBorrow);
+        let rebate = early_repay_rebate(
+            loan_position,
+            &ctx.accounts.protocol_state,
+            &ctx.accounts.protocol_treasury,
+            now,
+        );
+
+        // Token movements: transfer repayment tokens from borrower to lending pool.
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.user_borrow_token_account.to_account_info(),
+            to: ctx.accounts.lending_pool_token_account.to_account_info(),
+            authority: ctx.accounts.borrower.to_account_info(),
+        };
+        token::transfer(
+            CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+            amount,
+        )?;
+        if rebate > 0 {
+            let cpi_accounts = Transfer {
+                from: ctx.accounts.lending_pool_token_account.to_account_info(),
+                to: ctx.accounts.user_borrow_token_account.to_account_info(),
+                authority: ctx.accounts.lending_pool_authority.to_account_info(),
+            };
+            token::transfer(
+                CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+                rebate,
+            )?;
+            let treasury = &mut ctx.accounts.protocol_treasury;
+            treasury.total_fees_collected = treasury
+                .total_fees_collected
+                .checked_sub(rebate)
+                .ok_or(ZKError::MathOverflow)?;
+        }
+
+        // State mutations only once the repayment has landed.
+        apply_repayment(
+            &mut ctx.accounts.borrower_account,
+            &mut ctx.accounts.protocol_state,
+            &mut ctx.accounts.lending_pool,
+            principal,
+            accrued_interest,
+            amount,
+            now,
+        )
+    }
+
+    /// Repay a single loan position in full on the borrower's behalf, e.g. a delegator settling
+    /// a delegate's loan. Repaying a delegated loan frees its principal on the credit line.
+    pub fn repay_on_behalf(ctx: Context<RepayOnBehalf>, loan_id: u64, amount: u64) -> Result<()> {
+        let clock = Clock::get()?;
+        let now = clock.unix_timestamp;
+
+        let loan_position = &ctx.accounts.loan_position;
+        require!(loan_position.loan_id == loan_id, ZKError::InvalidLoan);
+        if loan_position.delegation != Pubkey::default() {
+            let delegated = ctx
+                .accounts
+                .delegated_borrower
+                .as_ref()
+                .ok_or(ZKError::InvalidDelegate)?;
+            require!(
+                delegated.key() == loan_position.delegation,
+                ZKError::InvalidDelegate
+            );
+        }
+
+        let principal = loan_position.principal;
+        let accrued_interest = loan_position.accrued_interest;
+        let interest_due = loan_interest_due(loan_position, &ctx.accounts.protocol_state, now)?;
+
+        let total_due = principal
+            .checked_add(accrued_interest)
+            .and_then(|v| v.checked_add(interest_due))
+            .ok_or(ZKError::MathOverflow)?;
+        require!(amount >= total_due, ZKError::RepayExceedsBorrow);
+
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.payer_token_account.to_account_info(),
+            to: ctx.accounts.lending_pool_token_account.to_account_info(),
+            authority: ctx.accounts.payer.to_account_info(),
+        };
+        token::transfer(
+            CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+            amount,
+        )?;
+
+        if let Some(delegated) = ctx.accounts.delegated_borrower.as_mut() {
+            if delegated.key() == ctx.accounts.loan_position.delegation {
+                delegated.borrowed_so_far = delegated.borrowed_so_far.saturating_sub(principal);
+            }
+        }
+
+        apply_repayment(
+            &mut ctx.accounts.borrower_account,
+            &mut ctx.accounts.protocol_state,
+            &mut ctx.accounts.lending_pool,
+            principal,
+            accrued_interest,
+            amount,
+            now,
+        )
+    }
+
+    /// Repay a lump sum across several of a borrower's loans without naming one, paying them
+    /// off in the protocol's `repay_order` (highest rate or oldest first) until `amount` no
+    /// longer covers the next loan in full. Repaid positions are closed; only the amount used
+    /// is transferred.
+    ///
+    /// `remaining_accounts` holds the borrower's writable loan positions, in any order.
+    /// Delegated loans must be repaid through `repay_on_behalf` with their credit line.
+    pub fn repay_all<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RepayAll<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+        let now = clock.unix_timestamp;
+        let borrower_account_key = ctx.accounts.borrower_account.key();
+        require!(!ctx.remaining_accounts.is_empty(), ZKError::InvalidLoan);
+
+        let mut loans = Vec::with_capacity(ctx.remaining_accounts.len());
+        for info in ctx.remaining_accounts.iter() {
+            require!(info.is_writable, ZKError::InvalidLoan);
+            let loan_position = Account::<LoanPosition>::try_from(info)?;
+            require!(
+                loan_position.borrower_account == borrower_account_key,
+                ZKError::InvalidLoan
+            );
+            require!(
+                loan_position.delegation == Pubkey::default(),
+                ZKError::InvalidDelegate
+            );
+            require!(
+                !ctx.remaining_accounts[..loans.len()].iter().any(|other| other.key == info.key),
+                ZKError::InvalidLoan
+            );
+            let rate = current_loan_rate(&loan_position, &ctx.accounts.protocol_state, now);
+            let interest_due =
+                loan_interest_due(&loan_position, &ctx.accounts.protocol_state, now)?;
+            let total_due = loan_position
+                .principal
+                .checked_add(loan_position.accrued_interest)
+                .and_then(|v| v.checked_add(interest_due))
+                .ok_or(ZKError::MathOverflow)?;
+            loans.push((loan_position, rate, total_due));
+        }
+
+        let (repaid, used) =
+            plan_repay_all(loans, ctx.accounts.protocol_state.repay_order, amount);
+        require!(!repaid.is_empty(), ZKError::RepayExceedsBorrow);
+
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.payer_token_account.to_account_info(),
+            to: ctx.accounts.lending_pool_token_account.to_account_info(),
+            authority: ctx.accounts.payer.to_account_info(),
+        };
+        token::transfer(
+            CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+            used,
+        )?;
+
+        let mut loan_ids = Vec::with_capacity(repaid.len());
+        for (loan_position, total_due) in repaid {
+            apply_repayment(
+                &mut ctx.accounts.borrower_account,
+                &mut ctx.accounts.protocol_state,
+                &mut ctx.accounts.lending_pool,
+                loan_position.principal,
+                loan_position.accrued_interest,
+                total_due,
+                now,
+            )?;
+            loan_ids.push(loan_position.loan_id);
+            loan_position.close(ctx.accounts.borrower.to_account_info())?;
+        }
+
+        emit!(RepayAllEvent {
+            borrower_account: borrower_account_key,
+            loan_ids,
+            amount_used: used,
+        });
+        Ok(())
+    }
+
+    /// Repay a loan with a different token by swapping it into the borrow token through a DEX.
+    ///
+    /// The swap is a generic CPI: `swap_data` is passed through to `swap_program` together with
+    /// `remaining_accounts`, and its proceeds are measured from the borrower's token balances.
+    pub fn repay_with_swap<'info>(
+        ctx: Context<'_, '_, '_, 'info, RepayWithSwap<'info>>,
+        loan_id: u64,
+        input_amount: u64,
+        min_out: u64,
+        swap_data: Vec<u8>,
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+        let now = clock.unix_timestamp;
+
+        let input_before = ctx.accounts.user_input_token_account.amount;
+        let output_before = ctx.accounts.user_borrow_token_account.amount;
+
+        // Swap the input token into the borrow token.
+        let swap_ix = Instruction {
+            program_id: ctx.accounts.swap_program.key(),
+            accounts: ctx
+                .remaining_accounts
+                .iter()
+                .map(|account| AccountMeta {
+                    pubkey: account.key(),
+                    is_signer: account.is_signer,
+                    is_writable: account.is_writable,
+                })
+                .collect(),
+            data: swap_data,
+        };
+        let mut swap_account_infos = ctx.remaining_accounts.to_vec();
+        swap_account_infos.push(ctx.accounts.swap_program.to_account_info());
+        invoke(&swap_ix, &swap_account_infos)?;
+
+        // Measure what the swap actually spent and produced.
+        ctx.accounts.user_input_token_account.reload()?;
+        ctx.accounts.user_borrow_token_account.reload()?;
+        let spent = input_before
+            .checked_sub(ctx.accounts.user_input_token_account.amount)
+            .ok_or(ZKError::MathOverflow)?;
+        require!(spent <= input_amount, ZKError::SwapInputExceeded);
+        let proceeds = ctx
+            .accounts
+            .user_borrow_token_account
+            .amount
+            .checked_sub(output_before)
+            .ok_or(ZKError::MathOverflow)?;
+        require!(proceeds >= min_out, ZKError::SlippageExceeded);
+
+        let loan_position = &ctx.accounts.loan_position;
+        require!(loan_position.loan_id == loan_id, ZKError::InvalidLoan);
+
+        let principal = loan_position.principal;
+        let accrued_interest = loan_position.accrued_interest;
+        let interest_due = loan_interest_due(loan_position, &ctx.accounts.protocol_state, now)?;
+
+        let total_due = principal
+            .checked_add(accrued_interest)
+            .and_then(|v| v.checked_add(interest_due))
+            .ok_or(ZKError::MathOverflow)?;
+        require!(proceeds >= total_due, ZKError::RepayExceedsBorrow);
+        let rebate = early_repay_rebate(
+            loan_position,
+            &ctx.accounts.protocol_state,
+            &ctx.accounts.protocol_treasury,
+            now,
+        );
+
+        // Transfer the swap proceeds from borrower to lending pool.
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.user_borrow_token_account.to_account_info(),
+            to: ctx.accounts.lending_pool_token_account.to_account_info(),
+            authority: ctx.accounts.borrower.to_account_info(),
+        };
+        token::transfer(
+            CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+            proceeds,
+        )?;
+        if rebate > 0 {
+            let cpi_accounts = Transfer {
+                from: ctx.accounts.lending_pool_token_account.to_account_info(),
+                to: ctx.accounts.user_borrow_token_account.to_account_info(),
+                authority: ctx.accounts.lending_pool_authority.to_account_info(),
+            };
+            token::transfer(
+                CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+                rebate,
+            )?;
+            let treasury = &mut ctx.accounts.protocol_treasury;
+            treasury.total_fees_collected = treasury
+                .total_fees_collected
+                .checked_sub(rebate)
+                .ok_or(ZKError::MathOverflow)?;
+        }
+
+        // State mutations only once the repayment has landed.
+        apply_repayment(
+            &mut ctx.accounts.borrower_account,
+            &mut ctx.accounts.protocol_state,
+            &mut ctx.accounts.lending_pool,
+            principal,
+            accrued_interest,
+            proceeds,
+            now,
+        )
+    }
+
+    /// Flash-borrow from lending pool escrow. The same transaction must later call `flash_repay`
+    /// for this flash loan, e.g. to unwind a position: repay the loan, withdraw the freed
+    /// collateral, swap enough of it through a DEX, then repay the flash loan.
+    pub fn flash_borrow(ctx: Context<FlashBorrow>, amount: u64) -> Result<()> {
+        let flash_loan = &mut ctx.accounts.flash_loan;
+        require!(flash_loan.amount == 0, ZKError::FlashLoanInProgress);
+        require_flash_repay(&ctx.accounts.instructions_sysvar, &flash_loan.key())?;
+
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.lending_pool_token_account.to_account_info(),
+            to: ctx.accounts.user_token_account.to_account_info(),
+            authority: ctx.accounts.lending_pool_authority.to_account_info(),
+        };
+        token::transfer(
+            CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+            amount,
+        )?;
+
+        flash_loan.borrower = ctx.accounts.borrower.key();
+        flash_loan.amount = amount;
+        flash_loan.fee = mul_div_up(amount as u128, FLASH_LOAN_FEE_BPS as u128, 10_000)? as u64;
+        Ok(())
+    }
+
+    /// Close the caller's flash loan by returning the amount borrowed plus the fee.
+    pub fn flash_repay(ctx: Context<FlashRepay>) -> Result<()> {
+        let flash_loan = &mut ctx.accounts.flash_loan;
+        require!(flash_loan.amount > 0, ZKError::FlashLoanNotRepaid);
+        let total = flash_loan
+            .amount
+            .checked_add(flash_loan.fee)
+            .ok_or(ZKError::MathOverflow)?;
+
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.user_token_account.to_account_info(),
+            to: ctx.accounts.lending_pool_token_account.to_account_info(),
+            authority: ctx.accounts.borrower.to_account_info(),
+        };
+        token::transfer(
+            CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+            total,
+        )?;
+        credit_treasury_fee(&mut ctx.accounts.protocol_treasury, flash_loan.fee)?;
+
+        flash_loan.amount = 0;
+        flash_loan.fee = 0;
+        Ok(())
+    }
+
+    /// Report the collateral price at which the borrower's position would become liquidatable,
+    /// as a `LiquidationPriceEvent`. Compare it against the pool's liquidation-side price.
+    pub fn liquidation_price(ctx: Context<LiquidationPrice>) -> Result<()> {
+        let borrower_account = &ctx.accounts.borrower_account;
+        emit!(LiquidationPriceEvent {
+            borrower_account: borrower_account.key(),
+            debt: borrower_debt(borrower_account),
+            collateral: borrower_account.encrypted_collateral.value,
+            liquidation_price: position_liquidation_price(
+                borrower_account,
+                &ctx.accounts.collateral_pool,
+                ctx.accounts.protocol_state.min_collateral_ratio,
+            )?,
+        });
+        Ok(())
+    }
+
+    /// Report a borrower's whole position in one call as a `PositionSummaryEvent`: collateral
+    /// value at the liquidation-side price, debt, health factor, accrued interest and
+    /// liquidation price. A borrower without debt reports a `u64::MAX` health factor and no
+    /// liquidation price.
+    pub fn position_summary(ctx: Context<PositionSummary>) -> Result<()> {
+        let borrower_account = &ctx.accounts.borrower_account;
+        let collateral_pool = &ctx.accounts.collateral_pool;
+        let protocol_state = &ctx.accounts.protocol_state;
+        let price = collateral_price(
+            collateral_pool,
+            ctx.accounts.price_oracle.as_ref(),
+            PRICE_SIDE_LIQUIDATION,
+            protocol_state.confidence_multiplier_bps,
+            Clock::get()?.unix_timestamp,
+        )?;
+        let collateral_value = collateral_value(
+            borrower_account.encrypted_collateral.value,
+            collateral_pool,
+            price,
+        )?;
+        let debt = borrower_debt(borrower_account);
+
+        emit!(PositionSummaryEvent {
+            borrower_account: borrower_account.key(),
+            collateral_value,
+            debt,
+            health_factor: health_factor(
+                collateral_value,
+                debt,
+                protocol_state.min_collateral_ratio,
+            ),
+            accrued_interest: borrower_account.accrued_interest,
+            liquidation_price: position_liquidation_price(
+                borrower_account,
+                collateral_pool,
+                protocol_state.min_collateral_ratio,
+            )?,
+        });
+        Ok(())
+    }
+
+    /// Authorize `keeper` to liquidate on the signer's behalf, routing the liquidation bonus to
+    /// `beneficiary`. Re-authorizing the same keeper updates the beneficiary.
+    pub fn authorize_liquidation_delegate(
+        ctx: Context<AuthorizeLiquidationDelegate>,
+        keeper: Pubkey,
+        beneficiary: Pubkey,
+    ) -> Result<()> {
+        let principal = ctx.accounts.principal.key();
+        require!(keeper != principal, ZKError::InvalidDelegate);
+
+        let liquidation_delegate = &mut ctx.accounts.liquidation_delegate;
+        liquidation_delegate.principal = principal;
+        liquidation_delegate.keeper = keeper;
+        liquidation_delegate.beneficiary = beneficiary;
+        Ok(())
+    }
+
+    /// Revoke a keeper's liquidation rights, refunding the delegate account's rent.
+    pub fn revoke_liquidation_delegate(_ctx: Context<RevokeLiquidationDelegate>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Liquidation step one: record that a position is unhealthy at the current slot.
+    pub fn mark_liquidatable(ctx: Context<MarkLiquidatable>) -> Result<()> {
+        let clock = Clock::get()?;
+        let borrower_account = &mut ctx.accounts.borrower_account;
+        let protocol_state = &ctx.accounts.protocol_state;
+        let price = collateral_price(
+            &ctx.accounts.collateral_pool,
+            ctx.accounts.price_oracle.as_ref(),
+            PRICE_SIDE_LIQUIDATION,
+            protocol_state.confidence_multiplier_bps,
+            clock.unix_timestamp,
+        )?;
+
+        // A protection buffer that restores health spares the position from being marked.
+        let drawn = draw_protection_buffer(
+            borrower_account,
+            &ctx.accounts.collateral_pool,
+            price,
+            protocol_state.min_collateral_ratio,
+        )?;
+        let liquidatable = is_liquidatable(
+            borrower_account,
+            &ctx.accounts.collateral_pool,
+            price,
+            protocol_state.min_collateral_ratio,
+        )?;
+        if drawn > 0 && !liquidatable {
+            return Ok(());
+        }
+        require!(liquidatable, ZKError::LiquidationNotAllowed);
+        // An unexpired mark keeps its original slot so the delay can't be restarted.
+        let marked_slot = borrower_account.liquidation_marked_slot;
+        require!(
+            marked_slot == 0 || clock.slot > marked_slot + LIQUIDATION_MARK_EXPIRY_SLOTS,
+            ZKError::LiquidationAlreadyMarked
+        );
+        borrower_account.liquidation_marked_slot = clock.slot;
+        Ok(())
+    }
+
+    /// Liquidation step two: partially liquidate 50% of collateral once the mark has matured.
+    /// The liquidator repays the debt the seized collateral covers into lending pool escrow.
+    pub fn execute_liquidation(
+        ctx: Context<ExecuteLiquidation>,
+        zk_proof: Vec<u8>,
+    ) -> Result<()> {
+        require!(zk_proof.len() <= MAX_PROOF_LEN, ZKError::ProofTooLarge);
+        log_compute(&ctx.accounts.protocol_state, "verify_zk_proof: start");
+        verify_circuit_proof(&ctx.accounts.verifying_key_registry, CIRCUIT_LIQUIDATION, &zk_proof)?;
+        log_compute(&ctx.accounts.protocol_state, "verify_zk_proof: end");
+
+        let clock = Clock::get()?;
+        let borrower_account = &mut ctx.accounts.borrower_account;
+        let collateral_pool = &mut ctx.accounts.collateral_pool;
+        let protocol_state = &ctx.accounts.protocol_state;
+
+        // The position must have been marked, the delay elapsed, and the mark not expired.
+        let marked_slot = borrower_account.liquidation_marked_slot;
+        require!(marked_slot > 0, ZKError::LiquidationNotMarked);
+        let grace_slots =
+            liquidation_grace_slots(borrower_debt(borrower_account), protocol_state);
+        require!(
+            clock.slot >= marked_slot + grace_slots,
+            ZKError::LiquidationDelayNotMet
+        );
+        require!(
+            clock.slot <= marked_slot + LIQUIDATION_MARK_EXPIRY_SLOTS,
+            ZKError::LiquidationNotMarked
+        );
+
+        // Draw on the protection buffer first; a position it restores is spared and unmarked.
+        let price = collateral_price(
+            collateral_pool,
+            ctx.accounts.price_oracle.as_ref(),
+            PRICE_SIDE_LIQUIDATION,
+            protocol_state.confidence_multiplier_bps,
+            clock.unix_timestamp,
+        )?;
+        let drawn = draw_protection_buffer(
+            borrower_account,
+            collateral_pool,
+            price,
+            protocol_state.min_collateral_ratio,
+        )?;
+        let liquidatable = is_liquidatable(
+            borrower_account,
+            collateral_pool,
+            price,
+            protocol_state.min_collateral_ratio,
+        )?;
+        if drawn > 0 && !liquidatable {
+            borrower_account.liquidation_marked_slot = 0;
+            return Ok(());
+        }
+
+        // Check that the position is still unhealthy.
+        require!(liquidatable, ZKError::LiquidationNotAllowed);
+
+        // Partial liquidation: liquidate 50% of the collateral.
+        let current_collateral = extract_value_from_encryption(borrower_account.encrypted_collateral.clone());
+        let liquidate_amount = current_collateral / 2;
+
+        // The seized collateral embeds the liquidation bonus; the protocol takes its share of it.
+        let bonus_bps = liquidation_bonus_bps(collateral_pool);
+        let bonus = (liquidate_amount as u128 * bonus_bps as u128
+            / (10_000 + bonus_bps) as u128) as u64;
+        let protocol_share = mul_div_up(
+            bonus as u128,
+            protocol_state.liquidation_protocol_share_bps as u128,
+            10_000,
+        )? as u64;
+        // A delegated keeper keeps the collateral covering debt; the rest of the bonus goes to
+        // the delegating institution's beneficiary.
+        let beneficiary_amount = match ctx.accounts.liquidation_delegate {
+            Some(_) => bonus.saturating_sub(protocol_share),
+            None => 0,
+        };
+        let liquidator_amount = liquidate_amount
+            .checked_sub(protocol_share)
+            .and_then(|v| v.checked_sub(beneficiary_amount))
+            .ok_or(ZKError::MathOverflow)?;
+
+        // Collateral seized net of the bonus covers debt, interest first and then principal.
+        let debt_covered = collateral_value(
+            liquidate_amount.checked_sub(bonus).ok_or(ZKError::MathOverflow)?,
+            collateral_pool,
+            price,
+        )?
+        .min(borrower_debt(borrower_account));
+
+        // The liquidator repays the covered debt into lending pool escrow in borrow tokens.
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.liquidator_repay_account.to_account_info(),
+            to: ctx.accounts.lending_pool_token_account.to_account_info(),
+            authority: ctx.accounts.liquidator.to_account_info(),
+        };
+        token::transfer(
+            CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+            debt_covered,
+        )?;
+
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.collateral_pool_token_account.to_account_info(),
+            to: ctx.accounts.liquidator_collateral_account.to_account_info(),
+            authority: ctx.accounts.collateral_pool_authority.to_account_info(),
+        };
+        token::transfer(
+            CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+            liquidator_amount,
+        )?;
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.collateral_pool_token_account.to_account_info(),
+            to: ctx.accounts.treasury_collateral_account.to_account_info(),
+            authority: ctx.accounts.collateral_pool_authority.to_account_info(),
+        };
+        token::transfer(
+            CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+            protocol_share,
+        )?;
+        if let Some(liquidation_delegate) = &ctx.accounts.liquidation_delegate {
+            let beneficiary_account = ctx
+                .accounts
+                .beneficiary_collateral_account
+                .as_ref()
+                .filter(|account| account.owner == liquidation_delegate.beneficiary)
+                .ok_or(ZKError::BeneficiaryAccountRequired)?;
+            let cpi_accounts = Transfer {
+                from: ctx.accounts.collateral_pool_token_account.to_account_info(),
+                to: beneficiary_account.to_account_info(),
+                authority: ctx.accounts.collateral_pool_authority.to_account_info(),
+            };
+            token::transfer(
+                CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+                beneficiary_amount,
+            )?;
+        }
+
+        // Top a thin liquidator bonus up to the floor from the rebate pool, when the liquidator
+        // passes the accounts to receive it in borrow tokens.
+        if let (Some(pool_authority), Some(rebate_account)) = (
+            &ctx.accounts.lending_pool_authority,
+            &ctx.accounts.liquidator_rebate_account,
+        ) {
+            let pool_token_account = &ctx.accounts.lending_pool_token_account;
+            require!(
+                rebate_account.mint == pool_token_account.mint,
+                ZKError::BorrowMintMismatch
+            );
+            let bonus_value = collateral_value(
+                bonus.saturating_sub(protocol_share),
+                collateral_pool,
+                price,
+            )?;
+            let treasury = &mut ctx.accounts.protocol_treasury;
+            let rebate = protocol_state
+                .liquidator_bonus_floor
+                .saturating_sub(bonus_value)
+                .min(treasury.liquidator_rebate_pool);
+            if rebate > 0 {
+                let cpi_accounts = Transfer {
+                    from: pool_token_account.to_account_info(),
+                    to: rebate_account.to_account_info(),
+                    authority: pool_authority.to_account_info(),
+                };
+                token::transfer(
+                    CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+                    rebate,
+                )?;
+                treasury.liquidator_rebate_pool = treasury
+                    .liquidator_rebate_pool
+                    .checked_sub(rebate)
+                    .ok_or(ZKError::MathOverflow)?;
+                emit!(LiquidatorRebateEvent {
+                    liquidator: ctx.accounts.liquidator.key(),
+                    bonus_value,
+                    rebate,
+                });
+            }
+        }
+
+        borrower_account.encrypted_collateral = update_encrypted_value(
+            borrower_account.encrypted_collateral.clone(),
+            liquidate_amount,
+            false,
+        );
+        borrower_account.liquidation_marked_slot = 0;
+        collateral_pool.total_collateral = collateral_pool
+            .total_collateral
+            .checked_sub(liquidate_amount)
+            .ok_or(ZKError::MathOverflow)?;
+        let protocol_state = &mut ctx.accounts.protocol_state;
+        adjust_protocol_collateral(protocol_state, liquidate_amount, false)?;
+        apply_liquidation_repayment(borrower_account, protocol_state, debt_covered)?;
+
+        // Debt left once the remaining collateral is worthless can never be recovered; write the
+        // principal off as bad debt for recapitalization bids to cover.
+        let remaining_debt = borrower_debt(borrower_account);
+        if remaining_debt > 0
+            && collateral_value(
+                borrower_account.encrypted_collateral.value,
+                collateral_pool,
+                price,
+            )? == 0
+        {
+            let principal = borrower_account.encrypted_principal.value;
+            protocol_state.total_loans = protocol_state.total_loans.saturating_sub(principal);
+            protocol_state.bad_debt = protocol_state
+                .bad_debt
+                .checked_add(principal)
+                .ok_or(ZKError::MathOverflow)?;
+            borrower_account.encrypted_principal = EncryptedAmount { value: 0 };
+            borrower_account.accrued_interest = 0;
+            borrower_account.borrow_timestamp = 0;
+            emit!(BadDebtEvent {
+                borrower_account: borrower_account.key(),
+                written_off: principal,
+                bad_debt: protocol_state.bad_debt,
+            });
+        }
+        update_utilization(protocol_state);
+
+        Ok(())
+    }
+
+    /// Governance: Propose a protocol parameter change, posting a refundable bond of at least
+    /// `min_proposal_bond` lamports. The bond is returned when an executed proposal is closed
+    /// and forfeited to the treasury when a failed one is.
+    pub fn propose_change(
+        ctx: Context<ProposeChange>,
+        proposal_type: u8,
+        new_value: u64,
+        bond: u64,
+    ) -> Result<()> {
+        post_proposal_bond(
+            &ctx.accounts.proposer,
+            &ctx.accounts.governance,
+            &ctx.accounts.protocol_state,
+            &ctx.accounts.system_program,
+            bond,
+        )?;
+
+        let governance = &mut ctx.accounts.governance;
+        governance.proposal_id = governance
+            .proposal_id
+            .checked_add(1)
+            .ok_or(ZKError::MathOverflow)?;
+        governance.proposal_type = proposal_type;
+        governance.new_value = new_value;
+        governance.votes = 0;
+        governance.pool = ctx.accounts.institutional_pool.key();
+        governance.proposer = ctx.accounts.proposer.key();
+        governance.voting_deadline = Clock::get()?.unix_timestamp + PROPOSAL_VOTING_PERIOD;
+        governance.bond = bond;
+        governance.bond_source = ctx.accounts.proposer.key();
+        Ok(())
+    }
+
+    /// Governance: Vote on a proposal (only allowed for authorized voters).
+    pub fn vote(ctx: Context<Vote>, proposal_id: u64, vote: bool) -> Result<()> {
+        let voter_key = ctx.accounts.voter.key();
+        require!(
+            ctx.accounts
+                .institutional_pool
+                .zk_whitelist
+                .iter()
+                .any(|entry| entry.institution == voter_key),
+            ZKError::UnauthorizedVoter
+        );
+
+        let governance = &mut ctx.accounts.governance;
+        require!(governance.proposal_id == proposal_id, ZKError::InvalidProposal);
+        require!(
+            Clock::get()?.unix_timestamp <= governance.voting_deadline,
+            ZKError::VotingClosed
+        );
+
+        if vote {
+            governance.votes = governance
+                .votes
+                .checked_add(1)
+                .ok_or(ZKError::MathOverflow)?;
+        } else {
+            // The tally never goes negative, so whitelist changes can't leave it below zero.
+            governance.votes = governance.votes.saturating_sub(1).max(0);
+        }
+        Ok(())
+    }
+
+    /// Governance: Close a proposal whose voting period has ended once it has been executed or
+    /// has failed, returning its rent to the proposer. An executed proposal's bond is refunded
+    /// with the rent; a failed proposal's bond is forfeited to the treasury.
+    pub fn close_proposal(ctx: Context<CloseProposal>) -> Result<()> {
+        let governance = &ctx.accounts.governance;
+        require!(
+            Clock::get()?.unix_timestamp > governance.voting_deadline
+                && (governance.executed || !proposal_passed(governance)),
+            ZKError::ProposalStillActive
+        );
+
+        let bond = governance.bond;
+        let forfeited = !governance.executed;
+        if bond > 0 {
+            if forfeited {
+                ctx.accounts.governance.sub_lamports(bond)?;
+                ctx.accounts.protocol_treasury.add_lamports(bond)?;
+            }
+            emit!(ProposalBondEvent {
+                proposal_id: ctx.accounts.governance.proposal_id,
+                bond_source: ctx.accounts.governance.bond_source,
+                bond,
+                forfeited,
+            });
+        }
+        Ok(())
+    }
+
+    /// Governance: Emit a proposal's derived lifecycle state, tally, and deadlines.
+    pub fn proposal_status(ctx: Context<ProposalStatus>) -> Result<()> {
+        let governance = &ctx.accounts.governance;
+        emit!(ProposalStatusEvent {
+            proposal_id: governance.proposal_id,
+            state: proposal_state(governance, Clock::get()?.unix_timestamp),
+            votes: governance.votes,
+            quorum: PROPOSAL_QUORUM,
+            voting_ends_at: governance.voting_deadline,
+        });
+        Ok(())
+    }
+
+    /// Governance: Execute a passed parameter change and record it in the change log.
+    pub fn execute_proposal(ctx: Context<ExecuteProposal>) -> Result<()> {
+        let governance = &mut ctx.accounts.governance;
+        require!(!governance.executed, ZKError::ProposalAlreadyExecuted);
+        require!(proposal_passed(governance), ZKError::ProposalNotPassed);
+
+        let protocol_state = &mut ctx.accounts.protocol_state;
+        let new_value = governance.new_value;
+        let old_value = match governance.proposal_type {
+            PROPOSAL_TYPE_BASE_INTEREST_RATE => {
+                let old_value = protocol_state.base_interest_rate as u64;
+                protocol_state.base_interest_rate =
+                    u8::try_from(new_value).map_err(|_| error!(ZKError::InvalidProposal))?;
+                old_value
+            }
+            PROPOSAL_TYPE_MIN_COLLATERAL_RATIO => {
+                let old_value = protocol_state.min_collateral_ratio as u64;
+                protocol_state.min_collateral_ratio =
+                    u16::try_from(new_value).map_err(|_| error!(ZKError::InvalidProposal))?;
+                old_value
+            }
+            PROPOSAL_TYPE_MIN_COLLATERAL_LOCK_TIME => {
+                let old_value = protocol_state.min_collateral_lock_time as u64;
+                protocol_state.min_collateral_lock_time =
+                    i64::try_from(new_value).map_err(|_| error!(ZKError::InvalidProposal))?;
+                old_value
+            }
+            PROPOSAL_TYPE_FEE_SWITCH => {
+                require!(new_value <= 1, ZKError::InvalidProposal);
+                let old_value = protocol_state.fees_enabled as u64;
+                protocol_state.fees_enabled = new_value == 1;
+                old_value
+            }
+            _ => return err!(ZKError::InvalidProposal),
+        };
+        governance.executed = true;
+
+        ctx.accounts.param_change_log.record(ParamChange {
+            proposal_id: governance.proposal_id,
+            proposal_type: governance.proposal_type,
+            old_value,
+            new_value,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        Ok(())
+    }
+
+    /// Governance: Propose listing a new collateral asset, posting a refundable bond like
+    /// `propose_change`.
+    pub fn propose_collateral_asset(
+        ctx: Context<ProposeCollateralAsset>,
+        asset_mint: Pubkey,
+        collateral_factor: u16,
+        liquidation_threshold: u16,
+        bond: u64,
+    ) -> Result<()> {
+        // Borrowing power must not exceed the liquidation threshold, and neither may exceed 100%.
+        require!(
+            collateral_factor <= liquidation_threshold && liquidation_threshold <= 10_000,
+            ZKError::InvalidCollateralParams
+        );
+        post_proposal_bond(
+            &ctx.accounts.proposer,
+            &ctx.accounts.governance,
+            &ctx.accounts.protocol_state,
+            &ctx.accounts.system_program,
+            bond,
+        )?;
+
+        let governance = &mut ctx.accounts.governance;
+        governance.proposal_id = governance
+            .proposal_id
+            .checked_add(1)
+            .ok_or(ZKError::MathOverflow)?;
+        governance.proposal_type = PROPOSAL_TYPE_ADD_COLLATERAL_ASSET;
+        governance.new_value = 0;
+        governance.votes = 0;
+        governance.executed = false;
+        governance.asset_mint = asset_mint;
+        governance.collateral_factor = collateral_factor;
+        governance.liquidation_threshold = liquidation_threshold;
+        governance.pool = ctx.accounts.institutional_pool.key();
+        governance.proposer = ctx.accounts.proposer.key();
+        governance.voting_deadline = Clock::get()?.unix_timestamp + PROPOSAL_VOTING_PERIOD;
+        governance.bond = bond;
+        governance.bond_source = ctx.accounts.proposer.key();
+        Ok(())
+    }
+
+    /// Governance: Execute a passed collateral listing by creating its collateral pool.
+    pub fn execute_add_collateral_asset(ctx: Context<ExecuteAddCollateralAsset>) -> Result<()> {
+        let governance = &mut ctx.accounts.governance;
+        require!(
+            governance.proposal_type == PROPOSAL_TYPE_ADD_COLLATERAL_ASSET,
+            ZKError::InvalidProposal
+        );
+        require!(!governance.executed, ZKError::ProposalAlreadyExecuted);
+        require!(proposal_passed(governance), ZKError::ProposalNotPassed);
+        governance.executed = true;
+
+        let collateral_pool = &mut ctx.accounts.collateral_pool;
+        collateral_pool.asset_mint = governance.asset_mint;
+        collateral_pool.total_collateral = 0;
+        collateral_pool.collateral_factor = governance.collateral_factor;
+        collateral_pool.liquidation_threshold = governance.liquidation_threshold;
+        collateral_pool.base_index = 0;
+        collateral_pool.current_index = 0;
+        collateral_pool.liquidation_bonus_bps = 0; // protocol default
+        collateral_pool.oracle_kind = ORACLE_KIND_NONE;
+        collateral_pool.min_stake = 0; // any stake size until configured
+        collateral_pool.factor_decay_bps_per_day = 0; // no collateral factor decay
+
+        let asset_registry = &mut ctx.accounts.asset_registry;
+        require!(
+            asset_registry.assets.len() < MAX_LISTED_ASSETS,
+            ZKError::AssetRegistryFull
+        );
+        asset_registry.assets.push(ListedAsset {
+            mint: governance.asset_mint,
+            collateral_pool: collateral_pool.key(),
+        });
+        Ok(())
+    }
+
+    /// Rebalance collateral: adjust collateral without revealing details. Added collateral moves
+    /// into the pool's escrow and released collateral goes back to the owner.
+    pub fn rebalance_collateral(
+        ctx: Context<RebalanceCollateral>,
+        amount: u64,
+        is_reduction: bool,
+        zk_proof: Vec<u8>,
+    ) -> Result<()> {
+        require!(zk_proof.len() <= MAX_PROOF_LEN, ZKError::ProofTooLarge);
+        log_compute(&ctx.accounts.protocol_state, "verify_zk_proof: start");
+        verify_circuit_proof(&ctx.accounts.verifying_key_registry, CIRCUIT_COLLATERAL, &zk_proof)?;
+        log_compute(&ctx.accounts.protocol_state, "verify_zk_proof: end");
+        let borrower_account = &ctx.accounts.borrower_account;
+        let protocol_state = &ctx.accounts.protocol_state;
+
+        // Reductions must leave a safety margin above the liquidation threshold, valuing the
+        // remaining collateral the way `borrow` does.
+        if is_reduction {
+            let now = Clock::get()?.unix_timestamp;
+            // Co-signed collateral goes back to its co-signers, not the borrower.
+            let own_collateral = borrower_account
+                .encrypted_collateral
+                .value
+                .saturating_sub(borrower_account.cosigned_collateral);
+            require!(amount <= own_collateral, ZKError::InsufficientCollateral);
+            let remaining = borrower_account
+                .encrypted_collateral
+                .value
+                .checked_sub(amount)
+                .ok_or(ZKError::InsufficientCollateral)?;
+            let price = collateral_price(
+                &ctx.accounts.collateral_pool,
+                ctx.accounts.price_oracle.as_ref(),
+                PRICE_SIDE_BORROW,
+                protocol_state.confidence_multiplier_bps,
+                now,
+            )?;
+            let power = borrowing_power(remaining, &ctx.accounts.collateral_pool, price, now)?;
+            require!(
+                rebalance_keeps_buffer(power, borrower_debt(borrower_account), protocol_state),
+                ZKError::InsufficientCollateral
+            );
+
+            let cpi_accounts = Transfer {
+                from: ctx.accounts.collateral_pool_token_account.to_account_info(),
+                to: ctx.accounts.user_collateral_account.to_account_info(),
+                authority: ctx.accounts.collateral_pool_authority.to_account_info(),
+            };
+            token::transfer(
+                CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+                amount,
+            )?;
+        } else {
+            let cpi_accounts = Transfer {
+                from: ctx.accounts.user_collateral_account.to_account_info(),
+                to: ctx.accounts.collateral_pool_token_account.to_account_info(),
+                authority: ctx.accounts.borrower.to_account_info(),
+            };
+            token::transfer(
+                CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+                amount,
+            )?;
+        }
+
+        let borrower_account = &mut ctx.accounts.borrower_account;
+        borrower_account.encrypted_collateral = update_encrypted_value(
+            borrower_account.encrypted_collateral.clone(),
+            amount,
+            !is_reduction,
+        );
+        let collateral_pool = &mut ctx.accounts.collateral_pool;
+        collateral_pool.total_collateral = if is_reduction {
+            collateral_pool.total_collateral.checked_sub(amount)
+        } else {
+            collateral_pool.total_collateral.checked_add(amount)
+        }
+        .ok_or(ZKError::MathOverflow)?;
+        adjust_protocol_collateral(&mut ctx.accounts.protocol_state, amount, !is_reduction)?;
+        Ok(())
+    }
+
+    /// Deposit liquidity into a lending pool and open (or top up) the lender's position.
+    pub fn deposit_liquidity(ctx: Context<DepositLiquidity>, amount: u64) -> Result<()> {
+        let clock = Clock::get()?;
+        let now = clock.unix_timestamp;
+
+        // Transfer liquidity tokens from lender to lending pool escrow.
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.lender_token_account.to_account_info(),
+            to: ctx.accounts.lending_pool_token_account.to_account_info(),
+            authority: ctx.accounts.lender.to_account_info(),
+        };
+        token::transfer(
+            CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+            amount,
+        )?;
+
+        let lender_position = &mut ctx.accounts.lender_position;
+        // Bank rewards earned on the old principal before it changes.
+        settle_lender_rewards(lender_position, &ctx.accounts.lending_pool)?;
+        // Tenure starts with the first deposit; top-ups don't reset it.
+        if lender_position.principal == 0 {
+            lender_position.lender = ctx.accounts.lender.key();
+            lender_position.lending_pool = ctx.accounts.lending_pool.key();
+            lender_position.deposit_timestamp = now;
+        }
+        lender_position.principal = lender_position
+            .principal
+            .checked_add(amount)
+            .ok_or(ZKError::MathOverflow)?;
+
+        let lending_pool = &mut ctx.accounts.lending_pool;
+        lending_pool.total_liquidity = lending_pool
+            .total_liquidity
+            .checked_add(amount)
+            .ok_or(ZKError::MathOverflow)?;
+
+        let protocol_state = &mut ctx.accounts.protocol_state;
+        protocol_state.total_liquidity = protocol_state
+            .total_liquidity
+            .checked_add(amount)
+            .ok_or(ZKError::MathOverflow)?;
+        update_utilization(protocol_state);
+
+        Ok(())
+    }
+
+    /// Donate tokens straight into a lending pool's lender rewards, shared pro rata by lenders.
+    pub fn donate_rewards(ctx: Context<DonateRewards>, amount: u64) -> Result<()> {
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.donor_token_account.to_account_info(),
+            to: ctx.accounts.lending_pool_token_account.to_account_info(),
+            authority: ctx.accounts.donor.to_account_info(),
+        };
+        token::transfer(
+            CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+            amount,
+        )?;
+        distribute_lender_rewards(
+            &mut ctx.accounts.lending_pool,
+            amount,
+            Clock::get()?.unix_timestamp,
+        )
+    }
+
+    /// Emit a lending pool's liquidity, utilization, rates, and realized supply APY.
+    pub fn pool_stats(ctx: Context<PoolStats>) -> Result<()> {
+        let lending_pool = &ctx.accounts.lending_pool;
+        let protocol_state = &ctx.accounts.protocol_state;
+        emit!(PoolStatsEvent {
+            lending_pool: lending_pool.key(),
+            total_liquidity: lending_pool.total_liquidity,
+            utilization_rate: protocol_state.utilization_rate,
+            borrow_rate: calculate_borrow_rate(protocol_state),
+            rewards_accrued_in_window: lending_pool.rewards_accrued_in_window,
+            supply_apy_bps: realized_supply_apy_bps(lending_pool, Clock::get()?.unix_timestamp),
+        });
+        Ok(())
+    }
+
+    /// Admin: one-time seeding of an empty pool at launch so the first borrows can succeed.
+    pub fn bootstrap_liquidity(ctx: Context<BootstrapLiquidity>, amount: u64) -> Result<()> {
+        let protocol_state = &ctx.accounts.protocol_state;
+        require!(
+            !protocol_state.liquidity_bootstrapped && protocol_state.total_liquidity == 0,
+            ZKError::BootstrapUnavailable
+        );
+
+        // Transfer seed liquidity from the admin to lending pool escrow.
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.admin_token_account.to_account_info(),
+            to: ctx.accounts.lending_pool_token_account.to_account_info(),
+            authority: ctx.accounts.admin.to_account_info(),
+        };
+        token::transfer(
+            CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+            amount,
+        )?;
+
+        let lending_pool = &mut ctx.accounts.lending_pool;
+        lending_pool.total_liquidity = lending_pool
+            .total_liquidity
+            .checked_add(amount)
+            .ok_or(ZKError::MathOverflow)?;
+
+        let protocol_state = &mut ctx.accounts.protocol_state;
+        protocol_state.liquidity_bootstrapped = true;
+        protocol_state.total_liquidity = amount;
+        update_utilization(protocol_state);
+        Ok(())
+    }
+
+    /// Claim the lender's share of pool rewards, boosted by deposit tenure.
+    pub fn claim_rewards(ctx: Context<ClaimRewards>) -> Result<()> {
+        let clock = Clock::get()?;
+        let now = clock.unix_timestamp;
+
+        let lender_position = &mut ctx.accounts.lender_position;
+        let lending_pool = &mut ctx.accounts.lending_pool;
+        let payout = take_lender_rewards(lender_position, lending_pool, now)?;
+
+        // Transfer rewards from lending pool escrow to lender.
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.lending_pool_token_account.to_account_info(),
+            to: ctx.accounts.lender_token_account.to_account_info(),
+            authority: ctx.accounts.lending_pool_authority.to_account_info(),
+        };
+        token::transfer(
+            CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+            payout,
+        )?;
+
+        lender_position.rewards_claimed = lender_position
+            .rewards_claimed
+            .checked_add(payout)
+            .ok_or(ZKError::MathOverflow)?;
+
+        Ok(())
+    }
+
+    /// Compound the lender's claimable rewards into their principal instead of paying them out.
+    ///
+    /// The rewards already sit in lending pool escrow, so no tokens move; they are re-lent as
+    /// liquidity and earn a larger share of future rewards.
+    pub fn compound_rewards(ctx: Context<CompoundRewards>) -> Result<()> {
+        let clock = Clock::get()?;
+        let lender_position = &mut ctx.accounts.lender_position;
+        let lending_pool = &mut ctx.accounts.lending_pool;
+        let amount = take_lender_rewards(lender_position, lending_pool, clock.unix_timestamp)?;
+        require!(amount > 0, ZKError::NoRewardsToClaim);
+
+        lender_position.principal = lender_position
+            .principal
+            .checked_add(amount)
+            .ok_or(ZKError::MathOverflow)?;
+        lender_position.rewards_claimed = lender_position
+            .rewards_claimed
+            .checked_add(amount)
+            .ok_or(ZKError::MathOverflow)?;
+        lending_pool.total_liquidity = lending_pool
+            .total_liquidity
+            .checked_add(amount)
+            .ok_or(ZKError::MathOverflow)?;
+
+        let protocol_state = &mut ctx.accounts.protocol_state;
+        protocol_state.total_liquidity = protocol_state
+            .total_liquidity
+            .checked_add(amount)
+            .ok_or(ZKError::MathOverflow)?;
+        update_utilization(protocol_state);
+        Ok(())
+    }
+
+    /// Exit a lending position: withdraw the lender's principal together with any unclaimed
+    /// rewards in one transfer, and close the position.
+    pub fn exit_lending(ctx: Context<ExitLending>) -> Result<()> {
+        let clock = Clock::get()?;
+        let now = clock.unix_timestamp;
+
+        let lender_position = &mut ctx.accounts.lender_position;
+        let lending_pool = &mut ctx.accounts.lending_pool;
+        let principal = lender_position.principal;
+        let rewards = take_lender_rewards(lender_position, lending_pool, now)?;
+        require!(
+            principal <= ctx.accounts.protocol_state.total_liquidity,
+            ZKError::InsufficientLiquidity
+        );
+        let payout = principal.checked_add(rewards).ok_or(ZKError::MathOverflow)?;
+
+        // Transfer principal and rewards from lending pool escrow to the lender.
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.lending_pool_token_account.to_account_info(),
+            to: ctx.accounts.lender_token_account.to_account_info(),
+            authority: ctx.accounts.lending_pool_authority.to_account_info(),
+        };
+        token::transfer(
+            CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+            payout,
+        )?;
+
+        lending_pool.total_liquidity = lending_pool
+            .total_liquidity
+            .checked_sub(principal)
+            .ok_or(ZKError::MathOverflow)?;
+
+        let protocol_state = &mut ctx.accounts.protocol_state;
+        protocol_state.total_liquidity = protocol_state
+            .total_liquidity
+            .checked_sub(principal)
+            .ok_or(ZKError::MathOverflow)?;
+        update_utilization(protocol_state);
+        Ok(())
+    }
+
+    /// Snapshot the lender's claimable rewards into a transferable single-token receipt.
+    ///
+    /// The rewards are reserved out of the pool; whoever holds the receipt can redeem them.
+    pub fn mint_reward_receipt(ctx: Context<MintRewardReceipt>) -> Result<()> {
+        let clock = Clock::get()?;
+        let lender_position = &mut ctx.accounts.lender_position;
+        let lending_pool = &mut ctx.accounts.lending_pool;
+        let amount = take_lender_rewards(lender_position, lending_pool, clock.unix_timestamp)?;
+        require!(amount > 0, ZKError::NoRewardsToClaim);
+
+        // Mint the receipt token, signed by the receipt authority PDA.
+        let seeds: &[&[u8]] = &[b"receipt_authority", &[ctx.bumps.receipt_authority]];
+        let cpi_accounts = MintTo {
+            mint: ctx.accounts.receipt_mint.to_account_info(),
+            to: ctx.accounts.receipt_token_account.to_account_info(),
+            authority: ctx.accounts.receipt_authority.to_account_info(),
+        };
+        token::mint_to(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                cpi_accounts,
+                &[seeds],
+            ),
+            1,
+        )?;
+
+        lender_position.rewards_claimed = lender_position
+            .rewards_claimed
+            .checked_add(amount)
+            .ok_or(ZKError::MathOverflow)?;
+
+        let reward_receipt = &mut ctx.accounts.reward_receipt;
+        reward_receipt.lending_pool = lending_pool.key();
+        reward_receipt.mint = ctx.accounts.receipt_mint.key();
+        reward_receipt.amount = amount;
+        Ok(())
+    }
+
+    /// Burn a reward receipt and pay its snapshotted rewards to the current holder.
+    pub fn redeem_reward_receipt(ctx: Context<RedeemRewardReceipt>) -> Result<()> {
+        let cpi_accounts = Burn {
+            mint: ctx.accounts.receipt_mint.to_account_info(),
+            from: ctx.accounts.holder_receipt_account.to_account_info(),
+            authority: ctx.accounts.holder.to_account_info(),
+        };
+        token::burn(
+            CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+            1,
+        )?;
+
+        // Transfer the reserved rewards from lending pool escrow to the holder.
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.lending_pool_token_account.to_account_info(),
+            to: ctx.accounts.holder_token_account.to_account_info(),
+            authority: ctx.accounts.lending_pool_authority.to_account_info(),
+        };
+        token::transfer(
+            CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+            ctx.accounts.reward_receipt.amount,
+        )?;
+        Ok(())
+    }
+
+    /// Claim compensation for a governance-approved loss, paid from the insurance fund.
+    ///
+    /// Payouts are capped by the lender's principal less what it has already claimed on this
+    /// event, the loss still unpaid on the event, and the fund balance, so a claim larger than the
+    /// fund is partially paid and can be topped up later.
+    pub fn claim_insurance(ctx: Context<ClaimInsurance>, amount: u64) -> Result<()> {
+        let loss_event = &mut ctx.accounts.loss_event;
+        require!(
+            loss_event.proposal_type == PROPOSAL_TYPE_LOSS_EVENT,
+            ZKError::InvalidProposal
+        );
+        require!(proposal_passed(loss_event), ZKError::ProposalNotPassed);
+        require!(!loss_event.executed, ZKError::ProposalAlreadyExecuted);
+
+        let treasury = &mut ctx.accounts.protocol_treasury;
+        let insurance_claim = &mut ctx.accounts.insurance_claim;
+        let payout = insurance_payout(
+            amount,
+            ctx.accounts.lender_position.principal,
+            insurance_claim.claimed,
+            loss_event.new_value,
+            treasury.insurance_fund,
+        );
+        require!(payout > 0, ZKError::InsufficientTreasuryReserves);
+
+        // Transfer the payout from lending pool escrow, where treasury funds are held, to the lender.
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.lending_pool_token_account.to_account_info(),
+            to: ctx.accounts.lender_token_account.to_account_info(),
+            authority: ctx.accounts.lending_pool_authority.to_account_info(),
+        };
+        token::transfer(
+            CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+            payout,
+        )?;
+
+        treasury.insurance_fund = treasury
+            .insurance_fund
+            .checked_sub(payout)
+            .ok_or(ZKError::MathOverflow)?;
+        loss_event.new_value = loss_event
+            .new_value
+            .checked_sub(payout)
+            .ok_or(ZKError::MathOverflow)?;
+        insurance_claim.loss_event = loss_event.key();
+        insurance_claim.lender_position = ctx.accounts.lender_position.key();
+        insurance_claim.claimed = insurance_claim
+            .claimed
+            .checked_add(payout)
+            .ok_or(ZKError::MathOverflow)?;
+        // A fully compensated loss event is closed to further claims.
+        if loss_event.new_value == 0 {
+            loss_event.executed = true;
+        }
+        Ok(())
+    }
+
+    /// Bid in the bad-debt auction: contribute up to `bid` tokens toward the protocol's bad debt
+    /// in exchange for a `RecapClaim` on future treasury fees worth the amount covered plus the
+    /// recapitalization premium.
+    pub fn bad_debt_auction(ctx: Context<BadDebtAuction>, bid: u64) -> Result<()> {
+        let covered = bid.min(ctx.accounts.protocol_state.bad_debt);
+        require!(covered > 0, ZKError::NoBadDebt);
+
+        // Transfer the bid from the bidder to lending pool escrow, restoring the lost liquidity.
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.bidder_token_account.to_account_info(),
+            to: ctx.accounts.lending_pool_token_account.to_account_info(),
+            authority: ctx.accounts.bidder.to_account_info(),
+        };
+        token::transfer(
+            CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+            covered,
+        )?;
+
+        let protocol_state = &mut ctx.accounts.protocol_state;
+        protocol_state.bad_debt = protocol_state
+            .bad_debt
+            .checked_sub(covered)
+            .ok_or(ZKError::MathOverflow)?;
+        protocol_state.total_liquidity = protocol_state
+            .total_liquidity
+            .checked_add(covered)
+            .ok_or(ZKError::MathOverflow)?;
+        update_utilization(protocol_state);
+        let lending_pool = &mut ctx.accounts.lending_pool;
+        lending_pool.total_liquidity = lending_pool
+            .total_liquidity
+            .checked_add(covered)
+            .ok_or(ZKError::MathOverflow)?;
+
+        let owed = mul_div_down(
+            covered as u128,
+            10_000 + protocol_state.recap_premium_bps as u128,
+            10_000,
+        )? as u64;
+        let recap_claim = &mut ctx.accounts.recap_claim;
+        recap_claim.bidder = ctx.accounts.bidder.key();
+        recap_claim.covered = recap_claim
+            .covered
+            .checked_add(covered)
+            .ok_or(ZKError::MathOverflow)?;
+        recap_claim.owed = recap_claim
+            .owed
+            .checked_add(owed)
+            .ok_or(ZKError::MathOverflow)?;
+        Ok(())
+    }
+
+    /// Redeem a `RecapClaim` against treasury reserves, paying as much as the reserves allow.
+    pub fn claim_recap(ctx: Context<ClaimRecap>) -> Result<()> {
+        let treasury = &mut ctx.accounts.protocol_treasury;
+        let payout = ctx.accounts.recap_claim.owed.min(treasury.total_fees_collected);
+        require!(payout > 0, ZKError::InsufficientTreasuryReserves);
+
+        // Pay out from lending pool escrow, where treasury funds are held.
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.lending_pool_token_account.to_account_info(),
+            to: ctx.accounts.bidder_token_account.to_account_info(),
+            authority: ctx.accounts.lending_pool_authority.to_account_info(),
+        };
+        token::transfer(
+            CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+            payout,
+        )?;
+
+        treasury.total_fees_collected = treasury
+            .total_fees_collected
+            .checked_sub(payout)
+            .ok_or(ZKError::MathOverflow)?;
+        let recap_claim = &mut ctx.accounts.recap_claim;
+        recap_claim.owed = recap_claim
+            .owed
+            .checked_sub(payout)
+            .ok_or(ZKError::MathOverflow)?;
+        Ok(())
+    }
+
+    /// Creates a keeper-managed collateral buffer for the owner's borrower account.
+    ///
+    /// The buffer's token account must be owned by the buffer PDA and is pre-funded by the owner.
+    pub fn initialize_collateral_buffer(
+        ctx: Context<InitializeCollateralBuffer>,
+        keeper: Pubkey,
+    ) -> Result<()> {
+        let collateral_buffer = &mut ctx.accounts.collateral_buffer;
+        collateral_buffer.borrower_account = ctx.accounts.borrower_account.key();
+        collateral_buffer.keeper = keeper;
+        collateral_buffer.token_account = ctx.accounts.buffer_token_account.key();
+        collateral_buffer.bump = ctx.bumps.collateral_buffer;
+        Ok(())
+    }
+
+    /// Keeper hook: top up a position from its collateral buffer to restore `target_health`,
+    /// valuing collateral at the liquidation-side price and the pool's effective factor.
+    pub fn auto_rebalance(ctx: Context<AutoRebalance>, target_health: u64) -> Result<()> {
+        require!(target_health > HEALTH_FACTOR_ONE, ZKError::InvalidHealthTarget);
+
+        let now = Clock::get()?.unix_timestamp;
+        let protocol_state = &ctx.accounts.protocol_state;
+        let collateral_pool = &ctx.accounts.collateral_pool;
+        let borrower_account = &ctx.accounts.borrower_account;
+        let price = collateral_price(
+            collateral_pool,
+            ctx.accounts.price_oracle.as_ref(),
+            PRICE_SIDE_LIQUIDATION,
+            protocol_state.confidence_multiplier_bps,
+            now,
+        )?;
+        require!(
+            priced_health(
+                borrower_account,
+                collateral_pool,
+                price,
+                now,
+                protocol_state.min_collateral_ratio,
+            )? < target_health,
+            ZKError::RebalanceNotNeeded
+        );
+
+        // Top up as much of the shortfall as the buffer can cover.
+        let required = collateral_for_health(
+            borrower_debt(borrower_account),
+            target_health,
+            protocol_state.min_collateral_ratio,
+        )?;
+        let power = borrowing_power(
+            borrower_account.encrypted_collateral.value,
+            collateral_pool,
+            price,
+            now,
+        )?;
+        let shortfall =
+            collateral_for_power(required.saturating_sub(power), collateral_pool, price, now)?;
+        let top_up = shortfall.min(ctx.accounts.buffer_token_account.amount);
+        require!(top_up > 0, ZKError::InsufficientCollateral);
+
+        // Transfer buffer tokens into the collateral pool escrow, signed by the buffer PDA.
+        let borrower_account_key = borrower_account.key();
+        let seeds: &[&[u8]] = &[
+            b"collateral_buffer",
+            borrower_account_key.as_ref(),
+            &[ctx.accounts.collateral_buffer.bump],
+        ];
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.buffer_token_account.to_account_info(),
+            to: ctx.accounts.collateral_pool_token_account.to_account_info(),
+            authority: ctx.accounts.collateral_buffer.to_account_info(),
+        };
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                cpi_accounts,
+                &[seeds],
+            ),
+            top_up,
+        )?;
+
+        let borrower_account = &mut ctx.accounts.borrower_account;
+        borrower_account.encrypted_collateral = update_encrypted_value(
+            borrower_account.encrypted_collateral.clone(),
+            top_up,
+            true,
+        );
+        let collateral_pool = &mut ctx.accounts.collateral_pool;
+        collateral_pool.total_collateral = collateral_pool
+            .total_collateral
+            .checked_add(top_up)
+            .ok_or(ZKError::MathOverflow)?;
+        adjust_protocol_collateral(&mut ctx.accounts.protocol_state, top_up, true)?;
+        Ok(())
+    }
+
+    /// Admin: correct a collateral pool's bookkeeping to the actual escrow token balance.
+    pub fn reconcile_collateral(ctx: Context<ReconcileCollateral>) -> Result<()> {
+        let collateral_pool = &mut ctx.accounts.collateral_pool;
+        let before = collateral_pool.total_collateral;
+        let after = ctx.accounts.collateral_pool_token_account.amount;
+        collateral_pool.total_collateral = after;
+        adjust_protocol_collateral(
+            &mut ctx.accounts.protocol_state,
+            before.abs_diff(after),
+            after > before,
+        )?;
+
+        emit!(ReconcileEvent {
+            collateral_pool: collateral_pool.key(),
+            before,
+            after,
+        });
+        Ok(())
+    }
+
+    /// Return dust collateral left on a debt-free account (e.g., after liquidation) to its owner.
+    pub fn sweep_dust_collateral(ctx: Context<SweepDustCollateral>) -> Result<()> {
+        let borrower_account = &mut ctx.accounts.borrower_account;
+        require!(
+            borrower_debt(borrower_account) == 0,
+            ZKError::OutstandingDebt
+        );
+        let dust = extract_value_from_encryption(borrower_account.encrypted_collateral.clone());
+        require!(
+            dust > 0 && dust < DUST_COLLATERAL_THRESHOLD,
+            ZKError::NotDustCollateral
+        );
+
+        // Transfer the residual collateral from the pool escrow back to the owner.
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.collateral_pool_token_account.to_account_info(),
+            to: ctx.accounts.owner_collateral_account.to_account_info(),
+            authority: ctx.accounts.collateral_pool_authority.to_account_info(),
+        };
+        token::transfer(
+            CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+            dust,
+        )?;
+
+        borrower_account.encrypted_collateral = reset_encryption();
+        let collateral_pool = &mut ctx.accounts.collateral_pool;
+        collateral_pool.total_collateral = collateral_pool
+            .total_collateral
+            .checked_sub(dust)
+            .ok_or(ZKError::MathOverflow)?;
+        adjust_protocol_collateral(&mut ctx.accounts.protocol_state, dust, false)?;
+        Ok(())
+    }
+
+    /// Cache the borrower's current health factor so liquidation bots can cheaply filter candidates.
+    /// Collateral is valued at the liquidation-side price and the pool's effective factor.
+    pub fn snapshot_solvency(ctx: Context<SnapshotSolvency>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let protocol_state = &ctx.accounts.protocol_state;
+        let collateral_pool = &ctx.accounts.collateral_pool;
+        let price = collateral_price(
+            collateral_pool,
+            ctx.accounts.price_oracle.as_ref(),
+            PRICE_SIDE_LIQUIDATION,
+            protocol_state.confidence_multiplier_bps,
+            now,
+        )?;
+        let borrower_account = &mut ctx.accounts.borrower_account;
+
+        borrower_account.solvency_snapshot = SolvencySnapshot {
+            health_factor: priced_health(
+                borrower_account,
+                collateral_pool,
+                price,
+                now,
+                protocol_state.min_collateral_ratio,
+            )?,
+            computed_at: now,
+        };
+        Ok(())
+    }
+
+    /// Reveal the owner's confidential balances, given a proof the signer knows their opening.
+    pub fn reveal_balance(ctx: Context<RevealBalance>, zk_proof: Vec<u8>) -> Result<()> {
+        require!(zk_proof.len() <= MAX_PROOF_LEN, ZKError::ProofTooLarge);
+        verify_circuit_proof(&ctx.accounts.verifying_key_registry, CIRCUIT_COLLATERAL, &zk_proof)?;
+
+        let borrower_account = &ctx.accounts.borrower_account;
+        emit!(BalanceRevealEvent {
+            owner: borrower_account.owner,
+            collateral: extract_value_from_encryption(
+                borrower_account.encrypted_collateral.clone()
+            ),
+            borrowed: borrower_debt(borrower_account),
+        });
+        Ok(())
+    }
+
+    /// Capitalizes a loan's accrued interest into the borrower's stored debt (callable by anyone).
+    pub fn accrue_interest(ctx: Context<AccrueInterest>) -> Result<()> {
+        let clock = Clock::get()?;
+        let now = clock.unix_timestamp;
+
+        let interest_before = ctx.accounts.loan_position.accrued_interest;
+        accrue_loan_interest(
+            &mut ctx.accounts.loan_position,
+            &mut ctx.accounts.borrower_account,
+            &ctx.accounts.protocol_state,
+            now,
+        )?;
+        let interest = ctx
+            .accounts
+            .loan_position
+            .accrued_interest
+            .checked_sub(interest_before)
+            .ok_or(ZKError::MathOverflow)?;
+
+        // Pay the caller from treasury reserves.
+        let treasury = &mut ctx.accounts.protocol_treasury;
+        let incentive = accrual_incentive(interest, treasury.total_fees_collected)?;
+        if incentive > 0 {
+            let cpi_accounts = Transfer {
+                from: ctx.accounts.lending_pool_token_account.to_account_info(),
+                to: ctx.accounts.caller_token_account.to_account_info(),
+                authority: ctx.accounts.lending_pool_authority.to_account_info(),
+            };
+            token::transfer(
+                CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+                incentive,
+            )?;
+            treasury.total_fees_collected = treasury
+                .total_fees_collected
+                .checked_sub(incentive)
+                .ok_or(ZKError::MathOverflow)?;
+        }
+        Ok(())
+    }
+
+    /// Keeper pass that advances a lending pool's interest indexes and capitalizes accrued
+    /// interest into the stored debt of many loans at once, so borrowers' debt stays current
+    /// between borrows and repayments (callable by anyone). Each pool accrues at its own
+    /// utilization and rate.
+    ///
+    /// `remaining_accounts` holds one writable `[borrower_account, loan_position]` pair per loan.
+    /// Each pair is written back before the next is loaded, so a borrower may appear in several.
+    pub fn accrue_global_interest<'info>(
+        ctx: Context<'_, '_, 'info, 'info, AccrueGlobalInterest<'info>>,
+    ) -> Result<()> {
+        require!(
+            ctx.remaining_accounts.len().is_multiple_of(2),
+            ZKError::BatchLengthMismatch
+        );
+        let now = Clock::get()?.unix_timestamp;
+        accrue_pool_indexes(&mut ctx.accounts.lending_pool, &ctx.accounts.protocol_state, now)?;
+        for accounts in ctx.remaining_accounts.chunks(2) {
+            let mut borrower_account = Account::<BorrowerAccount>::try_from(&accounts[0])?;
+            let mut loan_position = Account::<LoanPosition>::try_from(&accounts[1])?;
+            require!(
+                loan_position.borrower_account == accounts[0].key(),
+                ZKError::InvalidLoan
+            );
+            accrue_loan_interest(
+                &mut loan_position,
+                &mut borrower_account,
+                &ctx.accounts.protocol_state,
+                now,
+            )?;
+            loan_position.exit(&crate::ID)?;
+            borrower_account.exit(&crate::ID)?;
+        }
+        Ok(())
+    }
+
+    /// Lock a variable loan at the current curve rate for `duration` seconds, for an upfront fee.
+    pub fn lock_rate(ctx: Context<LockRate>, loan_id: u64, duration: i64) -> Result<()> {
+        require!(
+            duration > 0 && duration <= MAX_RATE_LOCK_DURATION,
+            ZKError::InvalidRateLockDuration
+        );
+        let clock = Clock::get()?;
+        let now = clock.unix_timestamp;
+
+        let loan_position = &mut ctx.accounts.loan_position;
+        require!(loan_position.loan_id == loan_id, ZKError::InvalidLoan);
+        require!(
+            loan_position.rate_mode == RATE_MODE_VARIABLE,
+            ZKError::RateModeMismatch
+        );
+        require!(loan_position.rate_lock_until <= now, ZKError::RateAlreadyLocked);
+
+        // Settle interest at the old rate before the lock takes effect.
+        accrue_loan_interest(
+            loan_position,
+            &mut ctx.accounts.borrower_account,
+            &ctx.accounts.protocol_state,
+            now,
+        )?;
+
+        // Charge the lock fee pro rata to the locked duration (at least one 30-day period).
+        let periods = ((duration + 2_591_999) / 2_592_000) as u64;
+        let fee = mul_div_up(
+            loan_position.principal as u128,
+            RATE_LOCK_FEE_BPS_PER_30_DAYS as u128 * periods as u128,
+            10_000,
+        )? as u64;
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.user_borrow_token_account.to_account_info(),
+            to: ctx.accounts.lending_pool_token_account.to_account_info(),
+            authority: ctx.accounts.borrower.to_account_info(),
+        };
+        token::transfer(
+            CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+            fee,
+        )?;
+        credit_treasury_fee(&mut ctx.accounts.protocol_treasury, fee)?;
+
+        loan_position.locked_rate = calculate_borrow_rate(&ctx.accounts.protocol_state);
+        loan_position.rate_lock_until = now + duration;
+        Ok(())
+    }
+
+    /// Consolidate `loan_b` into `loan_a`: principals are summed, rates blended by principal, and
+    /// the earlier origination kept. Both loans must share a rate mode and have no active lock.
+    pub fn merge_loans(
+        ctx: Context<MergeLoans>,
+        loan_a_id: u64,
+        loan_b_id: u64,
+    ) -> Result<()> {
+        require!(loan_a_id != loan_b_id, ZKError::InvalidLoan);
+        let clock = Clock::get()?;
+        let now = clock.unix_timestamp;
+
+        let target = &ctx.accounts.loan_a;
+        let source = &ctx.accounts.loan_b;
+        require!(target.rate_mode == source.rate_mode, ZKError::RateModeMismatch);
+        require!(target.delegation == source.delegation, ZKError::InvalidLoan);
+        require!(
+            target.rate_lock_until <= now && source.rate_lock_until <= now,
+            ZKError::RateAlreadyLocked
+        );
+
+        // Settle each loan's interest at its own rate before combining.
+        accrue_loan_interest(
+            &mut ctx.accounts.loan_a,
+            &mut ctx.accounts.borrower_account,
+            &ctx.accounts.protocol_state,
+            now,
+        )?;
+        accrue_loan_interest(
+            &mut ctx.accounts.loan_b,
+            &mut ctx.accounts.borrower_account,
+            &ctx.accounts.protocol_state,
+            now,
+        )?;
+
+        let source = &ctx.accounts.loan_b;
+        let target = &mut ctx.accounts.loan_a;
+        let principal = target
+            .principal
+            .checked_add(source.principal)
+            .ok_or(ZKError::MathOverflow)?;
+        if principal > 0 {
+            let weighted = target.principal as u128 * target.interest_rate as u128
+                + source.principal as u128 * source.interest_rate as u128;
+            target.interest_rate = (weighted / principal as u128) as u8;
+        }
+        target.principal = principal;
+        target.borrow_timestamp = target.borrow_timestamp.min(source.borrow_timestamp);
+        target.maturity = target.maturity.max(source.maturity);
+        target.penalty_rate = target.penalty_rate.max(source.penalty_rate);
+        target.delinquent |= source.delinquent;
+        target.residual_interest = target
+            .residual_interest
+            .checked_add(source.residual_interest)
+            .ok_or(ZKError::MathOverflow)?;
+        target.accrued_interest = target
+            .accrued_interest
+            .checked_add(source.accrued_interest)
+            .ok_or(ZKError::MathOverflow)?;
+        target.origination_fee = target
+            .origination_fee
+            .checked_add(source.origination_fee)
+            .ok_or(ZKError::MathOverflow)?;
+
+        let borrower_account = &mut ctx.accounts.borrower_account;
+        borrower_account.active_loans = borrower_account.active_loans.saturating_sub(1);
+        Ok(())
+    }
+
+    /// Split `split_amount` of principal off `loan_id` into a new loan with the same rate, rate
+    /// lock, origination time and maturity. Interest is settled first and stays on the original
+    /// loan; the origination fee is divided pro rata.
+    pub fn split_loan(ctx: Context<SplitLoan>, loan_id: u64, split_amount: u64) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            split_amount > 0 && split_amount < ctx.accounts.loan_position.principal,
+            ZKError::InvalidSplitAmount
+        );
+        let borrower_account = &mut ctx.accounts.borrower_account;
+        require!(
+            borrower_account.active_loans < ctx.accounts.protocol_state.max_loans_per_borrower,
+            ZKError::TooManyLoans
+        );
+
+        let original = &mut ctx.accounts.loan_position;
+        accrue_loan_interest(original, borrower_account, &ctx.accounts.protocol_state, now)?;
+        let split_fee = mul_div_down(
+            original.origination_fee as u128,
+            split_amount as u128,
+            original.principal as u128,
+        )? as u64;
+
+        let split = &mut ctx.accounts.split_position;
+        split.borrower_account = original.borrower_account;
+        split.loan_id = borrower_account.next_loan_id;
+        split.principal = split_amount;
+        split.rate_mode = original.rate_mode;
+        split.interest_rate = original.interest_rate;
+        split.borrow_timestamp = original.borrow_timestamp;
+        split.interest_accrued_at = original.interest_accrued_at;
+        split.locked_rate = original.locked_rate;
+        split.rate_lock_until = original.rate_lock_until;
+        split.residual_interest = 0;
+        split.origination_fee = split_fee;
+        split.accrued_interest = 0;
+        split.delegation = original.delegation;
+        split.maturity = original.maturity;
+        split.penalty_rate = original.penalty_rate;
+        split.delinquent = original.delinquent;
+
+        original.principal = original
+            .principal
+            .checked_sub(split_amount)
+            .ok_or(ZKError::MathOverflow)?;
+        original.origination_fee = original
+            .origination_fee
+            .checked_sub(split_fee)
+            .ok_or(ZKError::MathOverflow)?;
+        borrower_account.next_loan_id = borrower_account
+            .next_loan_id
+            .checked_add(1)
+            .ok_or(ZKError::MathOverflow)?;
+        borrower_account.active_loans = borrower_account
+            .active_loans
+            .checked_add(1)
+            .ok_or(ZKError::MathOverflow)?;
+        emit!(LoanSplitEvent {
+            loan_id,
+            split_loan_id: split.loan_id,
+            principal: original.principal,
+            split_principal: split_amount,
+        });
+        Ok(())
+    }
+
+    /// Refinance a loan onto the current variable curve rate, for a small fee.
+    pub fn refinance(ctx: Context<Refinance>) -> Result<()> {
+        let clock = Clock::get()?;
+        let now = clock.unix_timestamp;
+
+        let loan_position = &mut ctx.accounts.loan_position;
+        let new_rate = calculate_borrow_rate(&ctx.accounts.protocol_state);
+        require!(
+            new_rate < current_loan_rate(loan_position, &ctx.accounts.protocol_state, now),
+            ZKError::RefinanceNotBeneficial
+        );
+
+        // Settle interest at the old rate before switching.
+        accrue_loan_interest(
+            loan_position,
+            &mut ctx.accounts.borrower_account,
+            &ctx.accounts.protocol_state,
+            now,
+        )?;
+
+        let fee = mul_div_up(loan_position.principal as u128, REFINANCE_FEE_BPS as u128, 10_000)?
+            as u64;
+        let cpi_accounts = Transfer {
+            from: ctx.accounts.user_borrow_token_account.to_account_info(),
+            to: ctx.accounts.lending_pool_token_account.to_account_info(),
+            authority: ctx.accounts.borrower.to_account_info(),
+        };
+        token::transfer(
+            CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts),
+            fee,
+        )?;
+        credit_treasury_fee(&mut ctx.accounts.protocol_treasury, fee)?;
+
+        switch_to_variable_rate(loan_position, new_rate);
+        Ok(())
+    }
+
+    /// Roll a matured institutional loan over for another term at the pool's current fixed rate,
+    /// capitalizing its accrued interest into principal instead of repaying and re-borrowing.
+    pub fn rollover_institutional(ctx: Context<RolloverInstitutional>) -> Result<()> {
+        let clock = Clock::get()?;
+        let now = clock.unix_timestamp;
+
+        let borrower_key = ctx.accounts.borrower.key();
+        require!(
+            ctx.accounts
+                .institutional_pool
+                .zk_whitelist
+                .iter()
+                .any(|entry| entry.institution == borrower_key),
+            ZKError::UnauthorizedBorrower
+        );
+
+        let loan_position = &mut ctx.accounts.loan_position;
+        require!(
+            loan_position.rate_mode == RATE_MODE_FIXED,
+            ZKError::RateModeMismatch
+        );
+        require!(now >= loan_position.maturity, ZKError::LoanNotMatured);
+        require!(!loan_position.delinquent, ZKError::LoanDelinquent);
+
+        // Settle interest at the expiring rate, then fold it into principal.
+        let borrower_account = &mut ctx.accounts.borrower_account;
+        accrue_loan_interest(
+            loan_position,
+            borrower_account,
+            &ctx.accounts.protocol_state,
+            now,
+        )?;
+        let capitalized = loan_position.accrued_interest;
+        loan_position.principal = loan_position
+            .principal
+            .checked_add(capitalized)
+            .ok_or(ZKError::MathOverflow)?;
+        loan_position.accrued_interest = 0;
+        borrower_account.accrued_interest = borrower_account
+            .accrued_interest
+            .checked_sub(capitalized)
+            .ok_or(ZKError::MathOverflow)?;
+        borrower_account.encrypted_principal = update_encrypted_value(
+            borrower_account.encrypted_principal.clone(),
+            capitalized,
+            true,
+        );
+
+        loan_position.interest_rate = ctx.accounts.institutional_pool.fixed_interest_rate;
+        loan_position.maturity = now
+            .checked_add(INSTITUTIONAL_LOAN_TERM)
+            .ok_or(ZKError::MathOverflow)?;
+        loan_position.penalty_rate = ctx.accounts.protocol_state.overdue_penalty_rate;
+
+        let protocol_state = &mut ctx.accounts.protocol_state;
+        protocol_state.total_loans = protocol_state
+            .total_loans
+            .checked_add(capitalized)
+            .ok_or(ZKError::MathOverflow)?;
+        update_utilization(protocol_state);
+        Ok(())
+    }
+
+    /// Keeper: record a missed payment on a fixed-term loan still open past maturity plus
+    /// `MISSED_PAYMENT_GRACE_PERIOD`. The loan is flagged delinquent, its rates raised by
+    /// `DELINQUENCY_RATE_PREMIUM`, and the borrower's reputation docked. Delinquent loans can't
+    /// be rolled over.
+    pub fn record_missed_payment(ctx: Context<RecordMissedPayment>) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let loan_position = &mut ctx.accounts.loan_position;
+        require!(loan_position.maturity > 0, ZKError::RateModeMismatch);
+        require!(
+            now >= loan_position.maturity.saturating_add(MISSED_PAYMENT_GRACE_PERIOD),
+            ZKError::LoanNotMatured
+        );
+        require!(!loan_position.delinquent, ZKError::LoanDelinquent);
+
+        // Settle interest at the pre-delinquency rates before raising them.
+        accrue_loan_interest(
+            loan_position,
+            &mut ctx.accounts.borrower_account,
+            &ctx.accounts.protocol_state,
+            now,
+        )?;
+        loan_position.delinquent = true;
+        loan_position.interest_rate = loan_position
+            .interest_rate
+            .saturating_add(DELINQUENCY_RATE_PREMIUM);
+        if loan_position.penalty_rate > 0 {
+            loan_position.penalty_rate = loan_position
+                .penalty_rate
+                .saturating_add(DELINQUENCY_RATE_PREMIUM);
+        }
+
+        let reputation = &mut ctx.accounts.borrower_reputation;
+        reputation.zk_reputation_score = reputation
+            .zk_reputation_score
+            .saturating_sub(MISSED_PAYMENT_REPUTATION_PENALTY);
+
+        emit!(MissedPaymentEvent {
+            loan_position: loan_position.key(),
+            borrower: reputation.borrower,
+            interest_rate: loan_position.interest_rate,
+            reputation_score: reputation.zk_reputation_score,
+        });
+        Ok(())
+    }
+
+    /// Initializes the sliding-window borrow rate limiter.
+    pub fn initialize_borrow_rate_limit(
+        ctx: Context<InitializeBorrowRateLimit>,
+        max_borrow_per_window: u64,
+        window_seconds: i64,
+    ) -> Result<()> {
+        require!(window_seconds > 0, ZKError::InvalidRateLimitWindow);
+
+        let rate_limit = &mut ctx.accounts.borrow_rate_limit;
+        rate_limit.max_borrow_per_window = max_borrow_per_window;
+        rate_limit.window_seconds = window_seconds;
+        rate_limit.window_start = Clock::get()?.unix_timestamp;
+        rate_limit.borrowed_in_window = 0;
+        Ok(())
+    }
+}
+
+// ─────────────────────────────────────────────────────────────
+// Dummy & Helper Functions (Replace with actual ZK and confidential logic)
+// ─────────────────────────────────────────────────────────────
+
+fn verify_zk_proof(_verifying_key: &[u8; 32], _zk_proof: &Vec<u8>) -> bool {
+    true
+}
+
+/// Verifies `zk_proof` against the key registered for `circuit_id`, rejecting proofs for
+/// circuits without a registered key.
+fn verify_circuit_proof(
+    registry: &VerifyingKeyRegistry,
+    circuit_id: u8,
+    zk_proof: &Vec<u8>,
+) -> Result<()> {
+    let verifying_key = registry
+        .keys
+        .get(circuit_id as usize)
+        .filter(|key| **key != [0u8; 32])
+        .ok_or(ZKError::CircuitNotRegistered)?;
+    require!(verify_zk_proof(verifying_key, zk_proof), ZKError::InvalidProof);
+    Ok(())
+}
+
+fn update_encrypted_value(
+    current: EncryptedAmount,
+    amount: u64,
+    add: bool,
+) -> EncryptedAmount {
+    if add {
+        EncryptedAmount {
+            value: current.value.checked_add(amount).unwrap_or(current.value),
+        }
+    } else {
+        EncryptedAmount {
+            value: current.value.saturating_sub(amount),
+        }
+    }
+}
+
+/// Checks `collateral / amount >= min_collateral_ratio`, with the ratio in basis points (15000 = 150%).
+fn meets_collateral_ratio(
+    encrypted_collateral: EncryptedAmount,
+    amount: u64,
+    min_collateral_ratio: u16,
+) -> bool {
+    encrypted_collateral.value as u128 * 10_000 >= amount as u128 * min_collateral_ratio as u128
+}
+
+/// Fails with `InsufficientCollateral` unless the collateral covers the borrower's total `debt`,
+/// including the new borrow, at the minimum ratio, first emitting a diagnostic with the
+/// collateral required. The collateral held is confidential and left out.
+fn require_collateral_ratio(
+    encrypted_collateral: &EncryptedAmount,
+    debt: u64,
+    min_collateral_ratio: u16,
+) -> Result<()> {
+    if !meets_collateral_ratio(encrypted_collateral.clone(), debt, min_collateral_ratio) {
+        let required = debt as u128 * min_collateral_ratio as u128 / 10_000;
+        emit!(ErrorDiagnosticEvent {
+            error_code: ZKError::InsufficientCollateral.into(),
+            required: required.min(u64::MAX as u128) as u64,
+            available: None,
+        });
+        return err!(ZKError::InsufficientCollateral);
+    }
+    Ok(())
+}
+
+/// Fails with `InsufficientLiquidity` when `amount` exceeds the protocol's available liquidity,
+/// or with `BufferReserved` when it would dip into the lending pool's `min_liquidity_buffer`,
+/// first emitting a diagnostic with both amounts.
+fn require_liquidity(
+    protocol_state: &ProtocolState,
+    lending_pool: &LendingPool,
+    amount: u64,
+) -> Result<()> {
+    if amount > protocol_state.total_liquidity {
+        emit!(ErrorDiagnosticEvent {
+            error_code: ZKError::InsufficientLiquidity.into(),
+            required: amount,
+            available: Some(protocol_state.total_liquidity),
+        });
+        return err!(ZKError::InsufficientLiquidity);
+    }
+    let borrowable = protocol_state
+        .total_liquidity
+        .saturating_sub(lending_pool.min_liquidity_buffer);
+    if amount > borrowable {
+        emit!(ErrorDiagnosticEvent {
+            error_code: ZKError::BufferReserved.into(),
+            required: amount,
+            available: Some(borrowable),
+        });
+        return err!(ZKError::BufferReserved);
+    }
+    Ok(())
+}
+
+/// A position with outstanding debt is liquidatable once its collateral, valued at the pool's
+/// yield index and `price`, falls below the minimum collateral ratio.
+fn is_liquidatable(
+    borrower_account: &BorrowerAccount,
+    collateral_pool: &CollateralPool,
+    price: u64,
+    min_collateral_ratio: u16,
+) -> Result<bool> {
+    let collateral = EncryptedAmount {
+        value: collateral_value(
+            borrower_account.encrypted_collateral.value,
+            collateral_pool,
+            price,
+        )?,
+    };
+    Ok(borrower_debt(borrower_account) > 0
+        && !meets_collateral_ratio(
+            collateral,
+            borrower_debt(borrower_account),
+            min_collateral_ratio,
+        ))
+}
+
+/// Collateral price, scaled by `ORACLE_PRICE_ONE`, below which the position becomes liquidatable:
+/// the price at which its collateral value exactly covers its debt at `min_collateral_ratio`,
+/// rounded up. `None` without debt; `u64::MAX` if there is debt but no collateral.
+fn position_liquidation_price(
+    borrower_account: &BorrowerAccount,
+    collateral_pool: &CollateralPool,
+    min_collateral_ratio: u16,
+) -> Result<Option<u64>> {
+    let debt = borrower_debt(borrower_account);
+    if debt == 0 {
+        return Ok(None);
+    }
+    let required = collateral_for_health(debt, HEALTH_FACTOR_ONE, min_collateral_ratio)?;
+    let mut numerator = required as u128 * ORACLE_PRICE_ONE as u128;
+    let mut denominator = borrower_account.encrypted_collateral.value as u128;
+    if collateral_pool.base_index != 0 {
+        numerator = numerator
+            .checked_mul(collateral_pool.base_index as u128)
+            .ok_or(ZKError::MathOverflow)?;
+        denominator = denominator
+            .checked_mul(collateral_pool.current_index as u128)
+            .ok_or(ZKError::MathOverflow)?;
+    }
+    if denominator == 0 {
+        return Ok(Some(u64::MAX));
+    }
+    let price = numerator.div_ceil(denominator);
+    Ok(Some(price.min(u64::MAX as u128) as u64))
+}
+
+/// Moves just enough of a liquidatable borrower's protection buffer into collateral to restore
+/// the minimum collateral ratio, or the whole buffer if it falls short. Returns the amount drawn.
+fn draw_protection_buffer(
+    borrower_account: &mut BorrowerAccount,
+    collateral_pool: &CollateralPool,
+    price: u64,
+    min_collateral_ratio: u16,
+) -> Result<u64> {
+    if borrower_account.protection_buffer == 0
+        || !is_liquidatable(borrower_account, collateral_pool, price, min_collateral_ratio)?
+    {
+        return Ok(0);
+    }
+    let required = collateral_for_health(
+        borrower_debt(borrower_account),
+        HEALTH_FACTOR_ONE,
+        min_collateral_ratio,
+    )?;
+    let current = collateral_value(
+        borrower_account.encrypted_collateral.value,
+        collateral_pool,
+        price,
+    )?;
+    let shortfall =
+        collateral_for_value(required.saturating_sub(current), collateral_pool, price)?;
+    let drawn = shortfall.min(borrower_account.protection_buffer);
+
+    borrower_account.protection_buffer = borrower_account
+        .protection_buffer
+        .checked_sub(drawn)
+        .ok_or(ZKError::MathOverflow)?;
+    borrower_account.encrypted_collateral = update_encrypted_value(
+        borrower_account.encrypted_collateral.clone(),
+        drawn,
+        true,
+    );
+    Ok(drawn)
+}
+
+/// Liquidation bonus for a collateral pool, in basis points, falling back to the protocol default.
+fn liquidation_bonus_bps(collateral_pool: &CollateralPool) -> u64 {
+    match collateral_pool.liquidation_bonus_bps {
+        0 => LIQUIDATION_BONUS_BPS,
+        bps => bps as u64,
+    }
+}
+
+/// Values `amount` of pool collateral as `amount * price * current_index / base_index`, with
+/// `price` scaled by `ORACLE_PRICE_ONE`. Pools without a yield index skip the index term.
+fn collateral_value(amount: u64, collateral_pool: &CollateralPool, price: u64) -> Result<u64> {
+    let mut value = (amount as u128)
+        .checked_mul(price as u128)
+        .map(|v| v / ORACLE_PRICE_ONE as u128)
+        .ok_or(ZKError::MathOverflow)?;
+    if collateral_pool.base_index != 0 {
+        value = value
+            .checked_mul(collateral_pool.current_index as u128)
+            .map(|v| v / collateral_pool.base_index as u128)
+            .ok_or(ZKError::MathOverflow)?;
+    }
+    u64::try_from(value).map_err(|_| error!(ZKError::MathOverflow))
+}
+
+/// Collateral factor of a pool at `now`: its listed `collateral_factor`, less
+/// `factor_decay_bps_per_day` for each full day since `factor_decay_start`, floored at
+/// `min_collateral_factor`. Pools without a decay schedule keep their listed factor.
+fn effective_collateral_factor(collateral_pool: &CollateralPool, now: i64) -> u16 {
+    if collateral_pool.factor_decay_bps_per_day == 0 || now < collateral_pool.factor_decay_start {
+        return collateral_pool.collateral_factor;
+    }
+    let days = ((now - collateral_pool.factor_decay_start) / 86_400) as u64;
+    let decay = days.saturating_mul(collateral_pool.factor_decay_bps_per_day as u64);
+    (collateral_pool.collateral_factor as u64)
+        .saturating_sub(decay)
+        .max(collateral_pool.min_collateral_factor as u64) as u16
+}
+
+/// Value of `amount` of pool collateral for borrowing: `collateral_value` scaled down by how far
+/// the pool's collateral factor has decayed below its listed value.
+fn borrowing_power(
+    amount: u64,
+    collateral_pool: &CollateralPool,
+    price: u64,
+    now: i64,
+) -> Result<u64> {
+    let value = collateral_value(amount, collateral_pool, price)?;
+    if collateral_pool.collateral_factor == 0 {
+        return Ok(value);
+    }
+    let power = mul_div_down(
+        value as u128,
+        effective_collateral_factor(collateral_pool, now) as u128,
+        collateral_pool.collateral_factor as u128,
+    )?;
+    Ok(power as u64)
+}
+
+/// Pool collateral worth at least `value` at `price`, rounded up; the inverse of `collateral_value`.
+fn collateral_for_value(value: u64, collateral_pool: &CollateralPool, price: u64) -> Result<u64> {
+    let mut numerator = value as u128 * ORACLE_PRICE_ONE as u128;
+    let mut denominator = price.max(1) as u128;
+    if collateral_pool.base_index != 0 {
+        numerator = numerator
+            .checked_mul(collateral_pool.base_index as u128)
+            .ok_or(ZKError::MathOverflow)?;
+        denominator *= collateral_pool.current_index.max(1) as u128;
+    }
+    let amount = numerator.div_ceil(denominator);
+    u64::try_from(amount).map_err(|_| error!(ZKError::MathOverflow))
+}
+
+/// Pool collateral with at least `power` of borrowing power at `price` and `now`, rounded up;
+/// the inverse of `borrowing_power`.
+fn collateral_for_power(
+    power: u64,
+    collateral_pool: &CollateralPool,
+    price: u64,
+    now: i64,
+) -> Result<u64> {
+    let value = if collateral_pool.collateral_factor == 0 {
+        power as u128
+    } else {
+        mul_div_up(
+            power as u128,
+            collateral_pool.collateral_factor as u128,
+            effective_collateral_factor(collateral_pool, now).max(1) as u128,
+        )?
+    };
+    let value = u64::try_from(value).map_err(|_| error!(ZKError::MathOverflow))?;
+    collateral_for_value(value, collateral_pool, price)
+}
+
+/// Conservative price of a pool's collateral, scaled by `ORACLE_PRICE_ONE`, erring toward safety
+/// by `k = confidence_multiplier_bps` confidence intervals. Borrows value collateral at
+/// `price - k * conf`. Liquidations value debt at `price + k * conf`, applied here as the
+/// equivalent collateral price `price^2 / (price + k * conf)`. Pools without an oracle are
+/// priced at par.
+fn collateral_price(
+    collateral_pool: &CollateralPool,
+    price_oracle: Option<&AccountInfo>,
+    side: u8,
+    confidence_multiplier_bps: u16,
+    now: i64,
+) -> Result<u64> {
+    if collateral_pool.oracle_kind == ORACLE_KIND_NONE {
+        return Ok(ORACLE_PRICE_ONE);
+    }
+    let oracle = price_oracle
+        .filter(|oracle| oracle.key() == collateral_pool.oracle)
+        .ok_or(ZKError::InvalidOracle)?;
+    let (price, conf, publish_time) = read_price(collateral_pool.oracle_kind, oracle)?;
+    require!(
+        now.saturating_sub(publish_time) <= MAX_ORACLE_STALENESS,
+        ZKError::StaleOracle
+    );
+    // Round the margin up: it always moves the price against the borrower.
+    let margin = mul_div_up(conf as u128, confidence_multiplier_bps as u128, 10_000)?
+        .min(u64::MAX as u128) as u64;
+    if side == PRICE_SIDE_BORROW {
+        return Ok(price.saturating_sub(margin));
+    }
+    let debt_price = price.saturating_add(margin).max(1) as u128;
+    Ok((price as u128 * price as u128 / debt_price).min(u64::MAX as u128) as u64)
+}
+
+/// Reads `(price, confidence, publish_time)` from a Pyth v2 price account or a Switchboard v2
+/// aggregator, with price and confidence normalized to `ORACLE_PRICE_ONE`.
+fn read_price(oracle_kind: u8, oracle: &AccountInfo) -> Result<(u64, u64, i64)> {
+    const PYTH_MAGIC: u32 = 0xa1b2_c3d4;
+    const SWITCHBOARD_AGGREGATOR_DISCRIMINATOR: [u8; 8] = [217, 230, 65, 101, 201, 162, 27, 125];
+
+    let data = oracle.try_borrow_data()?;
+    let read = |offset: usize, len: usize| data.get(offset..offset + len).ok_or(ZKError::InvalidOracle);
+    match oracle_kind {
+        ORACLE_KIND_PYTH => {
+            require!(
+                u32::from_le_bytes(read(0, 4)?.try_into().unwrap()) == PYTH_MAGIC,
+                ZKError::InvalidOracle
+            );
+            let expo = i32::from_le_bytes(read(20, 4)?.try_into().unwrap());
+            let publish_time = i64::from_le_bytes(read(96, 8)?.try_into().unwrap());
+            let price = i64::from_le_bytes(read(208, 8)?.try_into().unwrap());
+            let conf = u64::from_le_bytes(read(216, 8)?.try_into().unwrap());
+            require!(price > 0, ZKError::InvalidOracle);
+            Ok((
+                normalize_price(price as u128, expo)?,
+                normalize_price(conf as u128, expo)?,
+                publish_time,
+            ))
+        }
+        ORACLE_KIND_SWITCHBOARD => {
+            require!(
+                read(0, 8)? == SWITCHBOARD_AGGREGATOR_DISCRIMINATOR,
+                ZKError::InvalidOracle
+            );
+            // `latest_confirmed_round` of the packed aggregator layout.
+            let publish_time = i64::from_le_bytes(read(358, 8)?.try_into().unwrap());
+            let result = i128::from_le_bytes(read(366, 16)?.try_into().unwrap());
+            let result_scale = u32::from_le_bytes(read(382, 4)?.try_into().unwrap());
+            let std_dev = i128::from_le_bytes(read(386, 16)?.try_into().unwrap());
+            let std_dev_scale = u32::from_le_bytes(read(402, 4)?.try_into().unwrap());
+            require!(result > 0 && std_dev >= 0, ZKError::InvalidOracle);
+            Ok((
+                normalize_price(result as u128, -(result_scale as i32))?,
+                normalize_price(std_dev as u128, -(std_dev_scale as i32))?,
+                publish_time,
+            ))
+        }
+        _ => err!(ZKError::InvalidOracle),
+    }
+}
+
+/// Rescales `value * 10^expo` to a price scaled by `ORACLE_PRICE_ONE`.
+fn normalize_price(value: u128, expo: i32) -> Result<u64> {
+    let shift = expo + ORACLE_PRICE_ONE.ilog10() as i32;
+    let factor = 10u128
+        .checked_pow(shift.unsigned_abs())
+        .ok_or(ZKError::InvalidOracle)?;
+    let normalized = if shift >= 0 {
+        value.checked_mul(factor).ok_or(ZKError::MathOverflow)?
+    } else {
+        value / factor
+    };
+    u64::try_from(normalized).map_err(|_| error!(ZKError::MathOverflow))
+}
+
+/// Borrow fee at the rate of the highest tier whose threshold `amount` reaches, discounted
+/// while the borrower's collateral is under a stake lock-up. Zero while the fee switch is off.
+fn calculate_borrow_fee(
+    amount: u64,
+    protocol_state: &ProtocolState,
+    borrower_account: &BorrowerAccount,
+    now: i64,
+) -> Result<u64> {
+    if !protocol_state.fees_enabled {
+        return Ok(0);
+    }
+    let tiers = &protocol_state.fee_tiers[..protocol_state.fee_tier_count as usize];
+    let mut bps = tiers
+        .iter()
+        .rev()
+        .find(|tier| amount >= tier.threshold)
+        .map_or(0, |tier| tier.bps) as u64;
+    if borrower_account.stake_lock_until > now {
+        // Round the discounted rate up so the discount never exceeds its nominal share.
+        bps = mul_div_up(bps as u128, (10_000 - STAKE_LOCK_FEE_DISCOUNT_BPS) as u128, 10_000)?
+            as u64;
+    }
+    let fee = mul_div_up(amount as u128, bps as u128, 10_000)?;
+    Ok(fee as u64)
+}
+
+/// Liquidation grace period for a debt, from the highest tier whose pool-share threshold it
+/// reaches. The pool share is the debt relative to the pool's loans plus liquidity.
+fn liquidation_grace_slots(debt: u64, protocol_state: &ProtocolState) -> u64 {
+    let pool_size = protocol_state.total_loans as u128 + protocol_state.total_liquidity as u128;
+    let share_bps = (debt as u128 * 10_000)
+        .checked_div(pool_size)
+        .map_or(10_000, |share| share.min(10_000) as u16);
+    let tiers = &protocol_state.grace_tiers[..protocol_state.grace_tier_count as usize];
+    tiers
+        .iter()
+        .rev()
+        .find(|tier| share_bps >= tier.min_pool_share_bps)
+        .map_or(LIQUIDATION_DELAY_SLOTS, |tier| tier.grace_slots)
+}
+
+/// Seconds a borrower must wait between borrows, from the highest lock tier their reputation
+/// score reaches, never longer than `min_collateral_lock_time`. Without a reputation account
+/// the score is zero.
+fn collateral_lock_time(
+    protocol_state: &ProtocolState,
+    reputation: Option<&BorrowerReputation>,
+) -> i64 {
+    let score = reputation.map_or(0, |reputation| reputation.zk_reputation_score);
+    let tiers = &protocol_state.lock_tiers[..protocol_state.lock_tier_count as usize];
+    tiers
+        .iter()
+        .rev()
+        .find(|tier| score >= tier.min_reputation_score)
+        .map_or(protocol_state.min_collateral_lock_time, |tier| {
+            tier.lock_time.min(protocol_state.min_collateral_lock_time)
+        })
+}
+
+/// Rejects a borrow while the borrower's most recent stake is still settling.
+fn require_stake_settled(
+    protocol_state: &ProtocolState,
+    borrower_account: &BorrowerAccount,
+    now: i64,
+) -> Result<()> {
+    require!(
+        now.saturating_sub(borrower_account.last_stake_at) >= protocol_state.stake_settle_time,
+        ZKError::StakeNotSettled
+    );
+    Ok(())
+}
+
+/// Flash loan protection: rejects a borrow made within `lock_time` seconds of the borrower's
+/// last one.
+fn require_collateral_lock(
+    borrower_account: &Account<BorrowerAccount>,
+    lock_time: i64,
+    now: i64,
+) -> Result<()> {
+    require!(
+        now - borrower_account.borrow_timestamp >= lock_time,
+        ZKError::CollateralLockTimeNotMet
+    );
+    Ok(())
+}
+
+/// Flash loan protection for a live borrow: returns whether a repeat borrow made within
+/// `lock_time` seconds of the borrower's last one must be blocked. Blocked attempts are
+/// counted in `flash_attempts` and emitted as a `FlashProtectionEvent`. Callers then return
+/// `Ok` without borrowing, so the record commits instead of rolling back with an error.
+fn record_flash_attempt(
+    borrower_account: &mut Account<BorrowerAccount>,
+    lock_time: i64,
+    now: i64,
+) -> Result<bool> {
+    if borrower_account.borrow_timestamp == 0
+        || now - borrower_account.borrow_timestamp >= lock_time
+    {
+        return Ok(false);
+    }
+    borrower_account.flash_attempts = borrower_account
+        .flash_attempts
+        .checked_add(1)
+        .ok_or(ZKError::MathOverflow)?;
+    emit!(FlashProtectionEvent {
+        borrower_account: borrower_account.key(),
+        last_borrow_at: borrower_account.borrow_timestamp,
+        attempted_at: now,
+        lock_time,
+        flash_attempts: borrower_account.flash_attempts,
+    });
+    Ok(true)
+}
+
+/// Moves a proposer's bond, at least `min_proposal_bond` lamports, into the new proposal account.
+fn post_proposal_bond<'info>(
+    proposer: &Signer<'info>,
+    governance: &Account<'info, Governance>,
+    protocol_state: &ProtocolState,
+    system_program: &Program<'info, System>,
+    bond: u64,
+) -> Result<()> {
+    require!(
+        bond >= protocol_state.min_proposal_bond,
+        ZKError::ProposalBondTooSmall
+    );
+    if bond > 0 {
+        anchor_lang::system_program::transfer(
+            CpiContext::new(
+                system_program.to_account_info(),
+                anchor_lang::system_program::Transfer {
+                    from: proposer.to_account_info(),
+                    to: governance.to_account_info(),
+                },
+            ),
+            bond,
+        )?;
+    }
+    Ok(())
+}
+
+/// Decodes a legacy `ProtocolState`, widening its `u8` utilization rate in place, and fills
+/// the fields added since with the defaults `initialize` sets.
+fn migrate_legacy_protocol_state(legacy: &[u8]) -> Result<ProtocolState> {
+    require!(
+        legacy.len() == LEGACY_PROTOCOL_STATE_LEN && legacy[..8] == ProtocolState::DISCRIMINATOR,
+        ZKError::InvalidAccountLayout
+    );
+    // Shift everything after the old byte up by one; the little-endian high byte is zero, and
+    // the fields past the legacy layout start zeroed.
+    let offset = PROTOCOL_STATE_UTILIZATION_OFFSET;
+    let mut data = vec![0u8; PROTOCOL_STATE_LEN];
+    data[..offset + 1].copy_from_slice(&legacy[..offset + 1]);
+    data[offset + 2..LEGACY_PROTOCOL_STATE_LEN + 1].copy_from_slice(&legacy[offset + 1..]);
+    let mut state = ProtocolState::try_deserialize(&mut &data[..])?;
+
+    state.grace_tiers[0] = GraceTier {
+        min_pool_share_bps: 0,
+        grace_slots: LIQUIDATION_DELAY_SLOTS,
+    };
+    state.grace_tier_count = 1;
+    state.rebalance_min_health = 11_000;
+    state.max_loans_per_borrower = 16;
+    state.confidence_multiplier_bps = DEFAULT_CONFIDENCE_MULTIPLIER_BPS;
+    state.fees_enabled = true;
+    state.repay_order = REPAY_ORDER_HIGHEST_RATE;
+    Ok(state)
+}
+
+/// Whether a proposal's net votes are positive and reach the quorum; a proposal must pass this
+/// to be executed.
+fn proposal_passed(governance: &Governance) -> bool {
+    governance.votes > 0 && governance.votes as u64 >= PROPOSAL_QUORUM
+}
+
+/// A proposal's lifecycle state as a `PROPOSAL_STATE_*` value.
+fn proposal_state(governance: &Governance, now: i64) -> u8 {
+    if governance.executed {
+        PROPOSAL_STATE_EXECUTED
+    } else if proposal_passed(governance) {
+        PROPOSAL_STATE_PASSED
+    } else if now <= governance.voting_deadline {
+        PROPOSAL_STATE_ACTIVE
+    } else {
+        PROPOSAL_STATE_EXPIRED
+    }
+}
+
+/// Credits a fee to the treasury reserve, overflowing anything above `reserve_cap` into
+/// the governance fund.
+fn credit_treasury_fee(treasury: &mut ProtocolTreasury, fee: u64) -> Result<()> {
+    let room = treasury.reserve_cap.saturating_sub(treasury.total_fees_collected);
+    let to_reserve = fee.min(room);
+    treasury.total_fees_collected = treasury
+        .total_fees_collected
+        .checked_add(to_reserve)
+        .ok_or(ZKError::MathOverflow)?;
+    treasury.governance_fund = treasury
+        .governance_fund
+        .checked_add(fee - to_reserve)
+        .ok_or(ZKError::MathOverflow)?;
+    Ok(())
+}
+
+/// The PDA that owns a collateral pool's token escrow.
+fn collateral_pool_authority(collateral_pool: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"collateral_pool_authority", collateral_pool.as_ref()],
+        &crate::ID,
+    )
+    .0
+}
+
+/// Total debt owed by a borrower: principal plus capitalized interest.
+fn borrower_debt(borrower_account: &BorrowerAccount) -> u64 {
+    borrower_account
+        .encrypted_principal
+        .value
+        .saturating_add(borrower_account.accrued_interest)
+}
+
+/// Health factor in basis points, where `HEALTH_FACTOR_ONE` is the minimum collateral ratio.
+/// Positions without debt are infinitely healthy.
+fn health_factor(collateral: u64, debt: u64, min_collateral_ratio: u16) -> u64 {
+    if debt == 0 {
+        return u64::MAX;
+    }
+    let health = collateral as u128 * 10_000 * HEALTH_FACTOR_ONE as u128
+        / (debt as u128 * min_collateral_ratio as u128);
+    health.min(u64::MAX as u128) as u64
+}
+
+/// Whether `power` of remaining borrowing power keeps `debt` at or above the rebalance buffer,
+/// `rebalance_min_health`.
+fn rebalance_keeps_buffer(power: u64, debt: u64, protocol_state: &ProtocolState) -> bool {
+    health_factor(power, debt, protocol_state.min_collateral_ratio)
+        >= protocol_state.rebalance_min_health
+}
+
+/// Health factor of a position with its collateral valued at `price` and scaled by the pool's
+/// effective collateral factor, as `borrowing_power` values it.
+fn priced_health(
+    borrower_account: &BorrowerAccount,
+    collateral_pool: &CollateralPool,
+    price: u64,
+    now: i64,
+    min_collateral_ratio: u16,
+) -> Result<u64> {
+    let power = borrowing_power(
+        borrower_account.encrypted_collateral.value,
+        collateral_pool,
+        price,
+        now,
+    )?;
+    Ok(health_factor(
+        power,
+        borrower_debt(borrower_account),
+        min_collateral_ratio,
+    ))
+}
+
+/// Collateral needed for `debt` to reach `target_health`, rounded up.
+fn collateral_for_health(debt: u64, target_health: u64, min_collateral_ratio: u16) -> Result<u64> {
+    let numerator = (debt as u128)
+        .checked_mul(min_collateral_ratio as u128)
+        .and_then(|v| v.checked_mul(target_health as u128))
+        .ok_or(ZKError::MathOverflow)?;
+    let denominator = 10_000u128 * HEALTH_FACTOR_ONE as u128;
+    let required = numerator.div_ceil(denominator);
+    u64::try_from(required).map_err(|_| error!(ZKError::MathOverflow))
+}
+
+/// `a * b / denominator`, rounded up. Amounts owed to the protocol (fees, interest) use this so
+/// truncation never works in the payer's favor.
+fn mul_div_up(a: u128, b: u128, denominator: u128) -> Result<u128> {
+    let product = a.checked_mul(b).ok_or(ZKError::MathOverflow)?;
+    Ok(product / denominator + u128::from(product % denominator != 0))
+}
+
+/// `a * b / denominator`, rounded down. Amounts paid out by the protocol (rewards, rebates,
+/// claims) use this.
+fn mul_div_down(a: u128, b: u128, denominator: u128) -> Result<u128> {
+    let product = a.checked_mul(b).ok_or(ZKError::MathOverflow)?;
+    Ok(product / denominator)
+}
+
+/// Simplified interest calculation, scaled by `RAY` so fractional interest isn't truncated:
+/// interest_due = principal * base_interest_rate * time_elapsed * RAY / (seconds in a year * 100)
+fn calculate_interest_due(
+    principal: u64,
+    base_interest_rate: u8,
+    time_elapsed: i64,
+) -> Result<u128> {
+    const DENOMINATOR: u128 = 31_536_000 * 100;
+    let numerator = (principal as u128)
+        .checked_mul(base_interest_rate as u128)
+        .and_then(|v| v.checked_mul(time_elapsed.max(0) as u128))
+        .ok_or(ZKError::MathOverflow)?;
+    // Split the division so the RAY scaling can't overflow.
+    let fraction = mul_div_up(numerator % DENOMINATOR, RAY, DENOMINATOR)?;
+    let interest_due = (numerator / DENOMINATOR)
+        .checked_mul(RAY)
+        .and_then(|v| v.checked_add(fraction))
+        .ok_or(ZKError::MathOverflow)?;
+    Ok(interest_due)
+}
+
+/// Rate a loan is currently accruing at: its active rate lock, its fixed rate, or the curve rate.
+fn current_loan_rate(
+    loan_position: &LoanPosition,
+    protocol_state: &ProtocolState,
+    now: i64,
+) -> u8 {
+    if loan_position.rate_lock_until > now {
+        loan_position.locked_rate
+    } else if loan_position.rate_mode == RATE_MODE_FIXED {
+        loan_position.interest_rate
+    } else {
+        calculate_borrow_rate(protocol_state)
+    }
+}
+
+/// Whole token units of interest owed on a loan since its last accrual, rounded up.
+fn loan_interest_due(
+    loan_position: &LoanPosition,
+    protocol_state: &ProtocolState,
+    now: i64,
+) -> Result<u64> {
+    let interest_due_ray = loan_interest_due_ray(loan_position, protocol_state, now)?;
+    let interest_due = mul_div_up(interest_due_ray, 1, RAY)?;
+    u64::try_from(interest_due).map_err(|_| error!(ZKError::MathOverflow))
+}
+
+/// `RAY`-scaled interest owed on a loan since its last accrual, including its carried residual.
+///
+/// Nothing accrues during the protocol's interest-free period after origination. Time covered
+/// by an active rate lock accrues at `locked_rate`; the rest accrues at the loan's fixed rate
+/// or, for variable loans, the current curve rate.
+fn loan_interest_due_ray(
+    loan_position: &LoanPosition,
+    protocol_state: &ProtocolState,
+    now: i64,
+) -> Result<u128> {
+    let interest_free_until = loan_position
+        .borrow_timestamp
+        .saturating_add(protocol_state.interest_free_seconds);
+    let start = loan_position.interest_accrued_at.max(interest_free_until);
+    if now <= start {
+        return Ok(loan_position.residual_interest);
+    }
+    let unlocked_rate = if loan_position.rate_mode == RATE_MODE_FIXED {
+        loan_position.interest_rate
+    } else {
+        calculate_borrow_rate(protocol_state)
+    };
+    // Only the time past maturity accrues at the penalty rate.
+    let penalty_from = if loan_position.maturity > 0 && loan_position.penalty_rate > 0 {
+        loan_position.maturity.clamp(start, now)
+    } else {
+        now
+    };
+    let lock_end = loan_position.rate_lock_until.clamp(start, penalty_from);
+    // Capitalized interest keeps compounding alongside principal.
+    let balance = loan_position
+        .principal
+        .checked_add(loan_position.accrued_interest)
+        .ok_or(ZKError::MathOverflow)?;
+
+    let locked_interest =
+        calculate_interest_due(balance, loan_position.locked_rate, lock_end - start)?;
+    let unlocked_interest =
+        calculate_interest_due(balance, unlocked_rate, penalty_from - lock_end)?;
+    let penalty_interest =
+        calculate_interest_due(balance, loan_position.penalty_rate, now - penalty_from)?;
+    let interest_due = locked_interest
+        .checked_add(unlocked_interest)
+        .and_then(|v| v.checked_add(penalty_interest))
+        .and_then(|v| v.checked_add(loan_position.residual_interest))
+        .ok_or(ZKError::MathOverflow)?;
+    Ok(interest_due)
+}
+
+/// Initializes a freshly created loan position and advances the borrower's loan counter.
+#[allow(clippy::too_many_arguments)]
+fn open_loan_position(
+    loan_position: &mut LoanPosition,
+    borrower_account: &mut BorrowerAccount,
+    borrower_account_key: Pubkey,
+    principal: u64,
+    origination_fee: u64,
+    rate_mode: u8,
+    interest_rate: u8,
+    now: i64,
+    max_loans_per_borrower: u32,
+) -> Result<()> {
+    require!(
+        borrower_account.active_loans < max_loans_per_borrower,
+        ZKError::TooManyLoans
+    );
+    loan_position.borrower_account = borrower_account_key;
+    loan_position.loan_id = borrower_account.next_loan_id;
+    loan_position.principal = principal;
+    loan_position.rate_mode = rate_mode;
+    loan_position.interest_rate = interest_rate;
+    loan_position.borrow_timestamp = now;
+    loan_position.interest_accrued_at = now;
+    loan_position.residual_interest = 0;
+    loan_position.accrued_interest = 0;
+    loan_position.origination_fee = origination_fee;
+
+    borrower_account.next_loan_id = borrower_account
+        .next_loan_id
+        .checked_add(1)
+        .ok_or(ZKError::MathOverflow)?;
+    borrower_account.active_loans = borrower_account
+        .active_loans
+        .checked_add(1)
+        .ok_or(ZKError::MathOverflow)?;
+    Ok(())
+}
+
+/// Capitalizes interest accrued on a loan since its last accrual into the borrower's stored debt,
+/// kept apart from principal so `total_loans` only ever reflects principal lent out.
+fn accrue_loan_interest(
+    loan_position: &mut LoanPosition,
+    borrower_account: &mut BorrowerAccount,
+    protocol_state: &ProtocolState,
+    now: i64,
+) -> Result<()> {
+    log_compute(protocol_state, "accrue_loan_interest: start");
+    let interest_ray = loan_interest_due_ray(loan_position, protocol_state, now)?;
+    let interest =
+        u64::try_from(interest_ray / RAY).map_err(|_| error!(ZKError::MathOverflow))?;
+    // Carry the fractional remainder so short accrual intervals aren't lost to truncation.
+    loan_position.residual_interest = interest_ray % RAY;
+    loan_position.interest_accrued_at = now.max(loan_position.interest_accrued_at);
+    if interest > 0 {
+        loan_position.accrued_interest = loan_position
+            .accrued_interest
+            .checked_add(interest)
+            .ok_or(ZKError::MathOverflow)?;
+        borrower_account.accrued_interest = borrower_account
+            .accrued_interest
+            .checked_add(interest)
+            .ok_or(ZKError::MathOverflow)?;
+    }
+    log_compute(protocol_state, "accrue_loan_interest: end");
+    Ok(())
+}
+
+/// Keeper incentive for an accrual that booked `interest`: a fraction of that interest, so dust
+/// loans can't be farmed for it, capped per call and by the treasury's fee reserves.
+fn accrual_incentive(interest: u64, treasury_reserves: u64) -> Result<u64> {
+    let incentive = mul_div_down(interest as u128, ACCRUAL_INCENTIVE_BPS as u128, 10_000)? as u64;
+    Ok(incentive.min(MAX_ACCRUAL_INCENTIVE).min(treasury_reserves))
+}
+
+/// Rebate of a loan's origination fee for repaying within the early-repayment window, capped
+/// at the treasury's fee reserves. Zero outside the window or while the rebate is disabled.
+fn early_repay_rebate(
+    loan_position: &LoanPosition,
+    protocol_state: &ProtocolState,
+    treasury: &ProtocolTreasury,
+    now: i64,
+) -> u64 {
+    let window = protocol_state.early_repay_window;
+    if window == 0 || now.saturating_sub(loan_position.borrow_timestamp) > window {
+        return 0;
+    }
+    let rebate = loan_position.origination_fee as u128
+        * protocol_state.early_repay_rebate_bps as u128
+        / 10_000;
+    (rebate as u64).min(treasury.total_fees_collected)
+}
+
+/// Picks the loans a `repay_all` of `amount` pays off. Each `(loan, rate, total_due)` is taken
+/// in full, in `repay_order`, until `amount` no longer covers the next one. Returns the loans
+/// paid with what each owed, and the total used.
+fn plan_repay_all<L: std::ops::Deref<Target = LoanPosition>>(
+    mut loans: Vec<(L, u8, u64)>,
+    repay_order: u8,
+    amount: u64,
+) -> (Vec<(L, u64)>, u64) {
+    if repay_order == REPAY_ORDER_OLDEST {
+        loans.sort_by_key(|(loan, ..)| (loan.borrow_timestamp, loan.loan_id));
+    } else {
+        loans.sort_by_key(|(loan, rate, _)| {
+            (std::cmp::Reverse(*rate), loan.borrow_timestamp, loan.loan_id)
+        });
+    }
+
+    let mut remaining = amount;
+    let mut repaid = Vec::new();
+    for (loan_position, _, total_due) in loans {
+        if total_due > remaining {
+            break;
+        }
+        remaining -= total_due;
+        repaid.push((loan_position, total_due));
+    }
+    (repaid, amount - remaining)
+}
+
+/// Books the full repayment of a loan's `principal` and capitalized `accrued_interest` with
+/// `amount` already transferred into the lending pool.
+fn apply_repayment(
+    borrower_account: &mut BorrowerAccount,
+    protocol_state: &mut ProtocolState,
+    lending_pool: &mut LendingPool,
+    principal: u64,
+    accrued_interest: u64,
+    amount: u64,
+    now: i64,
+) -> Result<()> {
+    // Distribute a portion of repayment as yield farming rewards (e.g., 1%).
+    let reward = amount.checked_div(100).ok_or(ZKError::MathOverflow)?;
+    distribute_lender_rewards(lending_pool, reward, now)?;
+
+    // Update borrower account: remove the loan's debt, resetting the timestamp once debt-free.
+    borrower_account.active_loans = borrower_account.active_loans.saturating_sub(1);
+    borrower_account.encrypted_principal = update_encrypted_value(
+        borrower_account.encrypted_principal.clone(),
+        principal,
+        false,
+    );
+    borrower_account.accrued_interest =
+        borrower_account.accrued_interest.saturating_sub(accrued_interest);
+    if borrower_debt(borrower_account) == 0 {
+        borrower_account.encrypted_principal = reset_encryption();
+        borrower_account.borrow_timestamp = 0;
+    }
+
+    // Update protocol state; `total_loans` tracks principal only. Global accounting can drift
+    // below the sum of open loans, so a shortfall is clamped and reported rather than failing.
+    if principal > protocol_state.total_loans {
+        emit!(AccountingDriftEvent {
+            principal,
+            total_loans: protocol_state.total_loans,
+        });
+    }
+    protocol_state.total_loans = protocol_state.total_loans.saturating_sub(principal);
+    protocol_state.total_liquidity = protocol_state
+        .total_liquidity
+        .checked_add(amount)
+        .ok_or(ZKError::MathOverflow)?;
+    update_utilization(protocol_state);
+
+    Ok(())
+}
+
+/// Insurance payable on a claim for `requested`: capped by the position's `principal` less what
+/// it has already `claimed` on the loss event, the event's `unpaid_loss`, and the `fund` balance.
+fn insurance_payout(
+    requested: u64,
+    principal: u64,
+    claimed: u64,
+    unpaid_loss: u64,
+    fund: u64,
+) -> u64 {
+    requested
+        .min(principal.saturating_sub(claimed))
+        .min(unpaid_loss)
+        .min(fund)
+}
+
+/// Moves a loan onto the variable curve at `rate`, dropping any rate lock and, for a fixed loan,
+/// its maturity and penalty rate, which only apply to fixed terms.
+fn switch_to_variable_rate(loan_position: &mut LoanPosition, rate: u8) {
+    loan_position.rate_mode = RATE_MODE_VARIABLE;
+    loan_position.interest_rate = rate;
+    loan_position.locked_rate = 0;
+    loan_position.rate_lock_until = 0;
+    loan_position.maturity = 0;
+    loan_position.penalty_rate = 0;
+}
+
+/// Books `repaid` borrow tokens, already transferred into the lending pool by a liquidator,
+/// against the borrower's debt: the pool's liquidity is credited first, then interest and
+/// principal are paid down in that order.
+fn apply_liquidation_repayment(
+    borrower_account: &mut BorrowerAccount,
+    protocol_state: &mut ProtocolState,
+    repaid: u64,
+) -> Result<()> {
+    require!(
+        repaid <= borrower_debt(borrower_account),
+        ZKError::RepayExceedsBorrow
+    );
+    protocol_state.total_liquidity = protocol_state
+        .total_liquidity
+        .checked_add(repaid)
+        .ok_or(ZKError::MathOverflow)?;
+
+    let interest_covered = repaid.min(borrower_account.accrued_interest);
+    let principal_covered = repaid - interest_covered;
+    borrower_account.accrued_interest -= interest_covered;
+    borrower_account.encrypted_principal = update_encrypted_value(
+        borrower_account.encrypted_principal.clone(),
+        principal_covered,
+        false,
+    );
+    if borrower_debt(borrower_account) == 0 {
+        borrower_account.borrow_timestamp = 0;
+    }
+    protocol_state.total_loans = protocol_state.total_loans.saturating_sub(principal_covered);
+    Ok(())
+}
+
+/// Logs `label` and the remaining compute units while `debug_compute` profiling is enabled.
+fn log_compute(protocol_state: &ProtocolState, label: &str) {
+    if protocol_state.debug_compute {
+        msg!(label);
+        sol_log_compute_units();
+    }
+}
+
+fn reset_encryption() -> EncryptedAmount {
+    EncryptedAmount { value: 0 }
+}
+
+fn extract_value_from_encryption(encrypted: EncryptedAmount) -> u64 {
+    encrypted.value
+}
+
+/// Recomputes utilization, emitting `KinkCrossedEvent` when it crosses `optimal_utilization`.
+fn update_utilization(protocol_state: &mut ProtocolState) {
+    let old_util = protocol_state.utilization_rate;
+    let new_util =
+        calculate_utilization(protocol_state.total_loans, protocol_state.total_liquidity);
+    protocol_state.utilization_rate = new_util;
+
+    let optimal = protocol_state.optimal_utilization as u16;
+    if let Some(direction) = kink_crossing(old_util, new_util, optimal) {
+        emit!(KinkCrossedEvent {
+            old_util,
+            new_util,
+            direction,
+        });
+    }
+}
+
+/// Direction in which a move from `old_util` to `new_util` crosses `optimal`, if it does.
+fn kink_crossing(old_util: u16, new_util: u16, optimal: u16) -> Option<u8> {
+    if old_util <= optimal && new_util > optimal {
+        Some(KINK_DIRECTION_UP)
+    } else if old_util > optimal && new_util <= optimal {
+        Some(KINK_DIRECTION_DOWN)
+    } else {
+        None
+    }
+}
+
+/// Utilization as a percentage of liquidity, saturating at `u16::MAX` rather than wrapping.
+fn calculate_utilization(total_loans: u64, total_liquidity: u64) -> u16 {
+    if total_liquidity == 0 {
+        // A fully drained pool with outstanding loans is 100% utilized, not idle.
+        if total_loans > 0 {
+            100
+        } else {
+            0
+        }
+    } else {
+        (total_loans as u128 * 100 / total_liquidity as u128).min(u16::MAX as u128) as u16
+    }
+}
+
+/// Rejects a borrow of `amount` that would push utilization past `max_utilization`. A zero cap
+/// disables the check; callers check liquidity first.
+fn require_max_utilization(protocol_state: &ProtocolState, amount: u64) -> Result<()> {
+    if protocol_state.max_utilization == 0 {
+        return Ok(());
+    }
+    let utilization = calculate_utilization(
+        protocol_state.total_loans.saturating_add(amount),
+        protocol_state.total_liquidity.saturating_sub(amount),
+    );
+    require!(
+        utilization <= protocol_state.max_utilization,
+        ZKError::MaxUtilizationReached
+    );
+    Ok(())
+}
+
+/// Rejects a borrow of `amount` that would push protocol-wide `total_loans / total_collateral`
+/// past `max_protocol_leverage_bps`. A zero limit disables the check.
+fn require_protocol_leverage(protocol_state: &ProtocolState, amount: u64) -> Result<()> {
+    if protocol_state.max_protocol_leverage_bps == 0 {
+        return Ok(());
+    }
+    let loans = protocol_state.total_loans as u128 + amount as u128;
+    require!(
+        loans * 10_000
+            <= protocol_state.total_collateral as u128
+                * protocol_state.max_protocol_leverage_bps as u128,
+        ZKError::ProtocolLeverageExceeded
+    );
+    Ok(())
+}
+
+/// Mirrors a collateral pool total change onto the protocol-wide `total_collateral`.
+/// Decreases saturate, since collateral staked before protocol-wide tracking isn't counted.
+fn adjust_protocol_collateral(
+    protocol_state: &mut ProtocolState,
+    amount: u64,
+    add: bool,
+) -> Result<()> {
+    protocol_state.total_collateral = if add {
+        protocol_state
+            .total_collateral
+            .checked_add(amount)
+            .ok_or(ZKError::MathOverflow)?
+    } else {
+        protocol_state.total_collateral.saturating_sub(amount)
+    };
+    Ok(())
+}
+
+/// Records `amount` against the current rate-limit window, starting a new window if the old one elapsed.
+fn apply_borrow_rate_limit(
+    rate_limit: &mut BorrowRateLimit,
+    amount: u64,
+    now: i64,
+) -> Result<()> {
+    if now - rate_limit.window_start >= rate_limit.window_seconds {
+        rate_limit.window_start = now;
+        rate_limit.borrowed_in_window = 0;
+    }
+    let borrowed = rate_limit
+        .borrowed_in_window
+        .checked_add(amount)
+        .ok_or(ZKError::MathOverflow)?;
+    require!(borrowed <= rate_limit.max_borrow_per_window, ZKError::RateLimited);
+    rate_limit.borrowed_in_window = borrowed;
+    Ok(())
+}
+
+/// Serializes a signed delegation authorization as `delegator || delegate || amount || expiry || nonce`.
+fn delegation_message(
+    delegator: &Pubkey,
+    delegate: &Pubkey,
+    amount: u64,
+    expiry: i64,
+    nonce: u64,
+) -> Vec<u8> {
+    let mut message = Vec::with_capacity(32 + 32 + 8 + 8 + 8);
+    message.extend_from_slice(delegator.as_ref());
+    message.extend_from_slice(delegate.as_ref());
+    message.extend_from_slice(&amount.to_le_bytes());
+    message.extend_from_slice(&expiry.to_le_bytes());
+    message.extend_from_slice(&nonce.to_le_bytes());
+    message
+}
+
+/// Serializes a reserve attestation as `collateral_pool || borrower || attested_reserves || expiry`.
+fn attestation_message(
+    collateral_pool: &Pubkey,
+    borrower: &Pubkey,
+    attestation: &ReserveAttestation,
+) -> Vec<u8> {
+    let mut message = Vec::with_capacity(32 + 32 + 8 + 8);
+    message.extend_from_slice(collateral_pool.as_ref());
+    message.extend_from_slice(borrower.as_ref());
+    message.extend_from_slice(&attestation.attested_reserves.to_le_bytes());
+    message.extend_from_slice(&attestation.expiry.to_le_bytes());
+    message
+}
+
+/// Checks that the current instruction is a top-level call to this program and that a later
+/// instruction in the transaction is a `flash_repay` for `flash_loan`.
+fn require_flash_repay(instructions_sysvar: &AccountInfo, flash_loan: &Pubkey) -> Result<()> {
+    let current_index = load_current_index_checked(instructions_sysvar)? as usize;
+    let current = load_instruction_at_checked(current_index, instructions_sysvar)?;
+    require!(current.program_id == crate::ID, ZKError::FlashLoanNotRepaid);
+
+    let mut index = current_index + 1;
+    while let Ok(ix) = load_instruction_at_checked(index, instructions_sysvar) {
+        if ix.program_id == crate::ID
+            && ix.data.get(..8) == Some(&instruction::FlashRepay::DISCRIMINATOR[..])
+            && ix.accounts.iter().any(|meta| meta.pubkey == *flash_loan)
+        {
+            return Ok(());
+        }
+        index += 1;
+    }
+    err!(ZKError::FlashLoanNotRepaid)
+}
+
+/// Checks that the previous instruction is an ed25519 precompile verifying `message` signed by `signer`.
+fn verify_ed25519_instruction(
+    instructions_sysvar: &AccountInfo,
+    signer: &Pubkey,
+    message: &[u8],
+) -> Result<()> {
+    let current_index = load_current_index_checked(instructions_sysvar)?;
+    require!(current_index > 0, ZKError::InvalidSignature);
+    let ix = load_instruction_at_checked((current_index - 1) as usize, instructions_sysvar)?;
+    require!(
+        ix.program_id == ed25519_program::ID,
+        ZKError::InvalidSignature
+    );
+
+    // Layout: num_signatures (u8), padding (u8), then one 14-byte offsets struct.
+    let data = &ix.data;
+    require!(data.len() >= 16 && data[0] == 1, ZKError::InvalidSignature);
+    let read_u16 = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]) as usize;
+    let public_key_offset = read_u16(6);
+    let message_offset = read_u16(10);
+    let message_size = read_u16(12);
+
+    // Signature, key, and message must all live in the precompile instruction itself.
+    let this_instruction = u16::MAX as usize;
+    require!(
+        read_u16(4) == this_instruction
+            && read_u16(8) == this_instruction
+            && read_u16(14) == this_instruction,
+        ZKError::InvalidSignature
+    );
+
+    let signed_key = data
+        .get(public_key_offset..public_key_offset + 32)
+        .ok_or(ZKError::InvalidSignature)?;
+    let signed_message = data
+        .get(message_offset..message_offset + message_size)
+        .ok_or(ZKError::InvalidSignature)?;
+    require!(
+        signed_key == signer.as_ref() && signed_message == message,
+        ZKError::InvalidSignature
+    );
+    Ok(())
+}
+
+/// Kinked variable borrow rate (% per annum) at the protocol's current utilization.
+///
+/// The rate climbs from `base_interest_rate` by `rate_slope_low` up to `optimal_utilization`,
+/// then by a further `rate_slope_high` between the kink and 100% utilization.
+fn calculate_borrow_rate(protocol_state: &ProtocolState) -> u8 {
+    curve_rate(
+        protocol_state,
+        protocol_state.base_interest_rate,
+        protocol_state.utilization_rate,
+    )
+}
+
+/// Rate on the protocol's kinked curve for a market with its own base rate and utilization.
+fn curve_rate(protocol_state: &ProtocolState, base_interest_rate: u8, utilization: u16) -> u8 {
+    let utilization = utilization.min(100) as u64;
+    let optimal = protocol_state.optimal_utilization as u64;
+    let base = base_interest_rate as u64;
+    let slope_low = protocol_state.rate_slope_low as u64;
+    let slope_high = protocol_state.rate_slope_high as u64;
+
+    let rate = if utilization <= optimal {
+        base + slope_low * utilization / optimal.max(1)
+    } else {
+        base + slope_low + slope_high * (utilization - optimal) / (100 - optimal).max(1)
+    };
+    rate.min(u8::MAX as u64) as u8
+}
+
+/// Advances a lending pool's borrow and supply indexes to `now` at the pool's own curve rate.
+///
+/// The borrow index grows at the pool's borrow rate and the supply index at that rate scaled
+/// by the pool's utilization, each compounding once per accrual. Both start at `RAY`; the first
+/// accrual only starts the clock.
+fn accrue_pool_indexes(
+    lending_pool: &mut LendingPool,
+    protocol_state: &ProtocolState,
+    now: i64,
+) -> Result<()> {
+    if lending_pool.cumulative_borrow_index == 0 {
+        lending_pool.cumulative_borrow_index = RAY;
+        lending_pool.cumulative_supply_index = RAY;
+    }
+    let elapsed = if lending_pool.last_accrual == 0 {
+        0
+    } else {
+        now.saturating_sub(lending_pool.last_accrual).max(0) as u128
+    };
+    lending_pool.last_accrual = now.max(lending_pool.last_accrual);
+    if elapsed == 0 {
+        return Ok(());
+    }
+
+    const YEAR_PERCENT: u128 = 31_536_000 * 100;
+    let utilization = lending_pool.utilization_rate.min(100) as u128;
+    let rate = curve_rate(
+        protocol_state,
+        lending_pool.base_interest_rate,
+        lending_pool.utilization_rate as u16,
+    ) as u128;
+    let borrow_growth = mul_div_down(
+        lending_pool.cumulative_borrow_index,
+        rate * elapsed,
+        YEAR_PERCENT,
+    )?;
+    let supply_growth = mul_div_down(
+        lending_pool.cumulative_supply_index,
+        rate * utilization * elapsed,
+        YEAR_PERCENT * 100,
+    )?;
+    lending_pool.cumulative_borrow_index = lending_pool
+        .cumulative_borrow_index
+        .checked_add(borrow_growth)
+        .ok_or(ZKError::MathOverflow)?;
+    lending_pool.cumulative_supply_index = lending_pool
+        .cumulative_supply_index
+        .checked_add(supply_growth)
+        .ok_or(ZKError::MathOverflow)?;
+    Ok(())
+}
+
+/// Splits `reward` pro rata between protocol-owned and lender liquidity. The protocol-owned
+/// share goes to `protocol_yield` for the treasury; the rest is added to `lender_rewards` and
+/// raises the reward index by the reward per unit of lender liquidity.
+///
+/// The protocol share and the index both round down, so lenders' combined claims never exceed
+/// the rewards distributed. Lender rewards are also tallied per `APY_WINDOW_SECONDS` window for
+/// the realized supply APY.
+fn distribute_lender_rewards(lending_pool: &mut LendingPool, reward: u64, now: i64) -> Result<()> {
+    let lender_liquidity = lending_pool
+        .total_liquidity
+        .saturating_sub(lending_pool.protocol_owned_liquidity);
+    let protocol_share = if lending_pool.total_liquidity == 0 {
+        0
+    } else {
+        mul_div_down(
+            reward as u128,
+            lending_pool.protocol_owned_liquidity.min(lending_pool.total_liquidity) as u128,
+            lending_pool.total_liquidity as u128,
+        )? as u64
+    };
+    lending_pool.protocol_yield = lending_pool
+        .protocol_yield
+        .checked_add(protocol_share)
+        .ok_or(ZKError::MathOverflow)?;
+    let reward = reward.checked_sub(protocol_share).ok_or(ZKError::MathOverflow)?;
+    lending_pool.lender_rewards = lending_pool
+        .lender_rewards
+        .checked_add(reward)
+        .ok_or(ZKError::MathOverflow)?;
+
+    if now.saturating_sub(lending_pool.rewards_window_start) >= APY_WINDOW_SECONDS {
+        lending_pool.rewards_window_start = now;
+        lending_pool.rewards_accrued_in_window = 0;
+    }
+    lending_pool.rewards_accrued_in_window = lending_pool
+        .rewards_accrued_in_window
+        .checked_add(reward)
+        .ok_or(ZKError::MathOverflow)?;
+    if lender_liquidity > 0 {
+        let delta = (reward as u128)
+            .checked_mul(RAY)
+            .and_then(|v| v.checked_div(lender_liquidity as u128))
+            .ok_or(ZKError::MathOverflow)?;
+        lending_pool.reward_index = lending_pool
+            .reward_index
+            .checked_add(delta)
+            .ok_or(ZKError::MathOverflow)?;
+    }
+    Ok(())
+}
+
+/// Realized supply APY in basis points: rewards distributed to lenders in the current window,
+/// from repayment interest and donations alike, annualized over lender liquidity.
+fn realized_supply_apy_bps(lending_pool: &LendingPool, now: i64) -> u64 {
+    let lender_liquidity = lending_pool
+        .total_liquidity
+        .saturating_sub(lending_pool.protocol_owned_liquidity);
+    let elapsed = now.saturating_sub(lending_pool.rewards_window_start).max(1) as u128;
+    if lender_liquidity == 0 || elapsed > APY_WINDOW_SECONDS as u128 {
+        return 0;
+    }
+    let apy = lending_pool.rewards_accrued_in_window as u128 * 10_000 * 31_536_000
+        / (lender_liquidity as u128 * elapsed);
+    apy.min(u64::MAX as u128) as u64
+}
+
+/// Moves rewards earned since the lender's last snapshot into `pending_rewards` and
+/// re-snapshots the pool's reward index.
+fn settle_lender_rewards(
+    lender_position: &mut LenderPosition,
+    lending_pool: &LendingPool,
+) -> Result<()> {
+    let delta = lending_pool
+        .reward_index
+        .checked_sub(lender_position.reward_index_snapshot)
+        .ok_or(ZKError::MathOverflow)?;
+    let earned = mul_div_down(delta, lender_position.principal as u128, RAY)?;
+    lender_position.pending_rewards = lender_position
+        .pending_rewards
+        .checked_add(earned as u64)
+        .ok_or(ZKError::MathOverflow)?;
+    lender_position.reward_index_snapshot = lending_pool.reward_index;
+    Ok(())
+}
+
+/// Settles a lender's rewards and takes everything they can claim out of the pool, returning
+/// the payout. The base share comes out of `lender_rewards`. The tenure boost on top of it is
+/// paid from `reward_boost_budget` and shrinks to what the budget still holds, so boosted
+/// claims never dip into other lenders' rewards.
+fn take_lender_rewards(
+    lender_position: &mut LenderPosition,
+    lending_pool: &mut LendingPool,
+    now: i64,
+) -> Result<u64> {
+    settle_lender_rewards(lender_position, lending_pool)?;
+    let base_share = lender_position.pending_rewards.min(lending_pool.lender_rewards);
+    let tenure = now.checked_sub(lender_position.deposit_timestamp).unwrap_or(0);
+    let boost = (mul_div_down(base_share as u128, reward_boost_bps(tenure) as u128, 10_000)?
+        as u64)
+        .min(lending_pool.reward_boost_budget);
+    let payout = base_share.checked_add(boost).ok_or(ZKError::MathOverflow)?;
+
+    lender_position.pending_rewards = 0;
+    lending_pool.lender_rewards = lending_pool
+        .lender_rewards
+        .checked_sub(base_share)
+        .ok_or(ZKError::MathOverflow)?;
+    lending_pool.reward_boost_budget = lending_pool
+        .reward_boost_budget
+        .checked_sub(boost)
+        .ok_or(ZKError::MathOverflow)?;
+    Ok(payout)
+}
+
+/// Reward boost in basis points for a lender who has held a deposit for `tenure` seconds.
+fn reward_boost_bps(tenure: i64) -> u64 {
+    let days = (tenure.max(0) as u64) / 86_400;
+    days.saturating_mul(REWARD_BOOST_BPS_PER_DAY).min(MAX_REWARD_BOOST_BPS)
+}
+
+// ─────────────────────────────────────────────────────────────
+// Data Structures & Accounts
+// ─────────────────────────────────────────────────────────────
+
+/// Represents an encrypted amount (placeholder for real ZK encryption).
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
+pub struct EncryptedAmount {
+    pub value: u64,
+}
+
+/// A custodian's signed statement that off-chain reserves back a stake.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct ReserveAttestation {
+    pub attested_reserves: u64,
+    pub expiry: i64,
+}
+
+/// Cached health factor of a borrower, in basis points, and when it was computed.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct SolvencySnapshot {
+    pub health_factor: u64,
+    pub computed_at: i64,
+}
+
+impl SolvencySnapshot {
+    /// Snapshots older than `SOLVENCY_SNAPSHOT_TTL` must be ignored and recomputed.
+    pub fn is_fresh(&self, now: i64) -> bool {
+        self.computed_at > 0 && now - self.computed_at <= SOLVENCY_SNAPSHOT_TTL
+    }
+}
+
+/// Borrow fee tier: amounts at or above `threshold` pay `bps` basis points.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct FeeTier {
+    pub threshold: u64,
+    pub bps: u16,
+}
+
+/// Liquidation grace tier: debts at or above `min_pool_share_bps` of the pool wait `grace_slots`
+/// between being marked and liquidated.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct GraceTier {
+    pub min_pool_share_bps: u16,
+    pub grace_slots: u64,
+}
+
+/// A collateral asset listed through governance and the pool that holds it.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct ListedAsset {
+    pub mint: Pubkey,
+    pub collateral_pool: Pubkey,
+}
+
+/// Collateral lock tier: borrowers with a reputation score of at least `min_reputation_score`
+/// wait `lock_time` seconds between borrows instead of `min_collateral_lock_time`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct LockTier {
+    pub min_reputation_score: u64,
+    pub lock_time: i64,
+}
+
+/// Global protocol state.
+#[account]
+pub struct ProtocolState {
+    pub total_collateral: u64,
+    pub total_loans: u64,
+    pub total_liquidity: u64,
+    pub base_interest_rate: u8,
+    pub utilization_rate: u16,
+    pub min_collateral_lock_time: i64,
+    pub min_collateral_ratio: u16,
+    pub admin: Pubkey,
+    pub fee_tiers: [FeeTier; MAX_FEE_TIERS],
+    pub fee_tier_count: u8,
+    pub optimal_utilization: u8,
+    pub rate_slope_low: u8,
+    pub rate_slope_high: u8,
+    pub borrows_frozen: bool,
+    pub liquidation_protocol_share_bps: u16,
+    pub liquidity_bootstrapped: bool,
+    pub grace_tiers: [GraceTier; MAX_GRACE_TIERS],
+    pub grace_tier_count: u8,
+    pub borrow_whitelist: Pubkey,
+    pub rebalance_min_health: u64,
+    pub max_loans_per_borrower: u32,
+    pub max_protocol_leverage_bps: u16,
+    pub early_repay_window: i64,
+    pub early_repay_rebate_bps: u16,
+    pub confidence_multiplier_bps: u16,
+    pub fees_enabled: bool,
+    pub debug_compute: bool,
+    pub interest_free_seconds: i64,
+    pub max_utilization: u16,
+    pub lock_tiers: [LockTier; MAX_LOCK_TIERS],
+    pub lock_tier_count: u8,
+    pub overdue_penalty_rate: u8,
+    pub stake_settle_time: i64,
+    pub bad_debt: u64,
+    pub recap_premium_bps: u16,
+    pub liquidator_bonus_floor: u64,
+    pub repay_order: u8,
+    pub min_proposal_bond: u64,
+}
+
+/// Verifying keys for each ZK circuit, indexed by `CIRCUIT_*` id; an all-zero key is unregistered.
+#[account]
+pub struct VerifyingKeyRegistry {
+    pub keys: [[u8; 32]; MAX_CIRCUITS],
+}
+
+/// Every collateral asset the protocol supports, for clients to discover markets.
+#[account]
+pub struct AssetRegistry {
+    pub assets: Vec<ListedAsset>,
+}
+
+/// KYC whitelist for the standard borrow path.
+#[account]
+pub struct BorrowWhitelist {
+    pub members: Vec<Pubkey>,
+}
+
+/// Lending pool state.
+#[account]
+pub struct LendingPool {
+    pub pool_authority: Pubkey,
+    pub total_liquidity: u64,
+    pub base_interest_rate: u8,
+    pub utilization_rate: u8,
+    pub lender_rewards: u64,
+    pub borrow_enabled: bool,
+    pub reward_index: u128,
+    pub protocol_owned_liquidity: u64,
+    pub min_liquidity_buffer: u64,
+    pub rewards_window_start: i64,
+    pub rewards_accrued_in_window: u64,
+    pub borrow_mint: Pubkey,
+    /// Rewards earned by protocol-owned liquidity, kept apart from `lender_rewards` until
+    /// claimed to the treasury.
+    pub protocol_yield: u64,
+    /// Treasury funds set aside to pay lenders' tenure boosts.
+    pub reward_boost_budget: u64,
+    /// `RAY`-scaled growth of a unit of debt at this pool's borrow rate.
+    pub cumulative_borrow_index: u128,
+    /// `RAY`-scaled growth of a unit of supply at this pool's supply rate.
+    pub cumulative_supply_index: u128,
+    pub last_accrual: i64,
+}
+
+/// Multi-collateral pool state.
+#[account]
+pub struct CollateralPool {
+    pub asset_mint: Pubkey,
+    pub total_collateral: u64,
+    pub collateral_factor: u16,
+    pub liquidation_threshold: u16,
+    pub custodian_key: Pubkey,
+    pub index_account: Pubkey,
+    pub base_index: u64,
+    pub current_index: u64,
+    pub liquidation_bonus_bps: u16,
+    pub oracle_kind: u8,
+    pub oracle: Pubkey,
+    pub min_stake: u64,
+    pub factor_decay_start: i64,
+    pub factor_decay_bps_per_day: u16,
+    pub min_collateral_factor: u16,
+}
+
+/// A whitelisted institution; exempt institutions skip the flash loan lock time, and
+/// `last_nonce` is the highest nonce its borrows have used.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct WhitelistEntry {
+    pub institution: Pubkey,
+    pub exempt_from_lock: bool,
+    pub last_nonce: u64,
+}
+
+/// Institutional lending pool state.
+#[account]
+pub struct InstitutionalLendingPool {
+    pub pool_owner: Pubkey,
+    pub total_liquidity: u64,
+    pub fixed_interest_rate: u8,
+    pub zk_whitelist: Vec<WhitelistEntry>,
+}
+
+/// Treasury account for collecting protocol fees.
+#[account]
+pub struct ProtocolTreasury {
+    pub total_fees_collected: u64,
+    pub governance_fund: u64,
+    pub reserve_cap: u64,
+    pub insurance_fund: u64,
+    pub liquidator_rebate_pool: u64,
+}
+
+/// Insurance paid to one lender position for one loss event, capped in total at its principal.
+#[account]
+pub struct InsuranceClaim {
+    pub loss_event: Pubkey,
+    pub lender_position: Pubkey,
+    pub claimed: u64,
+}
+
+/// A bad-debt auction bidder's claim on future treasury fees.
+#[account]
+pub struct RecapClaim {
+    pub bidder: Pubkey,
+    /// Total bad debt this bidder has covered.
+    pub covered: u64,
+    /// Treasury fees still owed to the bidder, including the premium.
+    pub owed: u64,
+}
+
+/// Borrower account storing confidential collateral and borrow amounts.
+#[account]
+pub struct BorrowerAccount {
+    pub owner: Pubkey,
+    pub encrypted_collateral: EncryptedAmount,
+    pub encrypted_principal: EncryptedAmount,
+    pub accrued_interest: u64,
+    pub borrow_timestamp: i64,
+    pub liquidation_marked_slot: u64,
+    pub next_loan_id: u64,
+    pub solvency_snapshot: SolvencySnapshot,
+    pub active_loans: u32,
+    pub stake_lock_until: i64,
+    pub early_withdraw_penalty_bps: u16,
+    pub protection_buffer: u64,
+    /// Collateral posted by co-signers; only they can withdraw it.
+    pub cosigned_collateral: u64,
+    /// When collateral was last staked into this account.
+    pub last_stake_at: i64,
+    /// Borrows blocked by flash loan protection.
+    pub flash_attempts: u32,
+}
+
+/// Collateral a co-signer has posted for a borrower, returned to the co-signer on withdrawal.
+#[account]
+pub struct CosignerStake {
+    pub cosigner: Pubkey,
+    pub borrower_account: Pubkey,
+    pub amount: u64,
+}
+
+/// Pre-funded collateral a keeper may stake on a borrower's behalf to restore health.
+#[account]
+pub struct CollateralBuffer {
+    pub borrower_account: Pubkey,
+    pub keeper: Pubkey,
+    pub token_account: Pubkey,
+    pub bump: u8,
+}
+
+/// Replay protection for signed delegation authorizations between a delegator and delegate.
+#[account]
+pub struct DelegationNonce {
+    pub last_nonce: u64,
+}
+
+/// An individual loan held by a borrower, keyed by `[borrower_account, loan_id]`.
+#[account]
+pub struct LoanPosition {
+    pub borrower_account: Pubkey,
+    pub loan_id: u64,
+    pub principal: u64,
+    pub rate_mode: u8,
+    pub interest_rate: u8,
+    pub borrow_timestamp: i64,
+    pub interest_accrued_at: i64,
+    pub locked_rate: u8,
+    pub rate_lock_until: i64,
+    pub residual_interest: u128,
+    pub origination_fee: u64,
+    pub accrued_interest: u64,
+    /// Delegation whose credit line funded this loan, or the default key for a direct borrow.
+    pub delegation: Pubkey,
+    /// When a fixed-rate institutional loan falls due and may be rolled over; zero otherwise.
+    pub maturity: i64,
+    /// Rate accrued past `maturity` instead of the loan's own rate; zero if none.
+    pub penalty_rate: u8,
+    /// Set once a keeper records a missed payment; the loan's rates carry a premium from then on.
+    pub delinquent: bool,
+}
+
+/// Sliding-window limiter on total protocol borrows.
+#[account]
+pub struct BorrowRateLimit {
+    pub max_borrow_per_window: u64,
+    pub window_seconds: i64,
+    pub window_start: i64,
+    pub borrowed_in_window: u64,
+}
+
+/// Lender position: liquidity deposited into a lending pool.
+#[account]
+pub struct LenderPosition {
+    pub lender: Pubkey,
+    pub lending_pool: Pubkey,
+    pub principal: u64,
+    pub deposit_timestamp: i64,
+    pub rewards_claimed: u64,
+    pub reward_index_snapshot: u128,
+    pub pending_rewards: u64,
+}
+
+/// Flash loan in flight against a lending pool; `amount` is zero when none is open.
+#[account]
+pub struct FlashLoan {
+    pub borrower: Pubkey,
+    pub amount: u64,
+    pub fee: u64,
+}
+
+/// Transferable claim on rewards snapshotted from a lender position.
+#[account]
+pub struct RewardReceipt {
+    pub lending_pool: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+}
+
+/// Borrower reputation (for a ZK-based reputation system).
+#[account]
+pub struct BorrowerReputation {
+    pub borrower: Pubkey,
+    pub zk_reputation_score: u64,
+}
+
+/// Governance proposal.
+#[account]
+pub struct Governance {
+    pub proposal_id: u64,
+    pub proposal_type: u8,
+    pub new_value: u64,
+    pub votes: i64,
+    pub executed: bool,
+    pub asset_mint: Pubkey,
+    pub collateral_factor: u16,
+    pub liquidation_threshold: u16,
+    pub pool: Pubkey,
+    pub proposer: Pubkey,
+    pub voting_deadline: i64,
+    /// Lamports posted by `bond_source` when proposing, held in this account.
+    pub bond: u64,
+    pub bond_source: Pubkey,
+}
+
+/// A single executed parameter change.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct ParamChange {
+    pub proposal_id: u64,
+    pub proposal_type: u8,
+    pub old_value: u64,
+    pub new_value: u64,
+    pub timestamp: i64,
+}
+
+/// Ring buffer audit trail of executed governance parameter changes.
+#[account]
+pub struct ParamChangeLog {
+    pub entries: [ParamChange; PARAM_CHANGE_LOG_CAPACITY],
+    /// Slot the next entry is written to.
+    pub head: u32,
+    /// Number of valid entries, up to `PARAM_CHANGE_LOG_CAPACITY`.
+    pub len: u32,
+}
+
+impl ParamChangeLog {
+    /// Appends an entry, overwriting the oldest once the buffer is full.
+    pub fn record(&mut self, change: ParamChange) {
+        self.entries[self.head as usize] = change;
+        self.head = (self.head + 1) % PARAM_CHANGE_LOG_CAPACITY as u32;
+        self.len = (self.len + 1).min(PARAM_CHANGE_LOG_CAPACITY as u32);
+    }
+}
+
+/// A member borrowing under a whitelisted institution's entry in `institutional_pool`, capped
+/// at `max_borrow_amount` in total and with its own borrow nonce sequence.
+#[account]
+pub struct SubAccount {
+    pub institutional_pool: Pubkey,
+    pub parent: Pubkey,
+    pub member: Pubkey,
+    pub max_borrow_amount: u64,
+    pub borrowed_so_far: u64,
+    pub last_nonce: u64,
+}
+
+/// Delegated borrower: credit line assigned by a delegator.
+#[account]
+pub struct DelegatedBorrower {
+    pub delegator: Pubkey,
+    pub delegate: Pubkey,
+    pub max_borrow_amount: u64,
+    pub borrowed_so_far: u64,
+}
+
+/// A keeper authorized to liquidate on an institution's behalf; the bonus from its
+/// liquidations is paid to `beneficiary`.
+#[account]
+pub struct LiquidationDelegate {
+    pub principal: Pubkey,
+    pub keeper: Pubkey,
+    pub beneficiary: Pubkey,
+}
+
+// ─────────────────────────────────────────────────────────────
+// Events
+// ─────────────────────────────────────────────────────────────
+
+/// Plaintext balances revealed by a borrower account's owner.
+#[event]
+pub struct BalanceRevealEvent {
+    pub owner: Pubkey,
+    pub collateral: u64,
+    pub borrowed: u64,
+}
+
+/// A collateral pool's `total_collateral` was corrected to its escrow balance.
+#[event]
+pub struct ReconcileEvent {
+    pub collateral_pool: Pubkey,
+    pub before: u64,
+    pub after: u64,
+}
+
+/// Preview of a borrow's outcome, emitted by `simulate_borrow`.
+#[event]
+pub struct BorrowSimulationEvent {
+    pub borrower_account: Pubkey,
+    pub amount: u64,
+    pub fee: u64,
+    pub net_amount: u64,
+    pub health_factor: u64,
+    pub utilization_rate: u16,
+    pub interest_rate: u8,
+}
+
+/// A borrow was blocked by flash loan protection, `attempted_at - last_borrow_at` seconds
+/// after the borrower's last borrow when `lock_time` seconds were required. `flash_attempts` is
+/// the borrower's running count of blocked borrows, for anomaly detection.
+#[event]
+pub struct FlashProtectionEvent {
+    pub borrower_account: Pubkey,
+    pub last_borrow_at: i64,
+    pub attempted_at: i64,
+    pub lock_time: i64,
+    pub flash_attempts: u32,
+}
+
+/// Context for a failed check, emitted just before the matching error is returned.
+/// `available` is omitted when it would reveal a confidential balance.
+#[event]
+pub struct ErrorDiagnosticEvent {
+    pub error_code: u32,
+    pub required: u64,
+    pub available: Option<u64>,
+}
+
+/// Pool utilization crossed `optimal_utilization`; `direction` is a `KINK_DIRECTION_*` value.
+#[event]
+pub struct KinkCrossedEvent {
+    pub old_util: u16,
+    pub new_util: u16,
+    pub direction: u8,
+}
+
+/// Snapshot of a lending pool for dashboards; `supply_apy_bps` is realized over the current
+/// rewards window and includes donations.
+#[event]
+pub struct PoolStatsEvent {
+    pub lending_pool: Pubkey,
+    pub total_liquidity: u64,
+    pub utilization_rate: u16,
+    pub borrow_rate: u8,
+    pub rewards_accrued_in_window: u64,
+    pub supply_apy_bps: u64,
+}
+
+/// Collateral price, scaled by `ORACLE_PRICE_ONE`, below which a position is liquidatable;
+/// `None` when the position has no debt.
+#[event]
+pub struct LiquidationPriceEvent {
+    pub borrower_account: Pubkey,
+    pub debt: u64,
+    pub collateral: u64,
+    pub liquidation_price: Option<u64>,
+}
+
+/// A borrower's position at a glance. `collateral_value` uses the liquidation-side price;
+/// `health_factor` is `u64::MAX` and `liquidation_price` is `None` when there is no debt.
+#[event]
+pub struct PositionSummaryEvent {
+    pub borrower_account: Pubkey,
+    pub collateral_value: u64,
+    pub debt: u64,
+    pub health_factor: u64,
+    pub accrued_interest: u64,
+    pub liquidation_price: Option<u64>,
+}
+
+/// Rewards earned by a lending pool's protocol-owned liquidity were claimed to the treasury.
+#[event]
+pub struct ProtocolYieldClaimedEvent {
+    pub lending_pool: Pubkey,
+    pub amount: u64,
+}
+
+/// A closed proposal's bond was refunded to `bond_source`, or forfeited to the treasury when
+/// the proposal failed.
+#[event]
+pub struct ProposalBondEvent {
+    pub proposal_id: u64,
+    pub bond_source: Pubkey,
+    pub bond: u64,
+    pub forfeited: bool,
+}
+
+/// A proposal's lifecycle state (a `PROPOSAL_STATE_*` value) and tally. Passed proposals
+/// execute without a timelock, so there is no timelock deadline to report.
+#[event]
+pub struct ProposalStatusEvent {
+    pub proposal_id: u64,
+    pub state: u8,
+    pub votes: i64,
+    pub quorum: u64,
+    pub voting_ends_at: i64,
+}
+
+/// A `repay_all` lump sum paid off and closed loans `loan_ids`, in waterfall order.
+#[event]
+pub struct RepayAllEvent {
+    pub borrower_account: Pubkey,
+    pub loan_ids: Vec<u64>,
+    pub amount_used: u64,
+}
+
+/// Principal was split off loan `loan_id` into a new loan `split_loan_id`.
+#[event]
+pub struct LoanSplitEvent {
+    pub loan_id: u64,
+    pub split_loan_id: u64,
+    pub principal: u64,
+    pub split_principal: u64,
+}
+
+/// A `loop_borrow` ran `rounds` borrow-and-stake rounds, booking `borrowed` as one loan.
+#[event]
+pub struct LoopBorrowEvent {
+    pub borrower_account: Pubkey,
+    pub rounds: u8,
+    pub borrowed: u64,
+    pub staked: u64,
+}
+
+/// A liquidator's thin bonus, valued in borrow tokens, was topped up from the rebate pool.
+#[event]
+pub struct LiquidatorRebateEvent {
+    pub liquidator: Pubkey,
+    pub bonus_value: u64,
+    pub rebate: u64,
+}
+
+/// A keeper recorded a missed payment, flagging the loan delinquent at its raised rate.
+#[event]
+pub struct MissedPaymentEvent {
+    pub loan_position: Pubkey,
+    pub borrower: Pubkey,
+    pub interest_rate: u8,
+    pub reputation_score: u64,
+}
+
+/// A liquidation left debt with worthless collateral behind; its principal was written off.
+#[event]
+pub struct BadDebtEvent {
+    pub borrower_account: Pubkey,
+    pub written_off: u64,
+    pub bad_debt: u64,
+}
+
+/// A repayment's principal exceeded the protocol's `total_loans`, which was clamped to zero.
+#[event]
+pub struct AccountingDriftEvent {
+    pub principal: u64,
+    pub total_loans: u64,
+}
+
+// ─────────────────────────────────────────────────────────────
+// Contexts
+// ─────────────────────────────────────────────────────────────
+
+#[derive(Accounts)]
+pub struct Initialize<'info> {
+    #[account(
+        init,
+        payer = user,
+        space = PROTOCOL_STATE_LEN,
+        seeds = [b"protocol_state"],
+        bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+    #[account(
+        init,
+        payer = user,
+        space = 8 + 8 + 8 + 8 + 8 + 8,
+        seeds = [b"protocol_treasury"],
+        bump
+    )]
+    pub protocol_treasury: Account<'info, ProtocolTreasury>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeBorrowerAccount<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + 32 + 8 + 8 + 8 + 8 + 8 + 8 + (8 + 8) + 4 + 8 + 2 + 8 + 8 + 8 + 4,
+        seeds = [b"borrower", owner.key().as_ref()],
+        bump
+    )]
+    pub borrower_account: Account<'info, BorrowerAccount>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetReserveCap<'info> {
+    pub admin: Signer<'info>,
+    #[account(has_one = admin @ ZKError::Unauthorized)]
+    pub protocol_state: Account<'info, ProtocolState>,
+    #[account(mut)]
+    pub protocol_treasury: Account<'info, ProtocolTreasury>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateProtocolState<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    /// CHECK: Legacy-layout protocol state; size, discriminator, and admin are checked in the handler.
+    #[account(mut, owner = crate::ID)]
+    pub protocol_state: AccountInfo<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetLiquidationProtocolShare<'info> {
+    pub admin: Signer<'info>,
+    #[account(mut, has_one = admin @ ZKError::Unauthorized)]
+    pub protocol_state: Account<'info, ProtocolState>,
+}
+
+#[derive(Accounts)]
+pub struct SetBorrowWhitelist<'info> {
+    pub admin: Signer<'info>,
+    #[account(mut, has_one = admin @ ZKError::Unauthorized)]
+    pub protocol_state: Account<'info, ProtocolState>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterCircuit<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(has_one = admin @ ZKError::Unauthorized)]
+    pub protocol_state: Account<'info, ProtocolState>,
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + 32 * MAX_CIRCUITS,
+        seeds = [b"verifying_keys"],
+        bump
+    )]
+    pub verifying_key_registry: Account<'info, VerifyingKeyRegistry>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetBorrowWhitelistMembers<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    #[account(has_one = admin @ ZKError::Unauthorized)]
+    pub protocol_state: Account<'info, ProtocolState>,
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + 4 + 32 * MAX_BORROW_WHITELIST_MEMBERS,
+        seeds = [b"borrow_whitelist"],
+        bump
+    )]
+    pub borrow_whitelist: Account<'info, BorrowWhitelist>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetRebalanceMinHealth<'info> {
+    pub admin: Signer<'info>,
+    #[account(mut, has_one = admin @ ZKError::Unauthorized)]
+    pub protocol_state: Account<'info, ProtocolState>,
+}
+
+#[derive(Accounts)]
+pub struct SetMaxLoansPerBorrower<'info> {
+    pub admin: Signer<'info>,
+    #[account(mut, has_one = admin @ ZKError::Unauthorized)]
+    pub protocol_state: Account<'info, ProtocolState>,
+}
+
+#[derive(Accounts)]
+pub struct SetMaxProtocolLeverage<'info> {
+    pub admin: Signer<'info>,
+    #[account(mut, has_one = admin @ ZKError::Unauthorized)]
+    pub protocol_state: Account<'info, ProtocolState>,
+}
+
+#[derive(Accounts)]
+pub struct SetMaxUtilization<'info> {
+    pub admin: Signer<'info>,
+    #[account(mut, has_one = admin @ ZKError::Unauthorized)]
+    pub protocol_state: Account<'info, ProtocolState>,
+}
+
+#[derive(Accounts)]
+pub struct SetConfidenceMultiplier<'info> {
+    pub admin: Signer<'info>,
+    #[account(mut, has_one = admin @ ZKError::Unauthorized)]
+    pub protocol_state: Account<'info, ProtocolState>,
+}
+
+#[derive(Accounts)]
+pub struct SetInterestFreePeriod<'info> {
+    pub admin: Signer<'info>,
+    #[account(mut, has_one = admin @ ZKError::Unauthorized)]
+    pub protocol_state: Account<'info, ProtocolState>,
+}
+
+#[derive(Accounts)]
+pub struct SetOverduePenaltyRate<'info> {
+    pub admin: Signer<'info>,
+    #[account(mut, has_one = admin @ ZKError::Unauthorized)]
+    pub protocol_state: Account<'info, ProtocolState>,
+}
+
+#[derive(Accounts)]
+pub struct SetStakeSettleTime<'info> {
+    pub admin: Signer<'info>,
+    #[account(mut, has_one = admin @ ZKError::Unauthorized)]
+    pub protocol_state: Account<'info, ProtocolState>,
+}
+
+#[derive(Accounts)]
+pub struct SetLiquidatorBonusFloor<'info> {
+    pub admin: Signer<'info>,
+    #[account(mut, has_one = admin @ ZKError::Unauthorized)]
+    pub protocol_state: Account<'info, ProtocolState>,
+}
+
+#[derive(Accounts)]
+pub struct SetMinProposalBond<'info> {
+    pub admin: Signer<'info>,
+    #[account(mut, has_one = admin @ ZKError::Unauthorized)]
+    pub protocol_state: Account<'info, ProtocolState>,
+}
+
+#[derive(Accounts)]
+pub struct SetRepayOrder<'info> {
+    pub admin: Signer<'info>,
+    #[account(mut, has_one = admin @ ZKError::Unauthorized)]
+    pub protocol_state: Account<'info, ProtocolState>,
+}
+
+#[derive(Accounts)]
+pub struct SetRecapPremium<'info> {
+    pub admin: Signer<'info>,
+    #[account(mut, has_one = admin @ ZKError::Unauthorized)]
+    pub protocol_state: Account<'info, ProtocolState>,
+}
+
+#[derive(Accounts)]
+pub struct SetDebugCompute<'info> {
+    pub admin: Signer<'info>,
+    #[account(mut, has_one = admin @ ZKError::Unauthorized)]
+    pub protocol_state: Account<'info, ProtocolState>,
+}
+
+#[derive(Accounts)]
+pub struct SetEarlyRepayRebate<'info> {
+    pub admin: Signer<'info>,
+    #[account(mut, has_one = admin @ ZKError::Unauthorized)]
+    pub protocol_state: Account<'info, ProtocolState>,
+}
+
+#[derive(Accounts)]
+pub struct SetBorrowFreeze<'info> {
+    pub admin: Signer<'info>,
+    #[account(mut, has_one = admin @ ZKError::Unauthorized)]
+    pub protocol_state: Account<'info, ProtocolState>,
+}
+
+#[derive(Accounts)]
+pub struct SetBorrowEnabled<'info> {
+    pub admin: Signer<'info>,
+    #[account(has_one = admin @ ZKError::Unauthorized)]
+    pub protocol_state: Account<'info, ProtocolState>,
+    #[account(mut)]
+    pub lending_pool: Account<'info, LendingPool>,
+}
+
+#[derive(Accounts)]
+pub struct SetMinLiquidityBuffer<'info> {
+    pub admin: Signer<'info>,
+    #[account(has_one = admin @ ZKError::Unauthorized)]
+    pub protocol_state: Account<'info, ProtocolState>,
+    #[account(mut)]
+    pub lending_pool: Account<'info, LendingPool>,
+}
+
+#[derive(Accounts)]
+pub struct SetBorrowMint<'info> {
+    pub admin: Signer<'info>,
+    #[account(has_one = admin @ ZKError::Unauthorized)]
+    pub protocol_state: Account<'info, ProtocolState>,
+    #[account(mut)]
+    pub lending_pool: Account<'info, LendingPool>,
+}
+
+#[derive(Accounts)]
+pub struct DeployTreasuryLiquidity<'info> {
+    pub admin: Signer<'info>,
+    #[account(mut, has_one = admin @ ZKError::Unauthorized)]
+    pub protocol_state: Account<'info, ProtocolState>,
+    #[account(mut)]
+    pub proposal: Account<'info, Governance>,
+    #[account(mut)]
+    pub protocol_treasury: Account<'info, ProtocolTreasury>,
+    #[account(mut)]
+    pub lending_pool: Account<'info, LendingPool>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimProtocolYield<'info> {
+    pub admin: Signer<'info>,
+    #[account(has_one = admin @ ZKError::Unauthorized)]
+    pub protocol_state: Account<'info, ProtocolState>,
+    #[account(mut)]
+    pub protocol_treasury: Account<'info, ProtocolTreasury>,
+    #[account(mut)]
+    pub lending_pool: Account<'info, LendingPool>,
+}
+
+#[derive(Accounts)]
+pub struct FundRewardBoost<'info> {
+    pub admin: Signer<'info>,
+    #[account(has_one = admin @ ZKError::Unauthorized)]
+    pub protocol_state: Account<'info, ProtocolState>,
+    #[account(mut)]
+    pub protocol_treasury: Account<'info, ProtocolTreasury>,
+    #[account(mut)]
+    pub lending_pool: Account<'info, LendingPool>,
+}
+
+#[derive(Accounts)]
+pub struct FundInsurance<'info> {
+    pub admin: Signer<'info>,
+    #[account(has_one = admin @ ZKError::Unauthorized)]
+    pub protocol_state: Account<'info, ProtocolState>,
+    #[account(mut)]
+    pub protocol_treasury: Account<'info, ProtocolTreasury>,
+}
+
+#[derive(Accounts)]
+pub struct FundLiquidatorRebates<'info> {
+    pub admin: Signer<'info>,
+    #[account(has_one = admin @ ZKError::Unauthorized)]
+    pub protocol_state: Account<'info, ProtocolState>,
+    #[account(mut)]
+    pub protocol_treasury: Account<'info, ProtocolTreasury>,
+}
+
+#[derive(Accounts)]
+pub struct SetRateCurve<'info> {
+    pub admin: Signer<'info>,
+    #[account(mut, has_one = admin @ ZKError::Unauthorized)]
+    pub protocol_state: Account<'info, ProtocolState>,
+}
+
+#[derive(Accounts)]
+pub struct SetLockTiers<'info> {
+    pub admin: Signer<'info>,
+    #[account(mut, has_one = admin @ ZKError::Unauthorized)]
+    pub protocol_state: Account<'info, ProtocolState>,
+}
+
+#[derive(Accounts)]
+pub struct SetGraceTiers<'info> {
+    pub admin: Signer<'info>,
+    #[account(mut, has_one = admin @ ZKError::Unauthorized)]
+    pub protocol_state: Account<'info, ProtocolState>,
+}
+
+#[derive(Accounts)]
+pub struct SetFeeTiers<'info> {
+    pub admin: Signer<'info>,
+    #[account(mut, has_one = admin @ ZKError::Unauthorized)]
+    pub protocol_state: Account<'info, ProtocolState>,
+}
+
+#[derive(Accounts)]
+pub struct SetCustodianKey<'info> {
+    pub admin: Signer<'info>,
+    #[account(has_one = admin @ ZKError::Unauthorized)]
+    pub protocol_state: Account<'info, ProtocolState>,
+    #[account(mut)]
+    pub collateral_pool: Account<'info, CollateralPool>,
+}
+
+#[derive(Accounts)]
+pub struct SetCollateralFactorDecay<'info> {
+    pub admin: Signer<'info>,
+    #[account(has_one = admin @ ZKError::Unauthorized)]
+    pub protocol_state: Account<'info, ProtocolState>,
+    #[account(mut)]
+    pub collateral_pool: Account<'info, CollateralPool>,
+}
+
+#[derive(Accounts)]
+pub struct SetMinStake<'info> {
+    pub admin: Signer<'info>,
+    #[account(has_one = admin @ ZKError::Unauthorized)]
+    pub protocol_state: Account<'info, ProtocolState>,
+    #[account(mut)]
+    pub collateral_pool: Account<'info, CollateralPool>,
+}
+
+#[derive(Accounts)]
+pub struct SetCollateralOracle<'info> {
+    pub admin: Signer<'info>,
+    #[account(has_one = admin @ ZKError::Unauthorized)]
+    pub protocol_state: Account<'info, ProtocolState>,
+    #[account(mut)]
+    pub collateral_pool: Account<'info, CollateralPool>,
+}
+
+#[derive(Accounts)]
+pub struct SetLiquidationBonus<'info> {
+    pub admin: Signer<'info>,
+    #[account(has_one = admin @ ZKError::Unauthorized)]
+    pub protocol_state: Account<'info, ProtocolState>,
+    #[account(mut)]
+    pub collateral_pool: Account<'info, CollateralPool>,
+}
+
+#[derive(Accounts)]
+pub struct SetCollateralIndex<'info> {
+    pub admin: Signer<'info>,
+    #[account(has_one = admin @ ZKError::Unauthorized)]
+    pub protocol_state: Account<'info, ProtocolState>,
+    #[account(mut)]
+    pub collateral_pool: Account<'info, CollateralPool>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateCollateralIndex<'info> {
+    pub index_account: Signer<'info>,
+    #[account(mut, has_one = index_account @ ZKError::Unauthorized)]
+    pub collateral_pool: Account<'info, CollateralPool>,
+}
+
+#[derive(Accounts)]
+pub struct StakeCollateral<'info> {
+    #[account(mut)]
+    pub borrower: Signer<'info>,
+    #[account(
+        mut,
+        constraint = borrower_account.owner == borrower.key() @ ZKError::UnauthorizedBorrower
+    )]
+    pub borrower_account: Account<'info, BorrowerAccount>,
+    #[account(mut)]
+    pub collateral_pool: Account<'info, CollateralPool>,
+    #[account(
+        mut,
+        constraint = user_collateral_account.mint == collateral_pool.asset_mint @ ZKError::CollateralMintMismatch
+    )]
+    pub user_collateral_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = collateral_pool_token_account.mint == collateral_pool.asset_mint @ ZKError::CollateralMintMismatch
+    )]
+    pub collateral_pool_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub protocol_state: Account<'info, ProtocolState>,
+    #[account(seeds = [b"verifying_keys"], bump)]
+    pub verifying_key_registry: Account<'info, VerifyingKeyRegistry>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(beneficiary: Pubkey)]
+pub struct StakeCollateralFor<'info> {
+    #[account(mut)]
+    pub cosigner: Signer<'info>,
+    #[account(
+        mut,
+        constraint = borrower_account.owner == beneficiary @ ZKError::UnauthorizedBorrower
+    )]
+    pub borrower_account: Account<'info, BorrowerAccount>,
+    #[account(
+        init_if_needed,
+        payer = cosigner,
+        space = 8 + 32 + 32 + 8,
+        seeds = [b"cosigner_stake", borrower_account.key().as_ref(), cosigner.key().as_ref()],
+        bump
+    )]
+    pub cosigner_stake: Account<'info, CosignerStake>,
+    #[account(mut)]
+    pub collateral_pool: Account<'info, CollateralPool>,
+    #[account(
+        mut,
+        constraint = cosigner_collateral_account.mint == collateral_pool.asset_mint @ ZKError::CollateralMintMismatch
+    )]
+    pub cosigner_collateral_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = collateral_pool_token_account.mint == collateral_pool.asset_mint @ ZKError::CollateralMintMismatch,
+        constraint = collateral_pool_token_account.owner == collateral_pool_authority(&collateral_pool.key())
+            @ ZKError::InvalidPoolEscrow
+    )]
+    pub collateral_pool_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub protocol_state: Account<'info, ProtocolState>,
+    #[account(seeds = [b"verifying_keys"], bump)]
+    pub verifying_key_registry: Account<'info, VerifyingKeyRegistry>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawCosignedCollateral<'info> {
+    pub cosigner: Signer<'info>,
+    #[account(mut)]
+    pub borrower_account: Account<'info, BorrowerAccount>,
+    #[account(
+        mut,
+        seeds = [b"cosigner_stake", borrower_account.key().as_ref(), cosigner.key().as_ref()],
+        bump
+    )]
+    pub cosigner_stake: Account<'info, CosignerStake>,
+    #[account(mut)]
+    pub collateral_pool: Account<'info, CollateralPool>,
+    /// CHECK: PDA derived authority.
+    pub collateral_pool_authority: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = collateral_pool_token_account.mint == collateral_pool.asset_mint @ ZKError::CollateralMintMismatch
+    )]
+    pub collateral_pool_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = cosigner_collateral_account.mint == collateral_pool.asset_mint @ ZKError::CollateralMintMismatch,
+        constraint = cosigner_collateral_account.owner == cosigner.key() @ ZKError::Unauthorized
+    )]
+    pub cosigner_collateral_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub protocol_state: Account<'info, ProtocolState>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct FundProtection<'info> {
+    pub borrower: Signer<'info>,
+    #[account(
+        mut,
+        constraint = borrower_account.owner == borrower.key() @ ZKError::UnauthorizedBorrower
+    )]
+    pub borrower_account: Account<'info, BorrowerAccount>,
+    #[account(mut)]
+    pub collateral_pool: Account<'info, CollateralPool>,
+    #[account(
+        mut,
+        constraint = user_collateral_account.mint == collateral_pool.asset_mint @ ZKError::CollateralMintMismatch
+    )]
+    pub user_collateral_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = collateral_pool_token_account.mint == collateral_pool.asset_mint @ ZKError::CollateralMintMismatch
+    )]
+    pub collateral_pool_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub protocol_state: Account<'info, ProtocolState>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct LockCollateral<'info> {
+    pub borrower: Signer<'info>,
+    #[account(
+        mut,
+        constraint = borrower_account.owner == borrower.key() @ ZKError::UnauthorizedBorrower
+    )]
+    pub borrower_account: Account<'info, BorrowerAccount>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawCollateral<'info> {
+    pub borrower: Signer<'info>,
+    #[account(
+        mut,
+        constraint = borrower_account.owner == borrower.key() @ ZKError::UnauthorizedBorrower
+    )]
+    pub borrower_account: Account<'info, BorrowerAccount>,
+    #[account(mut)]
+    pub collateral_pool: Account<'info, CollateralPool>,
+    /// CHECK: PDA derived authority.
+    pub collateral_pool_authority: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = collateral_pool_token_account.mint == collateral_pool.asset_mint @ ZKError::CollateralMintMismatch
+    )]
+    pub collateral_pool_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = user_collateral_account.mint == collateral_pool.asset_mint @ ZKError::CollateralMintMismatch
+    )]
+    pub user_collateral_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = treasury_collateral_account.mint == collateral_pool.asset_mint @ ZKError::CollateralMintMismatch,
+        constraint = treasury_collateral_account.owner == protocol_treasury.key() @ ZKError::Unauthorized
+    )]
+    pub treasury_collateral_account: Account<'info, TokenAccount>,
+    pub protocol_treasury: Account<'info, ProtocolTreasury>,
+    #[account(mut)]
+    pub protocol_state: Account<'info, ProtocolState>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct BatchStake<'info> {
+    #[account(mut)]
+    pub borrower: Signer<'info>,
+    #[account(
+        mut,
+        constraint = borrower_account.owner == borrower.key() @ ZKError::UnauthorizedBorrower
+    )]
+    pub borrower_account: Account<'info, BorrowerAccount>,
+    #[account(mut)]
+    pub protocol_state: Account<'info, ProtocolState>,
+    #[account(seeds = [b"verifying_keys"], bump)]
+    pub verifying_key_registry: Account<'info, VerifyingKeyRegistry>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct StakeCollateralWithAttestation<'info> {
+    #[account(mut)]
+    pub borrower: Signer<'info>,
+    #[account(
+        mut,
+        constraint = borrower_account.owner == borrower.key() @ ZKError::UnauthorizedBorrower
+    )]
+    pub borrower_account: Account<'info, BorrowerAccount>,
+    #[account(mut)]
+    pub collateral_pool: Account<'info, CollateralPool>,
+    #[account(
+        mut,
+        constraint = user_collateral_account.mint == collateral_pool.asset_mint @ ZKError::CollateralMintMismatch
+    )]
+    pub user_collateral_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = collateral_pool_token_account.mint == collateral_pool.asset_mint @ ZKError::CollateralMintMismatch
+    )]
+    pub collateral_pool_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub protocol_state: Account<'info, ProtocolState>,
+    /// CHECK: Instructions sysvar, checked by address.
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(seeds = [b"verifying_keys"], bump)]
+    pub verifying_key_registry: Account<'info, VerifyingKeyRegistry>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Borrow<'info> {
+    #[account(mut)]
+    pub borrower: Signer<'info>,
+    #[account(
+        mut,
+        constraint = borrower_account.owner == borrower.key() @ ZKError::UnauthorizedBorrower
+    )]
+    pub borrower_account: Account<'info, BorrowerAccount>,
+    #[account(
+        init,
+        payer = borrower,
+        space = 8 + 32 + 8 + 8 + 1 + 1 + 8 + 8 + 1 + 8 + 16 + 8 + 8 + 32 + 8 + 1 + 1,
+        seeds = [
+            b"loan",
+            borrower_account.key().as_ref(),
+            &borrower_account.next_loan_id.to_le_bytes()
+        ],
+        bump
+    )]
+    pub loan_position: Account<'info, LoanPosition>,
+    #[account(mut)]
+    pub lending_pool: Account<'info, LendingPool>,
+    /// CHECK: PDA derived authority.
+    pub lending_pool_authority: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = lending_pool_token_account.mint == lending_pool.borrow_mint @ ZKError::BorrowMintMismatch
+    )]
+    pub lending_pool_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = user_borrow_token_account.mint == lending_pool.borrow_mint @ ZKError::BorrowMintMismatch
+    )]
+    pub user_borrow_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub protocol_state: Account<'info, ProtocolState>,
+    pub collateral_pool: Account<'info, CollateralPool>,
+    /// CHECK: Price oracle, checked against the collateral pool's configured oracle.
+    pub price_oracle: Option<AccountInfo<'info>>,
+    #[account(mut)]
+    pub protocol_treasury: Account<'info, ProtocolTreasury>,
+    #[account(mut, seeds = [b"borrow_rate_limit"], bump)]
+    pub borrow_rate_limit: Account<'info, BorrowRateLimit>,
+    pub borrow_whitelist: Option<Account<'info, BorrowWhitelist>>,
+    #[account(seeds = [b"verifying_keys"], bump)]
+    pub verifying_key_registry: Account<'info, VerifyingKeyRegistry>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    #[account(
+        constraint = borrower_reputation.borrower == borrower_account.owner @ ZKError::UnauthorizedBorrower
+    )]
+    pub borrower_reputation: Option<Account<'info, BorrowerReputation>>,
+}
+
+#[derive(Accounts)]
+pub struct LoopBorrow<'info> {
+    #[account(mut)]
+    pub borrower: Signer<'info>,
+    #[account(
+        mut,
+        constraint = borrower_account.owner == borrower.key() @ ZKError::UnauthorizedBorrower
+    )]
+    pub borrower_account: Account<'info, BorrowerAccount>,
+    #[account(
+        init,
+        payer = borrower,
+        space = 8 + 32 + 8 + 8 + 1 + 1 + 8 + 8 + 1 + 8 + 16 + 8 + 8 + 32 + 8 + 1 + 1,
+        seeds = [
+            b"loan",
+            borrower_account.key().as_ref(),
+            &borrower_account.next_loan_id.to_le_bytes()
+        ],
+        bump
+    )]
+    pub loan_position: Account<'info, LoanPosition>,
+    #[account(mut)]
+    pub lending_pool: Account<'info, LendingPool>,
+    /// CHECK: PDA derived authority.
+    pub lending_pool_authority: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = lending_pool_token_account.mint == lending_pool.borrow_mint @ ZKError::BorrowMintMismatch
+    )]
+    pub lending_pool_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = collateral_pool.asset_mint == lending_pool.borrow_mint @ ZKError::CollateralMintMismatch
+    )]
+    pub collateral_pool: Account<'info, CollateralPool>,
+    #[account(
+        mut,
+        constraint = collateral_pool_token_account.mint == collateral_pool.asset_mint @ ZKError::CollateralMintMismatch
+    )]
+    pub collateral_pool_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub protocol_state: Account<'info, ProtocolState>,
+    /// CHECK: Price oracle, checked against the collateral pool's configured oracle.
+    pub price_oracle: Option<AccountInfo<'info>>,
+    #[account(mut)]
+    pub protocol_treasury: Account<'info, ProtocolTreasury>,
+    #[account(mut, seeds = [b"borrow_rate_limit"], bump)]
+    pub borrow_rate_limit: Account<'info, BorrowRateLimit>,
+    pub borrow_whitelist: Option<Account<'info, BorrowWhitelist>>,
+    #[account(seeds = [b"verifying_keys"], bump)]
+    pub verifying_key_registry: Account<'info, VerifyingKeyRegistry>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    #[account(
+        constraint = borrower_reputation.borrower == borrower_account.owner @ ZKError::UnauthorizedBorrower
+    )]
+    pub borrower_reputation: Option<Account<'info, BorrowerReputation>>,
+}
+
+#[derive(Accounts)]
+pub struct SimulateBorrow<'info> {
+    pub borrower_account: Account<'info, BorrowerAccount>,
+    pub protocol_state: Account<'info, ProtocolState>,
+    pub lending_pool: Account<'info, LendingPool>,
+    pub collateral_pool: Account<'info, CollateralPool>,
+    /// CHECK: Price oracle, checked against the collateral pool's configured oracle.
+    pub price_oracle: Option<AccountInfo<'info>>,
+    #[account(seeds = [b"borrow_rate_limit"], bump)]
+    pub borrow_rate_limit: Account<'info, BorrowRateLimit>,
+    pub borrow_whitelist: Option<Account<'info, BorrowWhitelist>>,
+    #[account(
+        constraint = borrower_reputation.borrower == borrower_account.owner @ ZKError::UnauthorizedBorrower
+    )]
+    pub borrower_reputation: Option<Account<'info, BorrowerReputation>>,
+}
+
+#[derive(Accounts)]
+pub struct InstitutionalBorrow<'info> {
+    #[account(mut)]
+    pub borrower: Signer<'info>,
+    #[account(
+        mut,
+        constraint = borrower_account.owner == borrower.key() @ ZKError::UnauthorizedBorrower
+    )]
+    pub borrower_account: Account<'info, BorrowerAccount>,
+    #[account(
+        init,
+        payer = borrower,
+        space = 8 + 32 + 8 + 8 + 1 + 1 + 8 + 8 + 1 + 8 + 16 + 8 + 8 + 32 + 8 + 1 + 1,
+        seeds = [
+            b"loan",
+            borrower_account.key().as_ref(),
+            &borrower_account.next_loan_id.to_le_bytes()
+        ],
+        bump
+    )]
+    pub loan_position: Account<'info, LoanPosition>,
+    #[account(mut)]
+    pub lending_pool: Account<'info, LendingPool>,
+    /// CHECK: PDA derived authority.
+    pub lending_pool_authority: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = lending_pool_token_account.mint == lending_pool.borrow_mint @ ZKError::BorrowMintMismatch
+    )]
+    pub lending_pool_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = user_borrow_token_account.mint == lending_pool.borrow_mint @ ZKError::BorrowMintMismatch
+    )]
+    pub user_borrow_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub protocol_state: Account<'info, ProtocolState>,
+    pub collateral_pool: Account<'info, CollateralPool>,
+    /// CHECK: Price oracle, checked against the collateral pool's configured oracle.
+    pub price_oracle: Option<AccountInfo<'info>>,
+    #[account(mut)]
+    pub protocol_treasury: Account<'info, ProtocolTreasury>,
+    #[account(mut, seeds = [b"borrow_rate_limit"], bump)]
+    pub borrow_rate_limit: Account<'info, BorrowRateLimit>,
+    #[account(mut)]
+    pub institutional_pool: Account<'info, InstitutionalLendingPool>,
+    #[account(mut)]
+    pub sub_account: Option<Account<'info, SubAccount>>,
+    #[account(seeds = [b"verifying_keys"], bump)]
+    pub verifying_key_registry: Account<'info, VerifyingKeyRegistry>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(member: Pubkey)]
+pub struct SetSubAccount<'info> {
+    #[account(mut)]
+    pub parent: Signer<'info>,
+    pub institutional_pool: Account<'info, InstitutionalLendingPool>,
+    #[account(
+        init_if_needed,
+        payer = parent,
+        space = 8 + 32 + 32 + 32 + 8 + 8 + 8,
+        seeds = [
+            b"sub_account",
+            institutional_pool.key().as_ref(),
+            parent.key().as_ref(),
+            member.as_ref()
+        ],
+        bump
+    )]
+    pub sub_account: Account<'info, SubAccount>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeSubAccount<'info> {
+    #[account(mut)]
+    pub parent: Signer<'info>,
+    #[account(mut, close = parent, has_one = parent @ ZKError::Unauthorized)]
+    pub sub_account: Account<'info, SubAccount>,
+}
+
+#[derive(Accounts)]
+#[instruction(delegate: Pubkey)]
+pub struct CreateDelegation<'info> {
+    #[account(mut)]
+    pub delegator: Signer<'info>,
+    #[account(
+        init,
+        payer = delegator,
+        space = 8 + 32 + 32 + 8 + 8,
+        seeds = [b"delegation", delegator.key().as_ref(), delegate.as_ref()],
+        bump
+    )]
+    pub delegated_borrower: Account<'info, DelegatedBorrower>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct DelegatedBorrow<'info> {
+    #[account(mut)]
+    pub borrower: Signer<'info>,
+    #[account(
+        mut,
+        constraint = borrower_account.owner == delegated_borrower.delegator @ ZKError::UnauthorizedBorrower
+    )]
+    pub borrower_account: Account<'info, BorrowerAccount>,
+    #[account(
+        init,
+        payer = borrower,
+        space = 8 + 32 + 8 + 8 + 1 + 1 + 8 + 8 + 1 + 8 + 16 + 8 + 8 + 32 + 8 + 1 + 1,
+        seeds = [
+            b"loan",
+            borrower_account.key().as_ref(),
+            &borrower_account.next_loan_id.to_le_bytes()
+        ],
+        bump
+    )]
+    pub loan_position: Account<'info, LoanPosition>,
+    #[account(mut)]
+    pub lending_pool: Account<'info, LendingPool>,
+    /// CHECK: PDA derived authority.
+    pub lending_pool_authority: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = lending_pool_token_account.mint == lending_pool.borrow_mint @ ZKError::BorrowMintMismatch
+    )]
+    pub lending_pool_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = user_borrow_token_account.mint == lending_pool.borrow_mint @ ZKError::BorrowMintMismatch
+    )]
+    pub user_borrow_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub protocol_state: Account<'info, ProtocolState>,
+    pub collateral_pool: Account<'info, CollateralPool>,
+    /// CHECK: Price oracle, checked against the collateral pool's configured oracle.
+    pub price_oracle: Option<AccountInfo<'info>>,
+    #[account(mut)]
+    pub protocol_treasury: Account<'info, ProtocolTreasury>,
+    #[account(mut, seeds = [b"borrow_rate_limit"], bump)]
+    pub borrow_rate_limit: Account<'info, BorrowRateLimit>,
+    #[account(mut)]
+    pub delegated_borrower: Account<'info, DelegatedBorrower>,
+    #[account(seeds = [b"verifying_keys"], bump)]
+    pub verifying_key_registry: Account<'info, VerifyingKeyRegistry>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct DelegatedBorrowWithSignature<'info> {
+    #[account(mut)]
+    pub borrower: Signer<'info>,
+    #[account(
+        mut,
+        constraint = borrower_account.owner == delegator.key() @ ZKError::UnauthorizedBorrower
+    )]
+    pub borrower_account: Account<'info, BorrowerAccount>,
+    #[account(
+        init,
+        payer = borrower,
+        space = 8 + 32 + 8 + 8 + 1 + 1 + 8 + 8 + 1 + 8 + 16 + 8 + 8 + 32 + 8 + 1 + 1,
+        seeds = [
+            b"loan",
+            borrower_account.key().as_ref(),
+            &borrower_account.next_loan_id.to_le_bytes()
+        ],
+        bump
+    )]
+    pub loan_position: Account<'info, LoanPosition>,
+    #[account(mut)]
+    pub lending_pool: Account<'info, LendingPool>,
+    /// CHECK: PDA derived authority.
+    pub lending_pool_authority: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = lending_pool_token_account.mint == lending_pool.borrow_mint @ ZKError::BorrowMintMismatch
+    )]
+    pub lending_pool_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = user_borrow_token_account.mint == lending_pool.borrow_mint @ ZKError::BorrowMintMismatch
+    )]
+    pub user_borrow_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub protocol_state: Account<'info, ProtocolState>,
+    pub collateral_pool: Account<'info, CollateralPool>,
+    /// CHECK: Price oracle, checked against the collateral pool's configured oracle.
+    pub price_oracle: Option<AccountInfo<'info>>,
+    #[account(mut)]
+    pub protocol_treasury: Account<'info, ProtocolTreasury>,
+    #[account(mut, seeds = [b"borrow_rate_limit"], bump)]
+    pub borrow_rate_limit: Account<'info, BorrowRateLimit>,
+    /// CHECK: Only used as the expected signer of the delegation message.
+    pub delegator: AccountInfo<'info>,
+    #[account(
+        init_if_needed,
+        payer = borrower,
+        space = 8 + 8,
+        seeds = [b"delegation_nonce", delegator.key().as_ref(), borrower.key().as_ref()],
+        bump
+    )]
+    pub delegation_nonce: Account<'info, DelegationNonce>,
+    /// CHECK: Instructions sysvar, checked by address.
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions_sysvar: AccountInfo<'info>,
+    #[account(seeds = [b"verifying_keys"], bump)]
+    pub verifying_key_registry: Account<'info, VerifyingKeyRegistry>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(loan_id: u64)]
+pub struct Repay<'info> {
+    #[account(mut)]
+    pub borrower: Signer<'info>,
+    #[account(
+        mut,
+        constraint = borrower_account.owner == borrower.key() @ ZKError::UnauthorizedBorrower
+    )]
+    pub borrower_account: Account<'info, BorrowerAccount>,
+    #[account(
+        mut,
+        close = borrower,
+        has_one = borrower_account,
+        seeds = [b"loan", borrower_account.key().as_ref(), &loan_id.to_le_bytes()],
+        bump
+    )]
+    pub loan_position: Account<'info, LoanPosition>,
+    #[account(mut)]
+    pub lending_pool: Account<'info, LendingPool>,
+    /// CHECK: PDA derived authority.
+    pub lending_pool_authority: AccountInfo<'info>,
+    #[account(mut)]
+    pub lending_pool_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub user_borrow_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub protocol_state: Account<'info, ProtocolState>,
+    #[account(mut)]
+    pub protocol_treasury: Account<'info, ProtocolTreasury>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(loan_id: u64)]
+pub struct RepayOnBehalf<'info> {
+    pub payer: Signer<'info>,
+    /// CHECK: Owner of the borrower account; receives the closed loan position's rent.
+    #[account(mut, address = borrower_account.owner @ ZKError::UnauthorizedBorrower)]
+    pub borrower: AccountInfo<'info>,
+    #[account(mut)]
+    pub borrower_account: Account<'info, BorrowerAccount>,
+    #[account(
+        mut,
+        close = borrower,
+        has_one = borrower_account,
+        seeds = [b"loan", borrower_account.key().as_ref(), &loan_id.to_le_bytes()],
+        bump
+    )]
+    pub loan_position: Account<'info, LoanPosition>,
+    #[account(mut)]
+    pub delegated_borrower: Option<Account<'info, DelegatedBorrower>>,
+    #[account(mut)]
+    pub lending_pool: Account<'info, LendingPool>,
+    #[account(mut)]
+    pub lending_pool_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub payer_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub protocol_state: Account<'info, ProtocolState>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct RepayAll<'info> {
+    pub payer: Signer<'info>,
+    /// CHECK: Owner of the borrower account; receives the closed loan positions' rent.
+    #[account(mut, address = borrower_account.owner @ ZKError::UnauthorizedBorrower)]
+    pub borrower: AccountInfo<'info>,
+    #[account(mut)]
+    pub borrower_account: Account<'info, BorrowerAccount>,
+    #[account(mut)]
+    pub lending_pool: Account<'info, LendingPool>,
+    #[account(mut)]
+    pub lending_pool_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub payer_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub protocol_state: Account<'info, ProtocolState>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(loan_id: u64)]
+pub struct RepayWithSwap<'info> {
+    #[account(mut)]
+    pub borrower: Signer<'info>,
+    #[account(
+        mut,
+        constraint = borrower_account.owner == borrower.key() @ ZKError::UnauthorizedBorrower
+    )]
+    pub borrower_account: Account<'info, BorrowerAccount>,
+    #[account(
+        mut,
+        close = borrower,
+        has_one = borrower_account,
+        seeds = [b"loan", borrower_account.key().as_ref(), &loan_id.to_le_bytes()],
+        bump
+    )]
+    pub loan_position: Account<'info, LoanPosition>,
+    #[account(mut)]
+    pub lending_pool: Account<'info, LendingPool>,
+    /// CHECK: PDA derived authority.
+    pub lending_pool_authority: AccountInfo<'info>,
+    #[account(mut)]
+    pub lending_pool_token_account: Account<'info, TokenAccount>,
+    #[account(mut, constraint = user_input_token_account.owner == borrower.key())]
+    pub user_input_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = user_borrow_token_account.owner == borrower.key(),
+        constraint = user_borrow_token_account.mint == lending_pool_token_account.mint
+    )]
+    pub user_borrow_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub protocol_state: Account<'info, ProtocolState>,
+    #[account(mut)]
+    pub protocol_treasury: Account<'info, ProtocolTreasury>,
+    /// CHECK: DEX program invoked through the generic swap interface.
+    #[account(executable)]
+    pub swap_program: AccountInfo<'info>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct FlashBorrow<'info> {
+    #[account(mut)]
+    pub borrower: Signer<'info>,
+    pub lending_pool: Account<'info, LendingPool>,
+    /// CHECK: PDA derived authority.
+    pub lending_pool_authority: AccountInfo<'info>,
+    #[account(mut)]
+    pub lending_pool_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub user_token_account: Account<'info, TokenAccount>,
+    #[account(
+        init_if_needed,
+        payer = borrower,
+        space = 8 + 32 + 8 + 8,
+        seeds = [b"flash_loan", lending_pool.key().as_ref()],
+        bump
+    )]
+    pub flash_loan: Account<'info, FlashLoan>,
+    /// CHECK: Instructions sysvar, checked by address.
+    #[account(address = sysvar::instructions::ID)]
+    pub instructions_sysvar: AccountInfo<'info>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct FlashRepay<'info> {
+    pub borrower: Signer<'info>,
+    pub lending_pool: Account<'info, LendingPool>,
+    #[account(
+        mut,
+        seeds = [b"flash_loan", lending_pool.key().as_ref()],
+        bump,
+        constraint = flash_loan.borrower == borrower.key() @ ZKError::UnauthorizedBorrower
+    )]
+    pub flash_loan: Account<'info, FlashLoan>,
+    #[account(mut)]
+    pub lending_pool_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub user_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub protocol_treasury: Account<'info, ProtocolTreasury>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct LiquidationPrice<'info> {
+    pub borrower_account: Account<'info, BorrowerAccount>,
+    pub collateral_pool: Account<'info, CollateralPool>,
+    pub protocol_state: Account<'info, ProtocolState>,
+}
+
+#[derive(Accounts)]
+pub struct PositionSummary<'info> {
+    pub borrower_account: Account<'info, BorrowerAccount>,
+    pub collateral_pool: Account<'info, CollateralPool>,
+    pub protocol_state: Account<'info, ProtocolState>,
+    /// CHECK: Price oracle, checked against the collateral pool's configured oracle.
+    pub price_oracle: Option<AccountInfo<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct MarkLiquidatable<'info> {
+    pub liquidator: Signer<'info>,
+    #[account(mut)]
+    pub borrower_account: Account<'info, BorrowerAccount>,
+    pub collateral_pool: Account<'info, CollateralPool>,
+    pub protocol_state: Account<'info, ProtocolState>,
+    /// CHECK: Price oracle, checked against the collateral pool's configured oracle.
+    pub price_oracle: Option<AccountInfo<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteLiquidation<'info> {
+    #[account(mut)]
+    pub liquidator: Signer<'info>,
+    #[account(
+        mut,
+        constraint = borrower_account.owner != liquidator.key() @ ZKError::SelfLiquidationNotAllowed
+    )]
+    pub borrower_account: Account<'info, BorrowerAccount>,
+    #[account(mut)]
+    pub collateral_pool: Account<'info, CollateralPool>,
+    /// CHECK: PDA derived authority.
+    pub collateral_pool_authority: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = collateral_pool_token_account.mint == collateral_pool.asset_mint @ ZKError::CollateralMintMismatch
+    )]
+    pub collateral_pool_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = liquidator_collateral_account.mint == collateral_pool.asset_mint @ ZKError::CollateralMintMismatch
+    )]
+    pub liquidator_collateral_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = treasury_collateral_account.mint == collateral_pool.asset_mint @ ZKError::CollateralMintMismatch,
+        constraint = treasury_collateral_account.owner == protocol_treasury.key() @ ZKError::Unauthorized
+    )]
+    pub treasury_collateral_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub protocol_treasury: Account<'info, ProtocolTreasury>,
+    #[account(mut)]
+    pub protocol_state: Account<'info, ProtocolState>,
+    /// CHECK: Price oracle, checked against the collateral pool's configured oracle.
+    pub price_oracle: Option<AccountInfo<'info>>,
+    #[account(seeds = [b"verifying_keys"], bump)]
+    pub verifying_key_registry: Account<'info, VerifyingKeyRegistry>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    /// CHECK: PDA derived authority.
+    pub lending_pool_authority: Option<AccountInfo<'info>>,
+    pub lending_pool: Account<'info, LendingPool>,
+    #[account(
+        mut,
+        constraint = lending_pool_token_account.mint == lending_pool.borrow_mint @ ZKError::BorrowMintMismatch,
+        constraint = lending_pool_token_account.owner == lending_pool.pool_authority @ ZKError::InvalidPoolEscrow
+    )]
+    pub lending_pool_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = liquidator_repay_account.mint == lending_pool_token_account.mint @ ZKError::BorrowMintMismatch
+    )]
+    pub liquidator_repay_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub liquidator_rebate_account: Option<Account<'info, TokenAccount>>,
+    #[account(
+        constraint = liquidation_delegate.keeper == liquidator.key() @ ZKError::Unauthorized,
+        constraint = liquidation_delegate.principal != borrower_account.owner @ ZKError::SelfLiquidationNotAllowed
+    )]
+    pub liquidation_delegate: Option<Account<'info, LiquidationDelegate>>,
+    #[account(
+        mut,
+        constraint = beneficiary_collateral_account.mint == collateral_pool.asset_mint @ ZKError::CollateralMintMismatch
+    )]
+    pub beneficiary_collateral_account: Option<Account<'info, TokenAccount>>,
 }
 
-// ─────────────────────────────────────────────────────────────
-// Data Structures & Accounts
-// ─────────────────────────────────────────────────────────────
+#[derive(Accounts)]
+#[instruction(keeper: Pubkey)]
+pub struct AuthorizeLiquidationDelegate<'info> {
+    #[account(mut)]
+    pub principal: Signer<'info>,
+    #[account(
+        init_if_needed,
+        payer = principal,
+        space = 8 + 32 + 32 + 32,
+        seeds = [b"liquidation_delegate", principal.key().as_ref(), keeper.as_ref()],
+        bump
+    )]
+    pub liquidation_delegate: Account<'info, LiquidationDelegate>,
+    pub system_program: Program<'info, System>,
+}
 
-/// Represents an encrypted amount (placeholder for real ZK encryption).
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default)]
-pub struct EncryptedAmount {
-    pub value: u64,
+#[derive(Accounts)]
+pub struct RevokeLiquidationDelegate<'info> {
+    #[account(mut)]
+    pub principal: Signer<'info>,
+    #[account(mut, close = principal, has_one = principal @ ZKError::Unauthorized)]
+    pub liquidation_delegate: Account<'info, LiquidationDelegate>,
 }
 
-/// Global protocol state.
-#[account]
-pub struct ProtocolState {
-    pub total_collateral: u64,
-    pub total_loans: u64,
-    pub total_liquidity: u64,
-    pub base_interest_rate: u8,
-    pub utilization_rate: u8,
-    pub min_collateral_lock_time: i64,
+#[derive(Accounts)]
+pub struct ProposeChange<'info> {
+    #[account(mut)]
+    pub proposer: Signer<'info>,
+    #[account(
+        init,
+        payer = proposer,
+        space = 8 + 8 + 1 + 8 + 8 + 1 + 32 + 2 + 2 + 32 + 32 + 8 + 8 + 32
+    )]
+    pub governance: Account<'info, Governance>,
+    pub institutional_pool: Account<'info, InstitutionalLendingPool>,
+    pub protocol_state: Account<'info, ProtocolState>,
+    pub system_program: Program<'info, System>,
 }
 
-/// Lending pool state.
-#[account]
-pub struct LendingPool {
-    pub pool_authority: Pubkey,
-    pub total_liquidity: u64,
-    pub base_interest_rate: u8,
-    pub utilization_rate: u8,
-    pub lender_rewards: u64,
+#[derive(Accounts)]
+pub struct CloseProposal<'info> {
+    #[account(
+        mut,
+        close = proposer,
+        has_one = proposer @ ZKError::Unauthorized
+    )]
+    pub governance: Account<'info, Governance>,
+    /// CHECK: Rent recipient, checked against the proposal's proposer.
+    #[account(mut)]
+    pub proposer: AccountInfo<'info>,
+    #[account(mut, seeds = [b"protocol_treasury"], bump)]
+    pub protocol_treasury: Account<'info, ProtocolTreasury>,
 }
 
-/// Multi-collateral pool state.
-#[account]
-pub struct CollateralPool {
-    pub asset_mint: Pubkey,
-    pub total_collateral: u64,
+#[derive(Accounts)]
+pub struct ProposalStatus<'info> {
+    pub governance: Account<'info, Governance>,
 }
 
-/// Institutional lending pool state.
-#[account]
-pub struct InstitutionalLendingPool {
-    pub pool_owner: Pubkey,
-    pub total_liquidity: u64,
-    pub fixed_interest_rate: u8,
-    pub zk_whitelist: Vec<Pubkey>,
+#[derive(Accounts)]
+pub struct ExecuteProposal<'info> {
+    #[account(mut)]
+    pub executor: Signer<'info>,
+    #[account(mut)]
+    pub governance: Account<'info, Governance>,
+    #[account(mut)]
+    pub protocol_state: Account<'info, ProtocolState>,
+    #[account(
+        init_if_needed,
+        payer = executor,
+        space = 8 + (8 + 1 + 8 + 8 + 8) * PARAM_CHANGE_LOG_CAPACITY + 4 + 4,
+        seeds = [b"param_change_log"],
+        bump
+    )]
+    pub param_change_log: Account<'info, ParamChangeLog>,
+    pub system_program: Program<'info, System>,
 }
 
-/// Treasury account for collecting protocol fees.
-#[account]
-pub struct ProtocolTreasury {
-    pub total_fees_collected: u64,
-    pub governance_fund: u64,
+#[derive(Accounts)]
+pub struct ProposeCollateralAsset<'info> {
+    #[account(mut)]
+    pub proposer: Signer<'info>,
+    #[account(
+        init,
+        payer = proposer,
+        space = 8 + 8 + 1 + 8 + 8 + 1 + 32 + 2 + 2 + 32 + 32 + 8 + 8 + 32
+    )]
+    pub governance: Account<'info, Governance>,
+    pub institutional_pool: Account<'info, InstitutionalLendingPool>,
+    pub protocol_state: Account<'info, ProtocolState>,
+    pub system_program: Program<'info, System>,
 }
 
-/// Borrower account storing confidential collateral and borrow amounts.
-#[account]
-pub struct BorrowerAccount {
-    pub encrypted_collateral: EncryptedAmount,
-    pub encrypted_borrowed: EncryptedAmount,
-    pub borrow_timestamp: i64,
+#[derive(Accounts)]
+pub struct ExecuteAddCollateralAsset<'info> {
+    #[account(mut)]
+    pub executor: Signer<'info>,
+    #[account(mut)]
+    pub governance: Account<'info, Governance>,
+    #[account(
+        init,
+        payer = executor,
+        space = 8 + 32 + 8 + 2 + 2 + 32 + 32 + 8 + 8 + 2 + 1 + 32 + 8 + 8 + 2 + 2,
+        seeds = [b"collateral_pool", governance.asset_mint.as_ref()],
+        bump
+    )]
+    pub collateral_pool: Account<'info, CollateralPool>,
+    #[account(
+        init_if_needed,
+        payer = executor,
+        space = 8 + 4 + (32 + 32) * MAX_LISTED_ASSETS,
+        seeds = [b"asset_registry"],
+        bump
+    )]
+    pub asset_registry: Account<'info, AssetRegistry>,
+    pub system_program: Program<'info, System>,
 }
 
-/// Borrower reputation (for a ZK-based reputation system).
-#[account]
-pub struct BorrowerReputation {
-    pub borrower: Pubkey,
-    pub zk_reputation_score: u64,
+#[derive(Accounts)]
+pub struct Vote<'info> {
+    #[account(mut)]
+    pub voter: Signer<'info>,
+    #[account(
+        mut,
+        constraint = governance.pool == institutional_pool.key() @ ZKError::WrongGovernancePool
+    )]
+    pub governance: Account<'info, Governance>,
+    #[account(mut)]
+    pub institutional_pool: Account<'info, InstitutionalLendingPool>,
+    pub system_program: Program<'info, System>,
 }
 
-/// Governance proposal.
-#[account]
-pub struct Governance {
-    pub proposal_id: u64,
-    pub proposal_type: u8,
-    pub new_value: u64,
-    pub votes: i64,
+#[derive(Accounts)]
+pub struct RebalanceCollateral<'info> {
+    #[account(mut)]
+    pub borrower: Signer<'info>,
+    #[account(
+        mut,
+        constraint = borrower_account.owner == borrower.key() @ ZKError::UnauthorizedBorrower
+    )]
+    pub borrower_account: Account<'info, BorrowerAccount>,
+    #[account(mut)]
+    pub protocol_state: Account<'info, ProtocolState>,
+    #[account(mut)]
+    pub collateral_pool: Account<'info, CollateralPool>,
+    /// CHECK: PDA derived authority.
+    pub collateral_pool_authority: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = collateral_pool_token_account.mint == collateral_pool.asset_mint @ ZKError::CollateralMintMismatch,
+        constraint = collateral_pool_token_account.owner == crate::collateral_pool_authority(&collateral_pool.key())
+            @ ZKError::InvalidPoolEscrow
+    )]
+    pub collateral_pool_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = user_collateral_account.mint == collateral_pool.asset_mint @ ZKError::CollateralMintMismatch
+    )]
+    pub user_collateral_account: Account<'info, TokenAccount>,
+    /// CHECK: Price oracle, checked against the collateral pool's configured oracle.
+    pub price_oracle: Option<AccountInfo<'info>>,
+    #[account(seeds = [b"verifying_keys"], bump)]
+    pub verifying_key_registry: Account<'info, VerifyingKeyRegistry>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
 }
 
-/// Delegated borrower: credit line assigned by a delegator.
-#[account]
-pub struct DelegatedBorrower {
-    pub delegator: Pubkey,
-    pub delegate: Pubkey,
-    pub max_borrow_amount: u64,
+#[derive(Accounts)]
+pub struct AccrueInterest<'info> {
+    pub caller: Signer<'info>,
+    #[account(mut)]
+    pub borrower_account: Account<'info, BorrowerAccount>,
+    #[account(mut, has_one = borrower_account)]
+    pub loan_position: Account<'info, LoanPosition>,
+    #[account(mut)]
+    pub protocol_state: Account<'info, ProtocolState>,
+    #[account(mut)]
+    pub protocol_treasury: Account<'info, ProtocolTreasury>,
+    /// CHECK: PDA derived authority.
+    pub lending_pool_authority: AccountInfo<'info>,
+    #[account(mut)]
+    pub lending_pool_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub caller_token_account: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
 }
 
-// ─────────────────────────────────────────────────────────────
-// Contexts
-// ─────────────────────────────────────────────────────────────
+#[derive(Accounts)]
+pub struct AccrueGlobalInterest<'info> {
+    pub caller: Signer<'info>,
+    pub protocol_state: Account<'info, ProtocolState>,
+    #[account(mut)]
+    pub lending_pool: Account<'info, LendingPool>,
+}
 
 #[derive(Accounts)]
-pub struct Initialize<'info> {
-    #[account(init, payer = user, space = 8 + 32)]
+#[instruction(loan_id: u64)]
+pub struct LockRate<'info> {
+    pub borrower: Signer<'info>,
+    #[account(
+        mut,
+        constraint = borrower_account.owner == borrower.key() @ ZKError::UnauthorizedBorrower
+    )]
+    pub borrower_account: Account<'info, BorrowerAccount>,
+    #[account(
+        mut,
+        has_one = borrower_account,
+        seeds = [b"loan", borrower_account.key().as_ref(), &loan_id.to_le_bytes()],
+        bump
+    )]
+    pub loan_position: Account<'info, LoanPosition>,
+    #[account(mut)]
+    pub lending_pool_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub user_borrow_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
     pub protocol_state: Account<'info, ProtocolState>,
-    #[account(init, payer = user, space = 8 + 16)]
+    #[account(mut)]
     pub protocol_treasury: Account<'info, ProtocolTreasury>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(loan_a_id: u64, loan_b_id: u64)]
+pub struct MergeLoans<'info> {
     #[account(mut)]
-    pub user: Signer<'info>,
+    pub borrower: Signer<'info>,
+    #[account(
+        mut,
+        constraint = borrower_account.owner == borrower.key() @ ZKError::UnauthorizedBorrower
+    )]
+    pub borrower_account: Account<'info, BorrowerAccount>,
+    #[account(
+        mut,
+        has_one = borrower_account,
+        seeds = [b"loan", borrower_account.key().as_ref(), &loan_a_id.to_le_bytes()],
+        bump
+    )]
+    pub loan_a: Account<'info, LoanPosition>,
+    #[account(
+        mut,
+        close = borrower,
+        has_one = borrower_account,
+        seeds = [b"loan", borrower_account.key().as_ref(), &loan_b_id.to_le_bytes()],
+        bump
+    )]
+    pub loan_b: Account<'info, LoanPosition>,
+    #[account(mut)]
+    pub protocol_state: Account<'info, ProtocolState>,
+}
+
+#[derive(Accounts)]
+#[instruction(loan_id: u64)]
+pub struct SplitLoan<'info> {
+    #[account(mut)]
+    pub borrower: Signer<'info>,
+    #[account(
+        mut,
+        constraint = borrower_account.owner == borrower.key() @ ZKError::UnauthorizedBorrower
+    )]
+    pub borrower_account: Account<'info, BorrowerAccount>,
+    #[account(
+        mut,
+        has_one = borrower_account,
+        seeds = [b"loan", borrower_account.key().as_ref(), &loan_id.to_le_bytes()],
+        bump
+    )]
+    pub loan_position: Account<'info, LoanPosition>,
+    #[account(
+        init,
+        payer = borrower,
+        space = 8 + 32 + 8 + 8 + 1 + 1 + 8 + 8 + 1 + 8 + 16 + 8 + 8 + 32 + 8 + 1 + 1,
+        seeds = [
+            b"loan",
+            borrower_account.key().as_ref(),
+            &borrower_account.next_loan_id.to_le_bytes()
+        ],
+        bump
+    )]
+    pub split_position: Account<'info, LoanPosition>,
+    pub protocol_state: Account<'info, ProtocolState>,
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct StakeCollateral<'info> {
+pub struct Refinance<'info> {
+    pub borrower: Signer<'info>,
+    #[account(
+        mut,
+        constraint = borrower_account.owner == borrower.key() @ ZKError::UnauthorizedBorrower
+    )]
+    pub borrower_account: Account<'info, BorrowerAccount>,
+    #[account(mut, has_one = borrower_account)]
+    pub loan_position: Account<'info, LoanPosition>,
+    #[account(mut)]
+    pub lending_pool_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub user_borrow_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub protocol_state: Account<'info, ProtocolState>,
     #[account(mut)]
+    pub protocol_treasury: Account<'info, ProtocolTreasury>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct RolloverInstitutional<'info> {
     pub borrower: Signer<'info>,
+    #[account(
+        mut,
+        constraint = borrower_account.owner == borrower.key() @ ZKError::UnauthorizedBorrower
+    )]
+    pub borrower_account: Account<'info, BorrowerAccount>,
+    #[account(mut, has_one = borrower_account)]
+    pub loan_position: Account<'info, LoanPosition>,
+    pub institutional_pool: Account<'info, InstitutionalLendingPool>,
+    #[account(mut)]
+    pub protocol_state: Account<'info, ProtocolState>,
+}
+
+#[derive(Accounts)]
+pub struct RecordMissedPayment<'info> {
+    pub keeper: Signer<'info>,
     #[account(mut)]
     pub borrower_account: Account<'info, BorrowerAccount>,
+    #[account(mut, has_one = borrower_account)]
+    pub loan_position: Account<'info, LoanPosition>,
+    #[account(
+        mut,
+        constraint = borrower_reputation.borrower == borrower_account.owner @ ZKError::UnauthorizedBorrower
+    )]
+    pub borrower_reputation: Account<'info, BorrowerReputation>,
+    pub protocol_state: Account<'info, ProtocolState>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeBorrowRateLimit<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + 8 + 8 + 8 + 8,
+        seeds = [b"borrow_rate_limit"],
+        bump
+    )]
+    pub borrow_rate_limit: Account<'info, BorrowRateLimit>,
     #[account(mut)]
-    pub collateral_pool: Account<'info, CollateralPool>,
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct DepositLiquidity<'info> {
     #[account(mut)]
-    pub user_collateral_account: Account<'info, TokenAccount>,
+    pub lender: Signer<'info>,
+    #[account(
+        init_if_needed,
+        payer = lender,
+        space = 8 + 32 + 32 + 8 + 8 + 8 + 16 + 8,
+        seeds = [b"lender", lending_pool.key().as_ref(), lender.key().as_ref()],
+        bump
+    )]
+    pub lender_position: Account<'info, LenderPosition>,
     #[account(mut)]
-    pub collateral_pool_token_account: Account<'info, TokenAccount>,
+    pub lending_pool: Account<'info, LendingPool>,
+    #[account(mut)]
+    pub lending_pool_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub lender_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub protocol_state: Account<'info, ProtocolState>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct DonateRewards<'info> {
+    pub donor: Signer<'info>,
+    #[account(mut)]
+    pub lending_pool: Account<'info, LendingPool>,
+    #[account(mut)]
+    pub lending_pool_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub donor_token_account: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct PoolStats<'info> {
+    pub lending_pool: Account<'info, LendingPool>,
+    pub protocol_state: Account<'info, ProtocolState>,
+}
+
+#[derive(Accounts)]
+pub struct BootstrapLiquidity<'info> {
+    pub admin: Signer<'info>,
+    #[account(mut, has_one = admin @ ZKError::Unauthorized)]
+    pub protocol_state: Account<'info, ProtocolState>,
+    #[account(mut)]
+    pub lending_pool: Account<'info, LendingPool>,
+    #[account(mut)]
+    pub lending_pool_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub admin_token_account: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimRewards<'info> {
+    #[account(mut)]
+    pub lender: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"lender", lending_pool.key().as_ref(), lender.key().as_ref()],
+        bump
+    )]
+    pub lender_position: Account<'info, LenderPosition>,
+    #[account(mut)]
+    pub lending_pool: Account<'info, LendingPool>,
+    /// CHECK: PDA derived authority.
+    pub lending_pool_authority: AccountInfo<'info>,
+    #[account(mut)]
+    pub lending_pool_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub lender_token_account: Account<'info, TokenAccount>,
     pub token_program: Program<'info, Token>,
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct Borrow<'info> {
-    #[account(mut)]
-    pub borrower: Signer<'info>,
+pub struct CompoundRewards<'info> {
+    pub lender: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"lender", lending_pool.key().as_ref(), lender.key().as_ref()],
+        bump
+    )]
+    pub lender_position: Account<'info, LenderPosition>,
+    #[account(mut)]
+    pub lending_pool: Account<'info, LendingPool>,
+    #[account(mut)]
+    pub protocol_state: Account<'info, ProtocolState>,
+}
+
+#[derive(Accounts)]
+pub struct ExitLending<'info> {
     #[account(mut)]
-    pub borrower_account: Account<'info, BorrowerAccount>,
+    pub lender: Signer<'info>,
+    #[account(
+        mut,
+        close = lender,
+        seeds = [b"lender", lending_pool.key().as_ref(), lender.key().as_ref()],
+        bump
+    )]
+    pub lender_position: Account<'info, LenderPosition>,
     #[account(mut)]
     pub lending_pool: Account<'info, LendingPool>,
     /// CHECK: PDA derived authority.
@@ -624,122 +7761,276 @@ pub struct Borrow<'info> {
     #[account(mut)]
     pub lending_pool_token_account: Account<'info, TokenAccount>,
     #[account(mut)]
-    pub user_borrow_token_account: Account<'info, TokenAccount>,
+    pub lender_token_account: Account<'info, TokenAccount>,
     #[account(mut)]
     pub protocol_state: Account<'info, ProtocolState>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct MintRewardReceipt<'info> {
     #[account(mut)]
-    pub protocol_treasury: Account<'info, ProtocolTreasury>,
+    pub lender: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"lender", lending_pool.key().as_ref(), lender.key().as_ref()],
+        bump
+    )]
+    pub lender_position: Account<'info, LenderPosition>,
+    #[account(mut)]
+    pub lending_pool: Account<'info, LendingPool>,
+    /// CHECK: PDA mint authority for reward receipts; holds no data.
+    #[account(seeds = [b"receipt_authority"], bump)]
+    pub receipt_authority: AccountInfo<'info>,
+    #[account(
+        init,
+        payer = lender,
+        mint::decimals = 0,
+        mint::authority = receipt_authority
+    )]
+    pub receipt_mint: Account<'info, Mint>,
+    #[account(
+        init,
+        payer = lender,
+        token::mint = receipt_mint,
+        token::authority = lender
+    )]
+    pub receipt_token_account: Account<'info, TokenAccount>,
+    #[account(
+        init,
+        payer = lender,
+        space = 8 + 32 + 32 + 8,
+        seeds = [b"reward_receipt", receipt_mint.key().as_ref()],
+        bump
+    )]
+    pub reward_receipt: Account<'info, RewardReceipt>,
     pub token_program: Program<'info, Token>,
     pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
 }
 
 #[derive(Accounts)]
-pub struct InstitutionalBorrow<'info> {
-    #[account(mut)]
-    pub borrower: Signer<'info>,
+pub struct RedeemRewardReceipt<'info> {
     #[account(mut)]
-    pub borrower_account: Account<'info, BorrowerAccount>,
+    pub holder: Signer<'info>,
+    #[account(
+        mut,
+        close = holder,
+        has_one = lending_pool,
+        constraint = reward_receipt.mint == receipt_mint.key() @ ZKError::InvalidRewardReceipt,
+        seeds = [b"reward_receipt", receipt_mint.key().as_ref()],
+        bump
+    )]
+    pub reward_receipt: Account<'info, RewardReceipt>,
     #[account(mut)]
+    pub receipt_mint: Account<'info, Mint>,
+    #[account(
+        mut,
+        constraint = holder_receipt_account.mint == receipt_mint.key() @ ZKError::InvalidRewardReceipt,
+        constraint = holder_receipt_account.owner == holder.key() @ ZKError::InvalidRewardReceipt
+    )]
+    pub holder_receipt_account: Account<'info, TokenAccount>,
     pub lending_pool: Account<'info, LendingPool>,
     /// CHECK: PDA derived authority.
     pub lending_pool_authority: AccountInfo<'info>,
     #[account(mut)]
     pub lending_pool_token_account: Account<'info, TokenAccount>,
     #[account(mut)]
-    pub user_borrow_token_account: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub protocol_state: Account<'info, ProtocolState>,
-    #[account(mut)]
-    pub protocol_treasury: Account<'info, ProtocolTreasury>,
-    pub institutional_pool: Account<'info, InstitutionalLendingPool>,
+    pub holder_token_account: Account<'info, TokenAccount>,
     pub token_program: Program<'info, Token>,
-    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct DelegatedBorrow<'info> {
-    #[account(mut)]
-    pub borrower: Signer<'info>,
-    #[account(mut)]
-    pub borrower_account: Account<'info, BorrowerAccount>,
+pub struct ClaimInsurance<'info> {
     #[account(mut)]
+    pub lender: Signer<'info>,
+    #[account(
+        seeds = [b"lender", lending_pool.key().as_ref(), lender.key().as_ref()],
+        bump
+    )]
+    pub lender_position: Account<'info, LenderPosition>,
+    #[account(
+        init_if_needed,
+        payer = lender,
+        space = 8 + 32 + 32 + 8,
+        seeds = [b"insurance_claim", loss_event.key().as_ref(), lender_position.key().as_ref()],
+        bump
+    )]
+    pub insurance_claim: Account<'info, InsuranceClaim>,
     pub lending_pool: Account<'info, LendingPool>,
     /// CHECK: PDA derived authority.
     pub lending_pool_authority: AccountInfo<'info>,
-    #[account(mut)]
+    #[account(
+        mut,
+        constraint = lending_pool_token_account.mint == lending_pool.borrow_mint @ ZKError::BorrowMintMismatch,
+        constraint = lending_pool_token_account.owner == lending_pool.pool_authority @ ZKError::InvalidPoolEscrow
+    )]
     pub lending_pool_token_account: Account<'info, TokenAccount>,
     #[account(mut)]
-    pub user_borrow_token_account: Account<'info, TokenAccount>,
+    pub lender_token_account: Account<'info, TokenAccount>,
     #[account(mut)]
-    pub protocol_state: Account<'info, ProtocolState>,
+    pub loss_event: Account<'info, Governance>,
     #[account(mut)]
     pub protocol_treasury: Account<'info, ProtocolTreasury>,
-    pub delegated_borrower: Account<'info, DelegatedBorrower>,
     pub token_program: Program<'info, Token>,
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct Repay<'info> {
+pub struct BadDebtAuction<'info> {
     #[account(mut)]
-    pub borrower: Signer<'info>,
+    pub bidder: Signer<'info>,
+    #[account(
+        init_if_needed,
+        payer = bidder,
+        space = 8 + 32 + 8 + 8,
+        seeds = [b"recap_claim", bidder.key().as_ref()],
+        bump
+    )]
+    pub recap_claim: Account<'info, RecapClaim>,
     #[account(mut)]
-    pub borrower_account: Account<'info, BorrowerAccount>,
+    pub protocol_state: Account<'info, ProtocolState>,
+    #[account(mut)]
+    pub lending_pool: Account<'info, LendingPool>,
+    #[account(
+        mut,
+        constraint = lending_pool_token_account.mint == lending_pool.borrow_mint @ ZKError::BorrowMintMismatch
+    )]
+    pub lending_pool_token_account: Account<'info, TokenAccount>,
     #[account(mut)]
+    pub bidder_token_account: Account<'info, TokenAccount>,
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimRecap<'info> {
+    pub bidder: Signer<'info>,
+    #[account(
+        mut,
+        seeds = [b"recap_claim", bidder.key().as_ref()],
+        bump,
+        has_one = bidder @ ZKError::Unauthorized
+    )]
+    pub recap_claim: Account<'info, RecapClaim>,
     pub lending_pool: Account<'info, LendingPool>,
     /// CHECK: PDA derived authority.
     pub lending_pool_authority: AccountInfo<'info>,
-    #[account(mut)]
+    #[account(
+        mut,
+        constraint = lending_pool_token_account.mint == lending_pool.borrow_mint @ ZKError::BorrowMintMismatch
+    )]
     pub lending_pool_token_account: Account<'info, TokenAccount>,
     #[account(mut)]
-    pub user_borrow_token_account: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub protocol_state: Account<'info, ProtocolState>,
+    pub bidder_token_account: Account<'info, TokenAccount>,
     #[account(mut)]
     pub protocol_treasury: Account<'info, ProtocolTreasury>,
     pub token_program: Program<'info, Token>,
-    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct Liquidate<'info> {
+pub struct InitializeCollateralBuffer<'info> {
     #[account(mut)]
-    pub liquidator: Signer<'info>,
+    pub owner: Signer<'info>,
+    #[account(has_one = owner @ ZKError::UnauthorizedBorrower)]
+    pub borrower_account: Account<'info, BorrowerAccount>,
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + 32 + 32 + 32 + 1,
+        seeds = [b"collateral_buffer", borrower_account.key().as_ref()],
+        bump
+    )]
+    pub collateral_buffer: Account<'info, CollateralBuffer>,
+    #[account(constraint = buffer_token_account.owner == collateral_buffer.key())]
+    pub buffer_token_account: Account<'info, TokenAccount>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AutoRebalance<'info> {
+    pub keeper: Signer<'info>,
+    #[account(
+        has_one = keeper @ ZKError::UnauthorizedKeeper,
+        has_one = borrower_account,
+        seeds = [b"collateral_buffer", borrower_account.key().as_ref()],
+        bump = collateral_buffer.bump
+    )]
+    pub collateral_buffer: Account<'info, CollateralBuffer>,
     #[account(mut)]
     pub borrower_account: Account<'info, BorrowerAccount>,
+    #[account(mut, address = collateral_buffer.token_account)]
+    pub buffer_token_account: Account<'info, TokenAccount>,
     #[account(mut)]
     pub collateral_pool: Account<'info, CollateralPool>,
+    #[account(
+        mut,
+        constraint = collateral_pool_token_account.mint == collateral_pool.asset_mint @ ZKError::CollateralMintMismatch
+    )]
+    pub collateral_pool_token_account: Account<'info, TokenAccount>,
+    #[account(mut)]
+    pub protocol_state: Account<'info, ProtocolState>,
+    /// CHECK: Price oracle, checked against the collateral pool's configured oracle.
+    pub price_oracle: Option<AccountInfo<'info>>,
     pub token_program: Program<'info, Token>,
-    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct ProposeChange<'info> {
+pub struct ReconcileCollateral<'info> {
+    pub admin: Signer<'info>,
+    #[account(mut, has_one = admin @ ZKError::Unauthorized)]
+    pub protocol_state: Account<'info, ProtocolState>,
     #[account(mut)]
-    pub proposer: Signer<'info>,
-    #[account(init, payer = proposer, space = 8 + 8 + 1 + 8 + 8)]
-    pub governance: Account<'info, Governance>,
-    pub system_program: Program<'info, System>,
+    pub collateral_pool: Account<'info, CollateralPool>,
+    #[account(
+        constraint = collateral_pool_token_account.mint == collateral_pool.asset_mint @ ZKError::CollateralMintMismatch
+    )]
+    pub collateral_pool_token_account: Account<'info, TokenAccount>,
 }
 
 #[derive(Accounts)]
-pub struct Vote<'info> {
+pub struct SweepDustCollateral<'info> {
+    pub caller: Signer<'info>,
     #[account(mut)]
-    pub voter: Signer<'info>,
+    pub borrower_account: Account<'info, BorrowerAccount>,
     #[account(mut)]
-    pub governance: Account<'info, Governance>,
+    pub collateral_pool: Account<'info, CollateralPool>,
+    /// CHECK: PDA derived authority.
+    pub collateral_pool_authority: AccountInfo<'info>,
+    #[account(
+        mut,
+        constraint = collateral_pool_token_account.mint == collateral_pool.asset_mint @ ZKError::CollateralMintMismatch
+    )]
+    pub collateral_pool_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = owner_collateral_account.owner == borrower_account.owner @ ZKError::UnauthorizedBorrower,
+        constraint = owner_collateral_account.mint == collateral_pool.asset_mint @ ZKError::CollateralMintMismatch
+    )]
+    pub owner_collateral_account: Account<'info, TokenAccount>,
     #[account(mut)]
-    pub institutional_pool: Account<'info, InstitutionalLendingPool>,
-    pub system_program: Program<'info, System>,
+    pub protocol_state: Account<'info, ProtocolState>,
+    pub token_program: Program<'info, Token>,
 }
 
 #[derive(Accounts)]
-pub struct RebalanceCollateral<'info> {
-    #[account(mut)]
-    pub borrower: Signer<'info>,
+pub struct SnapshotSolvency<'info> {
+    pub caller: Signer<'info>,
     #[account(mut)]
     pub borrower_account: Account<'info, BorrowerAccount>,
-    pub system_program: Program<'info, System>,
+    pub collateral_pool: Account<'info, CollateralPool>,
+    pub protocol_state: Account<'info, ProtocolState>,
+    /// CHECK: Price oracle, checked against the collateral pool's configured oracle.
+    pub price_oracle: Option<AccountInfo<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct RevealBalance<'info> {
+    pub owner: Signer<'info>,
+    #[account(has_one = owner @ ZKError::UnauthorizedBorrower)]
+    pub borrower_account: Account<'info, BorrowerAccount>,
+    #[account(seeds = [b"verifying_keys"], bump)]
+    pub verifying_key_registry: Account<'info, VerifyingKeyRegistry>,
 }
 
 #[error_code]
@@ -768,5 +8059,1147 @@ pub enum ZKError {
     UnauthorizedBorrower,
     #[msg("Borrow amount exceeds delegated credit limit")]
     BorrowLimitExceeded,
+    #[msg("Token account mint does not match the collateral pool asset")]
+    CollateralMintMismatch,
+    #[msg("Position has not been marked for liquidation")]
+    LiquidationNotMarked,
+    #[msg("Position is already marked for liquidation")]
+    LiquidationAlreadyMarked,
+    #[msg("Liquidation delay since marking has not elapsed")]
+    LiquidationDelayNotMet,
+    #[msg("Borrow exceeds the rate limit for the current window")]
+    RateLimited,
+    #[msg("Rate limit window must be positive")]
+    InvalidRateLimitWindow,
+    #[msg("Swap output is below the minimum accepted amount")]
+    SlippageExceeded,
+    #[msg("Swap spent more input tokens than allowed")]
+    SwapInputExceeded,
+    #[msg("Loan position does not match the requested loan")]
+    InvalidLoan,
+    #[msg("Collateral factor and liquidation threshold are out of range")]
+    InvalidCollateralParams,
+    #[msg("Proposal has already been executed")]
+    ProposalAlreadyExecuted,
+    #[msg("Proposal has not passed")]
+    ProposalNotPassed,
+    #[msg("Ed25519 signature is missing or invalid")]
+    InvalidSignature,
+    #[msg("Delegation authorization has expired")]
+    DelegationExpired,
+    #[msg("Nonce has already been used")]
+    StaleNonce,
+    #[msg("Target health factor must be above 1.0")]
+    InvalidHealthTarget,
+    #[msg("Position is already at or above the target health")]
+    RebalanceNotNeeded,
+    #[msg("Signer is not the authorized keeper")]
+    UnauthorizedKeeper,
+    #[msg("Signer is not authorized for this action")]
+    Unauthorized,
+    #[msg("Fee tiers must start at zero, ascend strictly, and not exceed 100%")]
+    InvalidFeeTiers,
+    #[msg("Collateral pool has no registered custodian")]
+    CustodianNotRegistered,
+    #[msg("Reserve attestation has expired")]
+    AttestationExpired,
+    #[msg("Attested reserves do not cover the staked amount")]
+    InsufficientReserves,
+    #[msg("Optimal utilization must be between 1% and 99%")]
+    InvalidRateCurve,
+    #[msg("Rate lock duration is out of range")]
+    InvalidRateLockDuration,
+    #[msg("Operation is not supported for this loan's rate mode")]
+    RateModeMismatch,
+    #[msg("Loan rate is already locked")]
+    RateAlreadyLocked,
+    #[msg("Delegate must differ from the delegator")]
+    InvalidDelegate,
+    #[msg("Zero-knowledge proof exceeds the maximum size")]
+    ProofTooLarge,
+    #[msg("Collateral yield index must be non-zero")]
+    InvalidCollateralIndex,
+    #[msg("Refinancing would not lower the loan's rate")]
+    RefinanceNotBeneficial,
+    #[msg("Account still has outstanding debt")]
+    OutstandingDebt,
+    #[msg("Collateral is not below the dust threshold")]
+    NotDustCollateral,
+    #[msg("New borrows are frozen")]
+    BorrowsFrozen,
+    #[msg("Account is not in the expected legacy layout")]
+    InvalidAccountLayout,
+    #[msg("Liquidation protocol share cannot exceed 100%")]
+    InvalidLiquidationShare,
+    #[msg("Liquidity bootstrap already used or pool is not empty")]
+    BootstrapUnavailable,
+    #[msg("Grace tiers must start at zero, ascend strictly, and end before a mark expires")]
+    InvalidGraceTiers,
+    #[msg("Treasury reserves are insufficient")]
+    InsufficientTreasuryReserves,
+    #[msg("Batch amounts, proofs, and accounts do not line up")]
+    BatchLengthMismatch,
+    #[msg("Too many whitelist members")]
+    WhitelistFull,
+    #[msg("No rewards available to claim")]
+    NoRewardsToClaim,
+    #[msg("Token account does not hold this reward receipt")]
+    InvalidRewardReceipt,
+    #[msg("Borrower has reached the maximum number of open loans")]
+    TooManyLoans,
+    #[msg("Institutional pool does not match the proposal's governance pool")]
+    WrongGovernancePool,
+    #[msg("Borrow would exceed the protocol-wide leverage limit")]
+    ProtocolLeverageExceeded,
+    #[msg("Stake lock-up duration is out of range")]
+    InvalidStakeLockDuration,
+    #[msg("Borrowing is disabled for this asset")]
+    AssetBorrowDisabled,
+    #[msg("Invalid early-repayment rebate configuration")]
+    InvalidEarlyRepayRebate,
+    #[msg("Liquidation bonus exceeds the maximum")]
+    InvalidLiquidationBonus,
+    #[msg("Voting on this proposal has closed")]
+    VotingClosed,
+    #[msg("Proposal is still active")]
+    ProposalStillActive,
+    #[msg("Price oracle is missing, mismatched, or unreadable")]
+    InvalidOracle,
+    #[msg("Price oracle is stale")]
+    StaleOracle,
+    #[msg("Flash loan is not repaid later in the transaction")]
+    FlashLoanNotRepaid,
+    #[msg("A flash loan is already in progress")]
+    FlashLoanInProgress,
+    #[msg("No verifying key is registered for this circuit")]
+    CircuitNotRegistered,
+    #[msg("Borrow would dip into the lending pool's reserved liquidity buffer")]
+    BufferReserved,
+    #[msg("Loan has not reached maturity")]
+    LoanNotMatured,
+    #[msg("Borrowers cannot liquidate their own position")]
+    SelfLiquidationNotAllowed,
+    #[msg("Interest-free period is out of range")]
+    InvalidInterestFreePeriod,
+    #[msg("Borrow would push utilization past the protocol's cap")]
+    MaxUtilizationReached,
+    #[msg("Invalid collateral lock tiers")]
+    InvalidLockTiers,
+    #[msg("Token account mint does not match the lending pool's borrow mint")]
+    BorrowMintMismatch,
+    #[msg("Lending pool borrow mint already set")]
+    BorrowMintAlreadySet,
+    #[msg("Asset registry is full")]
+    AssetRegistryFull,
+    #[msg("Stake settle time must not be negative")]
+    InvalidStakeSettleTime,
+    #[msg("Recently staked collateral has not settled yet")]
+    StakeNotSettled,
+    #[msg("Recapitalization premium exceeds the maximum")]
+    InvalidRecapPremium,
+    #[msg("No bad debt to cover")]
+    NoBadDebt,
+    #[msg("Stake is below the collateral pool's minimum")]
+    StakeTooSmall,
+    #[msg("Delegated liquidations need the beneficiary's collateral account")]
+    BeneficiaryAccountRequired,
+    #[msg("Loan is delinquent")]
+    LoanDelinquent,
+    #[msg("Loop count must be between 1 and the maximum")]
+    InvalidLoopCount,
+    #[msg("Split amount must be positive and less than the loan's principal")]
+    InvalidSplitAmount,
+    #[msg("Unknown repayment waterfall order")]
+    InvalidRepayOrder,
+    #[msg("Proposal bond is below the minimum")]
+    ProposalBondTooSmall,
+    #[msg("No protocol-owned liquidity yield to claim")]
+    NoProtocolYield,
+    #[msg("Token account is not the collateral pool's escrow")]
+    InvalidPoolEscrow,
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::solana_program::sysvar::instructions::{
+        construct_instructions_data, store_current_index, BorrowedAccountMeta,
+        BorrowedInstruction,
+    };
+
+    /// A zeroed current-layout protocol state.
+    fn blank_protocol_state() -> ProtocolState {
+        let mut data = vec![0u8; PROTOCOL_STATE_LEN];
+        data[..8].copy_from_slice(&ProtocolState::DISCRIMINATOR);
+        ProtocolState::try_deserialize(&mut &data[..]).unwrap()
+    }
+
+    fn serialize_protocol_state(state: &ProtocolState) -> Vec<u8> {
+        let mut data = Vec::new();
+        state.try_serialize(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn protocol_state_len_matches_layout() {
+        assert_eq!(
+            serialize_protocol_state(&blank_protocol_state()).len(),
+            PROTOCOL_STATE_LEN
+        );
+    }
+
+    #[test]
+    fn migrated_legacy_protocol_state_loads() {
+        let admin = Pubkey::new_unique();
+        let mut state = blank_protocol_state();
+        state.total_liquidity = 1_000_000;
+        state.base_interest_rate = 5;
+        state.utilization_rate = 200;
+        state.min_collateral_lock_time = 600;
+        state.min_collateral_ratio = 15_000;
+        state.admin = admin;
+        state.fee_tier_count = 1;
+        state.borrows_frozen = true;
+        let current = serialize_protocol_state(&state);
+
+        // The legacy layout stores utilization in one byte and ends after `borrows_frozen`.
+        let offset = PROTOCOL_STATE_UTILIZATION_OFFSET;
+        let mut legacy = current[..offset + 1].to_vec();
+        legacy.extend_from_slice(&current[offset + 2..LEGACY_PROTOCOL_STATE_LEN + 1]);
+        assert_eq!(legacy.len(), LEGACY_PROTOCOL_STATE_LEN);
+        assert_eq!(
+            &legacy[LEGACY_PROTOCOL_STATE_ADMIN_OFFSET..][..32],
+            admin.as_ref()
+        );
+
+        let migrated = migrate_legacy_protocol_state(&legacy).unwrap();
+        let reloaded =
+            ProtocolState::try_deserialize(&mut &serialize_protocol_state(&migrated)[..]).unwrap();
+        assert_eq!(reloaded.total_liquidity, 1_000_000);
+        assert_eq!(reloaded.utilization_rate, 200);
+        assert_eq!(reloaded.min_collateral_lock_time, 600);
+        assert_eq!(reloaded.min_collateral_ratio, 15_000);
+        assert_eq!(reloaded.admin, admin);
+        assert!(reloaded.borrows_frozen);
+        assert_eq!(reloaded.grace_tier_count, 1);
+        assert_eq!(reloaded.max_loans_per_borrower, 16);
+        assert!(reloaded.fees_enabled);
+    }
+
+    #[test]
+    fn collateral_pool_authority_is_a_per_pool_pda() {
+        let pool_a = Pubkey::new_unique();
+        let pool_b = Pubkey::new_unique();
+        let authority = collateral_pool_authority(&pool_a);
+        assert!(!authority.is_on_curve());
+        assert_eq!(authority, collateral_pool_authority(&pool_a));
+        assert_ne!(authority, collateral_pool_authority(&pool_b));
+    }
+
+    /// Runs `require_flash_repay` against an instructions sysvar holding `instructions`, with
+    /// the instruction at `current` executing.
+    fn check_flash_repay(
+        instructions: &[Instruction],
+        current: u16,
+        flash_loan: &Pubkey,
+    ) -> Result<()> {
+        let borrowed: Vec<BorrowedInstruction> = instructions
+            .iter()
+            .map(|ix| BorrowedInstruction {
+                program_id: &ix.program_id,
+                accounts: ix
+                    .accounts
+                    .iter()
+                    .map(|meta| BorrowedAccountMeta {
+                        pubkey: &meta.pubkey,
+                        is_signer: meta.is_signer,
+                        is_writable: meta.is_writable,
+                    })
+                    .collect(),
+                data: &ix.data,
+            })
+            .collect();
+        let mut data = construct_instructions_data(&borrowed);
+        store_current_index(&mut data, current);
+        let key = sysvar::instructions::ID;
+        let owner = sysvar::ID;
+        let mut lamports = 0;
+        let info =
+            AccountInfo::new(&key, false, false, &mut lamports, &mut data, &owner, false, 0);
+        require_flash_repay(&info, flash_loan)
+    }
+
+    fn flash_instruction(discriminator: &[u8], flash_loan: Pubkey) -> Instruction {
+        Instruction {
+            program_id: crate::ID,
+            accounts: vec![AccountMeta::new(flash_loan, false)],
+            data: discriminator.to_vec(),
+        }
+    }
+
+    #[test]
+    fn flash_borrow_requires_repay_in_same_transaction() {
+        let flash_loan = Pubkey::new_unique();
+        let borrow = flash_instruction(&instruction::FlashBorrow::DISCRIMINATOR, flash_loan);
+        let repay = flash_instruction(&instruction::FlashRepay::DISCRIMINATOR, flash_loan);
+        let other_repay =
+            flash_instruction(&instruction::FlashRepay::DISCRIMINATOR, Pubkey::new_unique());
+
+        assert!(check_flash_repay(std::slice::from_ref(&borrow), 0, &flash_loan).is_err());
+        assert!(check_flash_repay(&[borrow.clone(), other_repay], 0, &flash_loan).is_err());
+        // A repay that ran before the borrow doesn't count.
+        assert!(check_flash_repay(&[repay.clone(), borrow.clone()], 1, &flash_loan).is_err());
+        assert!(check_flash_repay(&[borrow, repay], 0, &flash_loan).is_ok());
+    }
+
+    #[test]
+    fn migration_rejects_current_layout() {
+        let current = serialize_protocol_state(&blank_protocol_state());
+        assert!(migrate_legacy_protocol_state(&current).is_err());
+    }
+
+    /// A zeroed account of type `T`, for accounts too large to build field by field.
+    fn zeroed<T: AccountDeserialize + Discriminator>() -> T {
+        let mut data = vec![0u8; 4096];
+        data[..8].copy_from_slice(&T::DISCRIMINATOR);
+        T::try_deserialize(&mut &data[..]).unwrap()
+    }
+
+    fn lender(principal: u64, deposit_timestamp: i64) -> LenderPosition {
+        let mut position: LenderPosition = zeroed();
+        position.principal = principal;
+        position.deposit_timestamp = deposit_timestamp;
+        position
+    }
+
+    #[test]
+    fn reward_boost_grows_with_tenure_up_to_cap() {
+        assert_eq!(reward_boost_bps(-86_400), 0);
+        assert_eq!(reward_boost_bps(86_399), 0);
+        assert_eq!(reward_boost_bps(86_400), REWARD_BOOST_BPS_PER_DAY);
+        assert_eq!(reward_boost_bps(30 * 86_400), 30 * REWARD_BOOST_BPS_PER_DAY);
+        assert_eq!(reward_boost_bps(i64::MAX), MAX_REWARD_BOOST_BPS);
+    }
+
+    #[test]
+    fn tenure_boost_is_paid_from_budget_not_other_lenders() {
+        let now = 1_000 * 86_400;
+        let mut pool: LendingPool = zeroed();
+        pool.total_liquidity = 2_000;
+        let mut veteran = lender(1_000, 0);
+        let mut newcomer = lender(1_000, now);
+        distribute_lender_rewards(&mut pool, 1_000, now).unwrap();
+        pool.reward_boost_budget = 100;
+
+        let veteran_payout = take_lender_rewards(&mut veteran, &mut pool, now).unwrap();
+        assert_eq!(veteran_payout, 500 + 100);
+        assert_eq!(pool.reward_boost_budget, 0);
+        // The newcomer still gets its full base share after the veteran's boosted claim.
+        assert_eq!(take_lender_rewards(&mut newcomer, &mut pool, now).unwrap(), 500);
+        assert_eq!(pool.lender_rewards, 0);
+
+        // Without a budget the veteran gets no boost at all.
+        distribute_lender_rewards(&mut pool, 1_000, now).unwrap();
+        assert_eq!(take_lender_rewards(&mut veteran, &mut pool, now).unwrap(), 500);
+    }
+
+    #[test]
+    fn three_lenders_never_claim_more_than_distributed() {
+        let mut pool: LendingPool = zeroed();
+        pool.total_liquidity = 1_000 + 333 + 7;
+        let mut lenders = [lender(1_000, 0), lender(333, 0), lender(7, 0)];
+        let mut distributed = 0u64;
+        let mut claimed = 0u64;
+        for (round, reward) in [997u64, 13, 1, 4_999, 2].into_iter().enumerate() {
+            distribute_lender_rewards(&mut pool, reward, 0).unwrap();
+            distributed += reward;
+            // Lenders claim at different times; settlement order must not matter.
+            for position in lenders.iter_mut().skip(round % 3) {
+                claimed += take_lender_rewards(position, &mut pool, 0).unwrap();
+            }
+            assert!(claimed <= distributed);
+        }
+        for position in lenders.iter_mut() {
+            claimed += take_lender_rewards(position, &mut pool, 0).unwrap();
+        }
+        assert!(claimed <= distributed);
+        assert_eq!(claimed + pool.lender_rewards, distributed);
+    }
+
+    #[test]
+    fn protocol_yield_is_kept_apart_from_lender_rewards() {
+        let mut pool: LendingPool = zeroed();
+        pool.total_liquidity = 3_000;
+        pool.protocol_owned_liquidity = 1_000;
+        let mut a = lender(1_000, 0);
+        let mut b = lender(1_000, 0);
+        distribute_lender_rewards(&mut pool, 1_001, 0).unwrap();
+        assert_eq!(pool.protocol_yield, 333);
+        assert_eq!(pool.protocol_yield + pool.lender_rewards, 1_001);
+
+        // Lenders draining their rewards leaves the protocol's share intact.
+        let claimed = take_lender_rewards(&mut a, &mut pool, 0).unwrap()
+            + take_lender_rewards(&mut b, &mut pool, 0).unwrap();
+        assert_eq!(claimed, 668);
+        assert_eq!(pool.protocol_yield, 333);
+    }
+
+    #[test]
+    fn accrual_grows_stored_debt_before_repayment() {
+        let protocol_state = blank_protocol_state();
+        let mut borrower_account: BorrowerAccount = zeroed();
+        borrower_account.encrypted_principal.value = 1_000_000;
+        let mut loan_position: LoanPosition = zeroed();
+        loan_position.principal = 1_000_000;
+        loan_position.rate_mode = RATE_MODE_FIXED;
+        loan_position.interest_rate = 10;
+
+        let debt_before = borrower_debt(&borrower_account);
+        accrue_loan_interest(&mut loan_position, &mut borrower_account, &protocol_state, 31_536_000)
+            .unwrap();
+        assert_eq!(borrower_debt(&borrower_account), debt_before + 100_000);
+        assert_eq!(loan_position.accrued_interest, 100_000);
+
+        // A second accrual compounds on the capitalized interest.
+        accrue_loan_interest(&mut loan_position, &mut borrower_account, &protocol_state, 63_072_000)
+            .unwrap();
+        assert_eq!(borrower_debt(&borrower_account), debt_before + 210_000);
+    }
+
+    #[test]
+    fn pool_indexes_diverge_with_utilization() {
+        let mut protocol_state = blank_protocol_state();
+        protocol_state.optimal_utilization = 80;
+        protocol_state.rate_slope_low = 4;
+        protocol_state.rate_slope_high = 60;
+        let mut idle: LendingPool = zeroed();
+        idle.base_interest_rate = 2;
+        idle.utilization_rate = 20;
+        let mut busy: LendingPool = zeroed();
+        busy.base_interest_rate = 2;
+        busy.utilization_rate = 90;
+
+        for pool in [&mut idle, &mut busy] {
+            accrue_pool_indexes(pool, &protocol_state, 1).unwrap();
+            assert_eq!(pool.cumulative_borrow_index, RAY);
+            accrue_pool_indexes(pool, &protocol_state, 1 + 31_536_000).unwrap();
+        }
+        // 3% borrow rate at 20% utilization, 36% at 90%.
+        assert_eq!(idle.cumulative_borrow_index, RAY / 100 * 103);
+        assert_eq!(idle.cumulative_supply_index, RAY / 1_000 * 1_006);
+        assert_eq!(busy.cumulative_borrow_index, RAY / 100 * 136);
+        assert_eq!(busy.cumulative_supply_index, RAY / 1_000 * 1_324);
+    }
+
+    #[test]
+    fn accrual_incentive_tracks_interest_accrued() {
+        // Dust accruals earn nothing, however stale the loan.
+        assert_eq!(accrual_incentive(0, u64::MAX).unwrap(), 0);
+        assert_eq!(accrual_incentive(19, u64::MAX).unwrap(), 0);
+        assert_eq!(accrual_incentive(1_000, u64::MAX).unwrap(), 50);
+        assert_eq!(accrual_incentive(u64::MAX, u64::MAX).unwrap(), MAX_ACCRUAL_INCENTIVE);
+        assert_eq!(accrual_incentive(1_000, 7).unwrap(), 7);
+    }
+
+    #[test]
+    fn tiny_borrow_fee_rounds_up_to_one() {
+        let mut protocol_state = blank_protocol_state();
+        protocol_state.fees_enabled = true;
+        protocol_state.fee_tier_count = 1;
+        protocol_state.fee_tiers[0] = FeeTier { threshold: 0, bps: 30 };
+        let mut borrower_account: BorrowerAccount = zeroed();
+        assert_eq!(calculate_borrow_fee(1, &protocol_state, &borrower_account, 0).unwrap(), 1);
+
+        // The stake-lock discount takes 30 bps to 22.5, charged as 23.
+        borrower_account.stake_lock_until = 1;
+        assert_eq!(calculate_borrow_fee(1, &protocol_state, &borrower_account, 0).unwrap(), 1);
+        assert_eq!(
+            calculate_borrow_fee(10_000, &protocol_state, &borrower_account, 0).unwrap(),
+            23
+        );
+    }
+
+    #[test]
+    fn proposal_state_follows_lifecycle() {
+        let mut governance: Governance = zeroed();
+        governance.voting_deadline = 100;
+        assert_eq!(proposal_state(&governance, 100), PROPOSAL_STATE_ACTIVE);
+        assert_eq!(proposal_state(&governance, 101), PROPOSAL_STATE_EXPIRED);
+        governance.votes = PROPOSAL_QUORUM as i64;
+        assert_eq!(proposal_state(&governance, 50), PROPOSAL_STATE_PASSED);
+        assert_eq!(proposal_state(&governance, 101), PROPOSAL_STATE_PASSED);
+        governance.executed = true;
+        assert_eq!(proposal_state(&governance, 101), PROPOSAL_STATE_EXECUTED);
+    }
+
+    #[test]
+    fn blocked_rapid_borrow_counts_flash_attempt() {
+        let mut borrower_account: BorrowerAccount = zeroed();
+        borrower_account.borrow_timestamp = 1_000;
+        let mut data = Vec::new();
+        borrower_account.try_serialize(&mut data).unwrap();
+        let key = Pubkey::new_unique();
+        let mut lamports = 0;
+        let info =
+            AccountInfo::new(&key, false, true, &mut lamports, &mut data, &crate::ID, false, 0);
+        let mut borrower_account = Account::<BorrowerAccount>::try_from(&info).unwrap();
+
+        assert!(record_flash_attempt(&mut borrower_account, 60, 1_030).unwrap());
+        assert!(record_flash_attempt(&mut borrower_account, 60, 1_059).unwrap());
+        assert_eq!(borrower_account.flash_attempts, 2);
+        assert!(!record_flash_attempt(&mut borrower_account, 60, 1_060).unwrap());
+        assert_eq!(borrower_account.flash_attempts, 2);
+        // A first borrow has nothing to wait for.
+        borrower_account.borrow_timestamp = 0;
+        assert!(!record_flash_attempt(&mut borrower_account, 60, 30).unwrap());
+    }
+    #[test]
+    fn mul_div_rounds_toward_the_protocol() {
+        assert_eq!(mul_div_up(10, 3, 4).unwrap(), 8);
+        assert_eq!(mul_div_down(10, 3, 4).unwrap(), 7);
+        assert_eq!(mul_div_up(12, 3, 4).unwrap(), 9);
+        assert_eq!(mul_div_down(12, 3, 4).unwrap(), 9);
+        assert_eq!(mul_div_up(0, 3, 4).unwrap(), 0);
+        assert_eq!(mul_div_up(1, 1, RAY).unwrap(), 1);
+        assert_eq!(mul_div_down(1, 1, RAY).unwrap(), 0);
+        assert!(mul_div_up(u128::MAX, 2, 1).is_err());
+        assert!(mul_div_down(u128::MAX, 2, 1).is_err());
+    }
+    #[test]
+    fn borrow_fee_uses_highest_tier_reached() {
+        let mut protocol_state = blank_protocol_state();
+        protocol_state.fee_tier_count = 3;
+        protocol_state.fee_tiers[0] = FeeTier { threshold: 0, bps: 50 };
+        protocol_state.fee_tiers[1] = FeeTier { threshold: 10_000, bps: 30 };
+        protocol_state.fee_tiers[2] = FeeTier { threshold: 1_000_000, bps: 10 };
+        let borrower_account: BorrowerAccount = zeroed();
+        let fee = |amount| calculate_borrow_fee(amount, &protocol_state, &borrower_account, 0);
+
+        // The fee switch is off in a blank state.
+        assert_eq!(fee(9_999).unwrap(), 0);
+        protocol_state.fees_enabled = true;
+        let fee = |amount| calculate_borrow_fee(amount, &protocol_state, &borrower_account, 0);
+        assert_eq!(fee(9_999).unwrap(), 50);
+        assert_eq!(fee(10_000).unwrap(), 30);
+        assert_eq!(fee(999_999).unwrap(), 3_000);
+        assert_eq!(fee(1_000_000).unwrap(), 1_000);
+    }
+    #[test]
+    fn utilization_treats_drained_pool_as_full() {
+        assert_eq!(calculate_utilization(0, 0), 0);
+        assert_eq!(calculate_utilization(1, 0), 100);
+        assert_eq!(calculate_utilization(0, 1_000), 0);
+        assert_eq!(calculate_utilization(250, 1_000), 25);
+        assert_eq!(calculate_utilization(999, 1_000), 99);
+        assert_eq!(calculate_utilization(3_000, 1_000), 300);
+        assert_eq!(calculate_utilization(u64::MAX, 1), u16::MAX);
+    }
+    #[test]
+    fn interest_due_is_ray_scaled() {
+        assert_eq!(calculate_interest_due(1_000_000, 10, 31_536_000).unwrap(), 100_000 * RAY);
+        assert_eq!(calculate_interest_due(1_000_000, 10, 0).unwrap(), 0);
+        assert_eq!(calculate_interest_due(1_000_000, 10, -5).unwrap(), 0);
+        // A second's interest on one unit is a fraction of a unit, kept rather than truncated.
+        let tiny = calculate_interest_due(1, 1, 1).unwrap();
+        assert!(tiny > 0 && tiny < RAY);
+        assert_eq!(tiny, RAY.div_ceil(3_153_600_000));
+        assert!(calculate_interest_due(u64::MAX, u8::MAX, i64::MAX).is_err());
+    }
+    fn read_oracle(oracle_kind: u8, mut data: Vec<u8>) -> Result<(u64, u64, i64)> {
+        let key = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let mut lamports = 0;
+        let info =
+            AccountInfo::new(&key, false, false, &mut lamports, &mut data, &owner, false, 0);
+        read_price(oracle_kind, &info)
+    }
+
+    #[test]
+    fn read_price_normalizes_pyth_accounts() {
+        let mut data = vec![0u8; 240];
+        data[0..4].copy_from_slice(&0xa1b2_c3d4u32.to_le_bytes());
+        data[20..24].copy_from_slice(&(-8i32).to_le_bytes());
+        data[96..104].copy_from_slice(&1_700_000_000i64.to_le_bytes());
+        data[208..216].copy_from_slice(&150_000_000i64.to_le_bytes());
+        data[216..224].copy_from_slice(&1_000_000u64.to_le_bytes());
+        assert_eq!(
+            read_oracle(ORACLE_KIND_PYTH, data.clone()).unwrap(),
+            (1_500_000, 10_000, 1_700_000_000)
+        );
+
+        assert!(read_oracle(ORACLE_KIND_PYTH, data[..200].to_vec()).is_err());
+        assert!(read_oracle(ORACLE_KIND_SWITCHBOARD, data.clone()).is_err());
+        data[208..216].copy_from_slice(&(-1i64).to_le_bytes());
+        assert!(read_oracle(ORACLE_KIND_PYTH, data.clone()).is_err());
+        data[0] ^= 1;
+        assert!(read_oracle(ORACLE_KIND_PYTH, data).is_err());
+    }
+
+    #[test]
+    fn read_price_normalizes_switchboard_aggregators() {
+        let mut data = vec![0u8; 410];
+        data[0..8].copy_from_slice(&[217, 230, 65, 101, 201, 162, 27, 125]);
+        data[358..366].copy_from_slice(&1_700_000_000i64.to_le_bytes());
+        data[366..382].copy_from_slice(&25_000i128.to_le_bytes());
+        data[382..386].copy_from_slice(&4u32.to_le_bytes());
+        data[386..402].copy_from_slice(&5i128.to_le_bytes());
+        data[402..406].copy_from_slice(&4u32.to_le_bytes());
+        assert_eq!(
+            read_oracle(ORACLE_KIND_SWITCHBOARD, data.clone()).unwrap(),
+            (2_500_000, 500, 1_700_000_000)
+        );
+
+        assert!(read_oracle(ORACLE_KIND_PYTH, data.clone()).is_err());
+        assert!(read_oracle(ORACLE_KIND_NONE, data.clone()).is_err());
+        data[366..382].copy_from_slice(&0i128.to_le_bytes());
+        assert!(read_oracle(ORACLE_KIND_SWITCHBOARD, data).is_err());
+    }
+
+    #[test]
+    fn normalize_price_rescales_to_oracle_price_one() {
+        assert_eq!(normalize_price(1, 0).unwrap(), ORACLE_PRICE_ONE);
+        assert_eq!(normalize_price(123_456_789, -8).unwrap(), 1_234_567);
+        assert_eq!(normalize_price(42, -6).unwrap(), 42);
+        assert_eq!(normalize_price(7, 2).unwrap(), 700_000_000);
+        assert!(normalize_price(1, 40).is_err());
+        assert!(normalize_price(u128::MAX, 0).is_err());
+    }
+    #[test]
+    fn liquidation_price_covers_debt_at_min_ratio() {
+        let mut collateral_pool: CollateralPool = zeroed();
+        let mut borrower_account: BorrowerAccount = zeroed();
+        borrower_account.encrypted_collateral.value = 3_000;
+        assert_eq!(
+            position_liquidation_price(&borrower_account, &collateral_pool, 15_000).unwrap(),
+            None
+        );
+
+        // 1,000 of debt at 150% needs 1,500 of collateral value: 0.5 per unit of 3,000.
+        borrower_account.encrypted_principal.value = 1_000;
+        let price = |borrower_account: &BorrowerAccount, collateral_pool: &CollateralPool| {
+            position_liquidation_price(borrower_account, collateral_pool, 15_000).unwrap()
+        };
+        assert_eq!(price(&borrower_account, &collateral_pool), Some(500_000));
+        // 1,001 needs 1,501.5, rounded up to 1,502; the price rounds up too.
+        borrower_account.accrued_interest = 1;
+        assert_eq!(price(&borrower_account, &collateral_pool), Some(500_667));
+
+        // Collateral that has doubled against its index halves the liquidation price.
+        collateral_pool.base_index = 100;
+        collateral_pool.current_index = 200;
+        assert_eq!(price(&borrower_account, &collateral_pool), Some(250_334));
+
+        borrower_account.encrypted_collateral.value = 0;
+        assert_eq!(price(&borrower_account, &collateral_pool), Some(u64::MAX));
+    }
+
+    #[test]
+    fn liquidation_debt_reduction_equals_repaid_amount() {
+        let mut protocol_state = blank_protocol_state();
+        protocol_state.total_loans = 10_000;
+        protocol_state.total_liquidity = 5_000;
+        let mut borrower_account: BorrowerAccount = zeroed();
+        borrower_account.encrypted_principal.value = 1_000;
+        borrower_account.accrued_interest = 100;
+        borrower_account.borrow_timestamp = 1;
+
+        let debt_before = borrower_debt(&borrower_account);
+        apply_liquidation_repayment(&mut borrower_account, &mut protocol_state, 400).unwrap();
+        assert_eq!(debt_before - borrower_debt(&borrower_account), 400);
+        // Interest is paid first; only the rest reduces principal and outstanding loans.
+        assert_eq!(borrower_account.accrued_interest, 0);
+        assert_eq!(borrower_account.encrypted_principal.value, 700);
+        assert_eq!(protocol_state.total_loans, 9_700);
+        assert_eq!(protocol_state.total_liquidity, 5_400);
+
+        apply_liquidation_repayment(&mut borrower_account, &mut protocol_state, 700).unwrap();
+        assert_eq!(borrower_debt(&borrower_account), 0);
+        assert_eq!(borrower_account.borrow_timestamp, 0);
+        let overpaid = apply_liquidation_repayment(&mut borrower_account, &mut protocol_state, 1);
+        assert!(overpaid.is_err());
+    }
+
+    #[test]
+    fn collateral_ratio_boundary() {
+        let collateral = EncryptedAmount { value: 15_000 };
+        // 15_000 of collateral backs exactly 10_000 of debt at 150%.
+        assert!(meets_collateral_ratio(collateral.clone(), 10_000, 15_000));
+        assert!(!meets_collateral_ratio(collateral.clone(), 10_001, 15_000));
+        // Existing debt counts: 6_000 owed plus a 4_001 borrow is just over the ratio.
+        let mut borrower_account: BorrowerAccount = zeroed();
+        borrower_account.encrypted_principal.value = 5_000;
+        borrower_account.accrued_interest = 1_000;
+        let debt_after = borrower_debt(&borrower_account) + 4_001;
+        assert!(require_collateral_ratio(&collateral, debt_after, 15_000).is_err());
+        assert!(require_collateral_ratio(&collateral, debt_after - 1, 15_000).is_ok());
+    }
+
+    #[test]
+    fn insurance_claims_are_capped_at_principal() {
+        // Full payout when the fund and the loss both cover the principal.
+        assert_eq!(insurance_payout(1_000, 1_000, 0, 5_000, 5_000), 1_000);
+        // A short fund pays partially; the rest can be claimed once it is refilled, but the
+        // cumulative total never exceeds principal.
+        let first = insurance_payout(1_000, 1_000, 0, 5_000, 400);
+        assert_eq!(first, 400);
+        assert_eq!(insurance_payout(1_000, 1_000, first, 5_000, 5_000), 600);
+        assert_eq!(insurance_payout(1_000, 1_000, 1_000, 5_000, 5_000), 0);
+        // The unpaid loss on the event caps the payout too.
+        assert_eq!(insurance_payout(1_000, 1_000, 0, 250, 5_000), 250);
+    }
+
+    #[test]
+    fn rebalance_buffer_boundary() {
+        let mut protocol_state = blank_protocol_state();
+        protocol_state.min_collateral_ratio = 15_000;
+        protocol_state.rebalance_min_health = 11_000;
+        // 10_000 of debt needs 15_000 at the minimum ratio and 16_500 with the 10% buffer.
+        assert!(rebalance_keeps_buffer(16_500, 10_000, &protocol_state));
+        assert!(!rebalance_keeps_buffer(16_499, 10_000, &protocol_state));
+        assert!(rebalance_keeps_buffer(0, 0, &protocol_state));
+    }
+
+    #[test]
+    fn priced_health_values_collateral_at_price_and_factor() {
+        let mut borrower_account: BorrowerAccount = zeroed();
+        borrower_account.encrypted_collateral.value = 3_000;
+        borrower_account.encrypted_principal.value = 1_000;
+        let mut collateral_pool: CollateralPool = zeroed();
+        collateral_pool.collateral_factor = 8_000;
+        // Raw collateral would report a health of 2.0; at half the price it is 1.0.
+        let half = ORACLE_PRICE_ONE / 2;
+        assert_eq!(
+            priced_health(&borrower_account, &collateral_pool, half, 0, 15_000).unwrap(),
+            HEALTH_FACTOR_ONE
+        );
+        // A factor decayed from 80% to 40% halves the health again.
+        collateral_pool.factor_decay_start = 0;
+        collateral_pool.factor_decay_bps_per_day = 4_000;
+        collateral_pool.min_collateral_factor = 4_000;
+        assert_eq!(
+            priced_health(&borrower_account, &collateral_pool, half, 86_400, 15_000).unwrap(),
+            HEALTH_FACTOR_ONE / 2
+        );
+    }
+
+    #[test]
+    fn collateral_for_power_inverts_borrowing_power() {
+        let mut collateral_pool: CollateralPool = zeroed();
+        collateral_pool.collateral_factor = 8_000;
+        collateral_pool.factor_decay_bps_per_day = 2_000;
+        collateral_pool.min_collateral_factor = 4_000;
+        let price = ORACLE_PRICE_ONE / 2;
+        // After a day the factor is 60%: each token carries 0.5 * 6/8 of power.
+        let now = 86_400;
+        let amount = collateral_for_power(3_000, &collateral_pool, price, now).unwrap();
+        assert_eq!(amount, 8_000);
+        assert_eq!(borrowing_power(amount, &collateral_pool, price, now).unwrap(), 3_000);
+        // Rounding up never leaves the top-up short.
+        let amount = collateral_for_power(3_001, &collateral_pool, price, now).unwrap();
+        assert!(borrowing_power(amount, &collateral_pool, price, now).unwrap() >= 3_001);
+    }
+
+    #[test]
+    fn refinancing_a_fixed_loan_clears_its_term() {
+        let protocol_state = blank_protocol_state();
+        let mut loan_position: LoanPosition = zeroed();
+        loan_position.principal = 1_000_000;
+        loan_position.rate_mode = RATE_MODE_FIXED;
+        loan_position.interest_rate = 10;
+        loan_position.maturity = 100;
+        loan_position.penalty_rate = 50;
+
+        let mut variable: LoanPosition = zeroed();
+        variable.principal = 1_000_000;
+        variable.rate_mode = RATE_MODE_VARIABLE;
+        let year = 31_536_000;
+        let variable_due = loan_interest_due(&variable, &protocol_state, year).unwrap();
+        assert!(loan_interest_due(&loan_position, &protocol_state, year).unwrap() > variable_due);
+
+        switch_to_variable_rate(&mut loan_position, 4);
+        assert_eq!(loan_position.rate_mode, RATE_MODE_VARIABLE);
+        assert_eq!(loan_position.interest_rate, 4);
+        assert_eq!((loan_position.maturity, loan_position.penalty_rate), (0, 0));
+        // Past the old maturity the loan accrues like any variable loan, with no penalty.
+        assert_eq!(
+            loan_interest_due(&loan_position, &protocol_state, year).unwrap(),
+            variable_due
+        );
+    }
+
+    #[test]
+    fn borrow_rate_limit_window() {
+        let mut rate_limit: BorrowRateLimit = zeroed();
+        rate_limit.max_borrow_per_window = 1_000;
+        rate_limit.window_seconds = 60;
+        rate_limit.window_start = 100;
+
+        // Inside the limit, borrows accumulate up to exactly the cap.
+        apply_borrow_rate_limit(&mut rate_limit, 600, 110).unwrap();
+        apply_borrow_rate_limit(&mut rate_limit, 400, 159).unwrap();
+        assert_eq!(rate_limit.borrowed_in_window, 1_000);
+        // Over the limit is rejected and leaves the window untouched.
+        assert!(apply_borrow_rate_limit(&mut rate_limit, 1, 159).is_err());
+        assert_eq!(rate_limit.borrowed_in_window, 1_000);
+        // Once the window elapses a new one starts from zero.
+        apply_borrow_rate_limit(&mut rate_limit, 700, 160).unwrap();
+        assert_eq!(rate_limit.window_start, 160);
+        assert_eq!(rate_limit.borrowed_in_window, 700);
+    }
+
+    #[test]
+    fn protocol_leverage_limit() {
+        let mut protocol_state = blank_protocol_state();
+        protocol_state.total_collateral = 10_000;
+        protocol_state.total_loans = 4_000;
+        protocol_state.max_protocol_leverage_bps = 5_000;
+        // Loans may reach exactly half the collateral, but not a unit more.
+        assert!(require_protocol_leverage(&protocol_state, 1_000).is_ok());
+        assert!(require_protocol_leverage(&protocol_state, 1_001).is_err());
+        // A zero limit disables the check.
+        protocol_state.max_protocol_leverage_bps = 0;
+        assert!(require_protocol_leverage(&protocol_state, u64::MAX).is_ok());
+    }
+
+    #[test]
+    fn early_repay_rebate_window() {
+        let mut protocol_state = blank_protocol_state();
+        protocol_state.early_repay_window = 3_600;
+        protocol_state.early_repay_rebate_bps = 3_333;
+        let mut treasury: ProtocolTreasury = zeroed();
+        treasury.total_fees_collected = 1_000_000;
+        let mut loan_position: LoanPosition = zeroed();
+        loan_position.borrow_timestamp = 1_000;
+        loan_position.origination_fee = 1_001;
+
+        // 33.33% of 1_001 is 333.63, rounded down against the borrower.
+        let rebate = |now| early_repay_rebate(&loan_position, &protocol_state, &treasury, now);
+        assert_eq!(rebate(1_000 + 3_600), 333);
+        assert_eq!(rebate(1_000 + 3_601), 0);
+        // The rebate never exceeds the treasury's fee reserves.
+        treasury.total_fees_collected = 100;
+        assert_eq!(
+            early_repay_rebate(&loan_position, &protocol_state, &treasury, 1_000),
+            100
+        );
+    }
+
+    #[test]
+    fn protection_buffer_draws() {
+        let collateral_pool: CollateralPool = zeroed();
+        let price = ORACLE_PRICE_ONE;
+        let mut borrower_account: BorrowerAccount = zeroed();
+        borrower_account.encrypted_principal.value = 1_000;
+        borrower_account.encrypted_collateral.value = 1_000;
+
+        // A partial draw takes just the 500 that restores 150%, leaving the rest.
+        borrower_account.protection_buffer = 1_000;
+        let drawn =
+            draw_protection_buffer(&mut borrower_account, &collateral_pool, price, 15_000).unwrap();
+        assert_eq!(drawn, 500);
+        assert_eq!(borrower_account.protection_buffer, 500);
+        assert_eq!(borrower_account.encrypted_collateral.value, 1_500);
+        // The restored position draws nothing more.
+        let drawn =
+            draw_protection_buffer(&mut borrower_account, &collateral_pool, price, 15_000).unwrap();
+        assert_eq!(drawn, 0);
+
+        // A buffer too small to restore health is drawn in full and left exhausted.
+        borrower_account.encrypted_collateral.value = 1_000;
+        borrower_account.protection_buffer = 200;
+        let drawn =
+            draw_protection_buffer(&mut borrower_account, &collateral_pool, price, 15_000).unwrap();
+        assert_eq!(drawn, 200);
+        assert_eq!(borrower_account.protection_buffer, 0);
+        assert_eq!(borrower_account.encrypted_collateral.value, 1_200);
+    }
+
+    #[test]
+    fn liquidation_grace_scales_with_debt_and_is_capped() {
+        let mut protocol_state = blank_protocol_state();
+        protocol_state.total_loans = 60_000;
+        protocol_state.total_liquidity = 40_000;
+        protocol_state.grace_tiers[..3].copy_from_slice(&[
+            GraceTier { min_pool_share_bps: 0, grace_slots: 10 },
+            GraceTier { min_pool_share_bps: 1_000, grace_slots: 50 },
+            GraceTier { min_pool_share_bps: 5_000, grace_slots: 200 },
+        ]);
+        protocol_state.grace_tier_count = 3;
+
+        assert_eq!(liquidation_grace_slots(9_999, &protocol_state), 10);
+        assert_eq!(liquidation_grace_slots(10_000, &protocol_state), 50);
+        assert_eq!(liquidation_grace_slots(50_000, &protocol_state), 200);
+        // Debt beyond the pool's size stays capped at the top tier.
+        assert_eq!(liquidation_grace_slots(u64::MAX, &protocol_state), 200);
+        // An empty pool counts any debt as the whole pool.
+        protocol_state.total_loans = 0;
+        protocol_state.total_liquidity = 0;
+        assert_eq!(liquidation_grace_slots(1, &protocol_state), 200);
+    }
+
+    #[test]
+    fn realized_supply_apy() {
+        let mut pool: LendingPool = zeroed();
+        pool.total_liquidity = 2_000_000;
+        pool.protocol_owned_liquidity = 1_000_000;
+        pool.rewards_window_start = 1_000;
+
+        // Zero elapsed with nothing distributed reports zero rather than dividing by zero.
+        assert_eq!(realized_supply_apy_bps(&pool, 1_000), 0);
+        // 274 over a day on 1_000_000 of lender liquidity annualizes to 10%.
+        pool.rewards_accrued_in_window = 274;
+        assert_eq!(realized_supply_apy_bps(&pool, 1_000 + 86_400), 1_000);
+        // A stale window reports nothing.
+        assert_eq!(
+            realized_supply_apy_bps(&pool, 1_000 + APY_WINDOW_SECONDS + 1),
+            0
+        );
+    }
+
+    #[test]
+    fn max_utilization_boundary() {
+        let mut protocol_state = blank_protocol_state();
+        protocol_state.total_liquidity = 18_100;
+        protocol_state.max_utilization = 80;
+        // 8_099 borrowed against 10_001 left is 80% utilized; 8_100 against 10_000 is 81%.
+        assert!(require_max_utilization(&protocol_state, 8_099).is_ok());
+        assert!(require_max_utilization(&protocol_state, 8_100).is_err());
+        // A zero cap disables the check.
+        protocol_state.max_utilization = 0;
+        assert!(require_max_utilization(&protocol_state, 18_100).is_ok());
+    }
+
+    #[test]
+    fn collateral_lock_time_tiers() {
+        let mut protocol_state = blank_protocol_state();
+        protocol_state.min_collateral_lock_time = 600;
+        protocol_state.lock_tiers[..3].copy_from_slice(&[
+            LockTier { min_reputation_score: 100, lock_time: 300 },
+            LockTier { min_reputation_score: 500, lock_time: 0 },
+            LockTier { min_reputation_score: 900, lock_time: 900 },
+        ]);
+        protocol_state.lock_tier_count = 3;
+        let mut reputation: BorrowerReputation = zeroed();
+        let mut lock_time = |score| {
+            reputation.zk_reputation_score = score;
+            collateral_lock_time(&protocol_state, Some(&reputation))
+        };
+
+        assert_eq!(lock_time(99), 600);
+        assert_eq!(lock_time(100), 300);
+        assert_eq!(lock_time(499), 300);
+        assert_eq!(lock_time(500), 0);
+        // A tier never lengthens the lock past the protocol minimum.
+        assert_eq!(lock_time(900), 600);
+        // Without a reputation account the score is zero.
+        assert_eq!(collateral_lock_time(&protocol_state, None), 600);
+    }
+
+    #[test]
+    fn collateral_factor_decay_schedule() {
+        let mut collateral_pool: CollateralPool = zeroed();
+        collateral_pool.collateral_factor = 8_000;
+        collateral_pool.factor_decay_start = 1_000_000;
+        collateral_pool.factor_decay_bps_per_day = 500;
+        collateral_pool.min_collateral_factor = 6_000;
+        let start = collateral_pool.factor_decay_start;
+
+        // Before the ramp the listed factor applies.
+        assert_eq!(effective_collateral_factor(&collateral_pool, start - 1), 8_000);
+        // During it the factor falls per full day elapsed.
+        assert_eq!(effective_collateral_factor(&collateral_pool, start + 86_399), 8_000);
+        assert_eq!(effective_collateral_factor(&collateral_pool, start + 86_400), 7_500);
+        assert_eq!(effective_collateral_factor(&collateral_pool, start + 3 * 86_400), 6_500);
+        // After it the factor stays at its floor.
+        assert_eq!(effective_collateral_factor(&collateral_pool, start + 4 * 86_400), 6_000);
+        assert_eq!(effective_collateral_factor(&collateral_pool, i64::MAX), 6_000);
+    }
+
+    #[test]
+    fn wide_confidence_reduces_borrowing_power() {
+        let key = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let mut collateral_pool: CollateralPool = zeroed();
+        collateral_pool.oracle_kind = ORACLE_KIND_PYTH;
+        collateral_pool.oracle = key;
+        let now: i64 = 1_700_000_000;
+        let price_with_conf = |conf: u64, side: u8| {
+            let mut data = vec![0u8; 240];
+            data[0..4].copy_from_slice(&0xa1b2_c3d4u32.to_le_bytes());
+            data[20..24].copy_from_slice(&(-8i32).to_le_bytes());
+            data[96..104].copy_from_slice(&now.to_le_bytes());
+            data[208..216].copy_from_slice(&150_000_000i64.to_le_bytes());
+            data[216..224].copy_from_slice(&conf.to_le_bytes());
+            let mut lamports = 0;
+            let info =
+                AccountInfo::new(&key, false, false, &mut lamports, &mut data, &owner, false, 0);
+            collateral_price(&collateral_pool, Some(&info), side, 20_000, now).unwrap()
+        };
+
+        // A $1.50 price with a $0.01 interval, shaded by two intervals either way.
+        let tight = price_with_conf(1_000_000, PRICE_SIDE_BORROW);
+        let wide = price_with_conf(10_000_000, PRICE_SIDE_BORROW);
+        assert_eq!(tight, 1_480_000);
+        assert_eq!(wide, 1_300_000);
+        assert!(
+            borrowing_power(1_000, &collateral_pool, wide, now).unwrap()
+                < borrowing_power(1_000, &collateral_pool, tight, now).unwrap()
+        );
+        // Liquidations value debt at the upper bound, so collateral is worth less there too.
+        assert_eq!(price_with_conf(1_000_000, PRICE_SIDE_LIQUIDATION), 1_480_263);
+        // An interval wider than the price leaves the collateral no borrowing power at all.
+        assert_eq!(price_with_conf(100_000_000, PRICE_SIDE_BORROW), 0);
+    }
+
+    #[test]
+    fn interest_free_period_accrues_nothing() {
+        let mut protocol_state = blank_protocol_state();
+        protocol_state.interest_free_seconds = 86_400;
+        let mut loan_position: LoanPosition = zeroed();
+        loan_position.principal = 1_000_000;
+        loan_position.rate_mode = RATE_MODE_FIXED;
+        loan_position.interest_rate = 10;
+        loan_position.borrow_timestamp = 1_000;
+        loan_position.interest_accrued_at = 1_000;
+
+        let due = |now| loan_interest_due_ray(&loan_position, &protocol_state, now).unwrap();
+        assert_eq!(due(1_000 + 86_400), 0);
+        // Interest starts only once the period ends: a year later 10% is owed.
+        assert_eq!(due(1_000 + 86_400 + 31_536_000), 100_000 * RAY);
+    }
+
+    #[test]
+    fn penalty_rate_applies_only_after_maturity() {
+        let protocol_state = blank_protocol_state();
+        let year = 31_536_000;
+        let mut borrower_account: BorrowerAccount = zeroed();
+        borrower_account.encrypted_principal.value = 1_000_000;
+        let mut loan_position: LoanPosition = zeroed();
+        loan_position.principal = 1_000_000;
+        loan_position.rate_mode = RATE_MODE_FIXED;
+        loan_position.interest_rate = 10;
+        loan_position.maturity = year;
+        loan_position.penalty_rate = 20;
+
+        // Up to maturity the loan accrues at its own 10%.
+        accrue_loan_interest(&mut loan_position, &mut borrower_account, &protocol_state, year)
+            .unwrap();
+        assert_eq!(loan_position.accrued_interest, 100_000);
+        // Past it the 20% penalty rate applies to the whole balance.
+        accrue_loan_interest(&mut loan_position, &mut borrower_account, &protocol_state, 2 * year)
+            .unwrap();
+        assert_eq!(loan_position.accrued_interest, 100_000 + 220_000);
+        assert_eq!(borrower_account.accrued_interest, 320_000);
+    }
+
+    #[test]
+    fn liquidity_buffer_boundary() {
+        let mut protocol_state = blank_protocol_state();
+        protocol_state.total_liquidity = 10_000;
+        let mut lending_pool: LendingPool = zeroed();
+        lending_pool.min_liquidity_buffer = 2_000;
+
+        // Borrows may draw liquidity down to the buffer, but not into it.
+        assert!(require_liquidity(&protocol_state, &lending_pool, 8_000).is_ok());
+        assert_eq!(
+            require_liquidity(&protocol_state, &lending_pool, 8_001).unwrap_err(),
+            ZKError::BufferReserved.into()
+        );
+        assert_eq!(
+            require_liquidity(&protocol_state, &lending_pool, 10_001).unwrap_err(),
+            ZKError::InsufficientLiquidity.into()
+        );
+    }
+
+    #[test]
+    fn utilization_kink_crossings() {
+        let mut protocol_state = blank_protocol_state();
+        protocol_state.optimal_utilization = 80;
+        protocol_state.total_liquidity = 1_000;
+
+        // Staying below the kink updates utilization without a crossing.
+        protocol_state.total_loans = 500;
+        update_utilization(&mut protocol_state);
+        assert_eq!(protocol_state.utilization_rate, 50);
+        assert_eq!(kink_crossing(0, 50, 80), None);
+        assert_eq!(kink_crossing(50, 80, 80), None);
+        // Crossing it either way reports the direction.
+        protocol_state.total_loans = 900;
+        update_utilization(&mut protocol_state);
+        assert_eq!(protocol_state.utilization_rate, 90);
+        assert_eq!(kink_crossing(50, 90, 80), Some(KINK_DIRECTION_UP));
+        assert_eq!(kink_crossing(90, 80, 80), Some(KINK_DIRECTION_DOWN));
+        assert_eq!(kink_crossing(90, 95, 80), None);
+    }
+
+    #[test]
+    fn treasury_fees_overflow_reserve_cap_into_governance_fund() {
+        let mut treasury: ProtocolTreasury = zeroed();
+        treasury.reserve_cap = 1_000;
+        treasury.total_fees_collected = 900;
+
+        // The first 100 tops the reserve up to its cap; the rest goes to the governance fund.
+        credit_treasury_fee(&mut treasury, 250).unwrap();
+        assert_eq!(treasury.total_fees_collected, 1_000);
+        assert_eq!(treasury.governance_fund, 150);
+        // Once the reserve is full every fee goes to the governance fund.
+        credit_treasury_fee(&mut treasury, 40).unwrap();
+        assert_eq!(treasury.total_fees_collected, 1_000);
+        assert_eq!(treasury.governance_fund, 190);
+    }
+
+    #[test]
+    fn repay_all_settles_interest_then_principal_across_loans() {
+        let mut protocol_state = blank_protocol_state();
+        protocol_state.total_loans = 3_000;
+        let mut lending_pool: LendingPool = zeroed();
+        let mut borrower_account: BorrowerAccount = zeroed();
+        borrower_account.encrypted_principal.value = 3_000;
+        borrower_account.accrued_interest = 300;
+        borrower_account.active_loans = 3;
+        let loan = |loan_id, borrow_timestamp, accrued_interest| {
+            let mut loan_position: LoanPosition = zeroed();
+            loan_position.loan_id = loan_id;
+            loan_position.principal = 1_000;
+            loan_position.accrued_interest = accrued_interest;
+            loan_position.borrow_timestamp = borrow_timestamp;
+            Box::new(loan_position)
+        };
+        let loans = vec![
+            (loan(1, 30, 100), 5, 1_100),
+            (loan(2, 10, 150), 9, 1_150),
+            (loan(3, 20, 50), 7, 1_050),
+        ];
+
+        // Highest rate first: loans 2 and 3 fit in 2_500, loan 1 does not.
+        let (repaid, used) = plan_repay_all(loans, REPAY_ORDER_HIGHEST_RATE, 2_500);
+        assert_eq!(used, 2_200);
+        let ids: Vec<u64> = repaid.iter().map(|(loan, _)| loan.loan_id).collect();
+        assert_eq!(ids, [2, 3]);
+        for (loan_position, total_due) in &repaid {
+            apply_repayment(
+                &mut borrower_account,
+                &mut protocol_state,
+                &mut lending_pool,
+                loan_position.principal,
+                loan_position.accrued_interest,
+                *total_due,
+                0,
+            )
+            .unwrap();
+        }
+        // Each repaid loan clears its interest and its principal; loan 1's debt is untouched.
+        assert_eq!(borrower_account.accrued_interest, 100);
+        assert_eq!(borrower_account.encrypted_principal.value, 1_000);
+        assert_eq!(borrower_account.active_loans, 1);
+        assert_eq!(protocol_state.total_loans, 1_000);
+        assert_eq!(protocol_state.total_liquidity, 2_200);
+
+        // Oldest first stops at loan 3, which 1_200 can't cover, though loan 1 would fit.
+        let loans = vec![(loan(1, 30, 100), 5, 1_100), (loan(3, 20, 50), 7, 1_500)];
+        let (repaid, used) = plan_repay_all(loans, REPAY_ORDER_OLDEST, 1_200);
+        assert!(repaid.is_empty());
+        assert_eq!(used, 0);
+    }
+}